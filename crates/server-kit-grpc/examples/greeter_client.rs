@@ -35,7 +35,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing::info!(endpoint = %config.endpoint, "Connecting to gRPC server");
 
     // Connect to the server using the ChannelExt trait
-    let channel: Channel = Channel::connect(&config).await?;
+    let channel = Channel::connect(&config).await?;
 
     // Create the client
     let mut client = GreeterClient::new(channel);
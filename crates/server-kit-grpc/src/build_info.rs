@@ -0,0 +1,202 @@
+//! Build info service.
+//!
+//! Exposes [`BuildInfo`] over gRPC as `server_kit.v1.BuildInfo/GetBuildInfo`.
+//! The protobuf definition ships in `proto/build_info.proto` for clients:
+//!
+//! ```text
+//! service BuildInfo {
+//!   rpc GetBuildInfo(GetBuildInfoRequest) returns (BuildInfoResponse);
+//! }
+//! ```
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use server_kit::BuildInfo;
+use tonic::body::BoxBody;
+use tonic::codec::ProstCodec;
+use tonic::server::{Grpc, NamedService};
+use tonic::{Request, Response, Status};
+
+const GET_BUILD_INFO_PATH: &str = "/server_kit.v1.BuildInfo/GetBuildInfo";
+
+/// Request message for `GetBuildInfo`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct GetBuildInfoRequest {}
+
+/// Response message for `GetBuildInfo`.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BuildInfoResponse {
+    #[prost(string, tag = "1")]
+    pub name: String,
+    #[prost(string, tag = "2")]
+    pub version: String,
+    #[prost(string, tag = "3")]
+    pub git_sha: String,
+    #[prost(string, tag = "4")]
+    pub build_timestamp: String,
+    #[prost(string, tag = "5")]
+    pub rustc_version: String,
+    #[prost(string, repeated, tag = "6")]
+    pub features: Vec<String>,
+}
+
+impl From<&BuildInfo> for BuildInfoResponse {
+    fn from(info: &BuildInfo) -> Self {
+        Self {
+            name: info.name.clone(),
+            version: info.version.clone(),
+            git_sha: info.git_sha.clone().unwrap_or_default(),
+            build_timestamp: info.build_timestamp.clone().unwrap_or_default(),
+            rustc_version: info.rustc_version.clone().unwrap_or_default(),
+            features: info.features.clone(),
+        }
+    }
+}
+
+/// Create the build info service and register `info` as the process build info.
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit_grpc::{build_info, build_info_service};
+///
+/// Server::builder()
+///     .add_service(build_info_service(build_info!()))
+///     .add_service(my_service)
+///     .serve_with(&config)
+///     .await?;
+/// ```
+pub fn build_info_service(info: BuildInfo) -> BuildInfoServer {
+    BuildInfoServer {
+        info: Arc::new(info.register().into()),
+    }
+}
+
+/// gRPC server for `server_kit.v1.BuildInfo`.
+#[derive(Clone)]
+pub struct BuildInfoServer {
+    info: Arc<BuildInfoResponse>,
+}
+
+impl NamedService for BuildInfoServer {
+    const NAME: &'static str = "server_kit.v1.BuildInfo";
+}
+
+impl<B> tower::Service<http::Request<B>> for BuildInfoServer
+where
+    B: http_body::Body + Send + 'static,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: http::Request<B>) -> Self::Future {
+        if req.uri().path() != GET_BUILD_INFO_PATH {
+            let status = Status::unimplemented(format!("unknown method {}", req.uri().path()));
+            return Box::pin(async move { Ok(status.into_http()) });
+        }
+
+        let handler = GetBuildInfo {
+            info: Arc::clone(&self.info),
+        };
+        Box::pin(async move {
+            let mut grpc = Grpc::new(ProstCodec::<BuildInfoResponse, GetBuildInfoRequest>::default());
+            Ok(grpc.unary(handler, req).await)
+        })
+    }
+}
+
+struct GetBuildInfo {
+    info: Arc<BuildInfoResponse>,
+}
+
+impl tower::Service<Request<GetBuildInfoRequest>> for GetBuildInfo {
+    type Response = Response<BuildInfoResponse>;
+    type Error = Status;
+    type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _req: Request<GetBuildInfoRequest>) -> Self::Future {
+        std::future::ready(Ok(Response::new(self.info.as_ref().clone())))
+    }
+}
+
+/// Publish the registered build info as a `build_info` gauge.
+#[cfg(feature = "metrics")]
+pub(crate) fn record_build_info() {
+    if let Some(info) = BuildInfo::get() {
+        let labels = info.labels();
+        metrics::gauge!("build_info", &labels[..]).set(1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::{Buf, BufMut, Bytes, BytesMut};
+    use http_body_util::{BodyExt, Full};
+    use prost::Message;
+    use tower::ServiceExt;
+
+    fn grpc_frame(message: &impl Message) -> Bytes {
+        let encoded = message.encode_to_vec();
+        let mut frame = BytesMut::with_capacity(5 + encoded.len());
+        frame.put_u8(0);
+        frame.put_u32(encoded.len() as u32);
+        frame.put_slice(&encoded);
+        frame.freeze()
+    }
+
+    fn request(path: &str) -> http::Request<Full<Bytes>> {
+        http::Request::builder()
+            .method("POST")
+            .uri(path)
+            .header("content-type", "application/grpc")
+            .body(Full::new(grpc_frame(&GetBuildInfoRequest {})))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn returns_build_info() {
+        let server = BuildInfoServer {
+            info: Arc::new(BuildInfoResponse::from(
+                &BuildInfo::new("app", "1.2.3").with_build_env(None, None, None, Some("tls")),
+            )),
+        };
+
+        let response = server.oneshot(request(GET_BUILD_INFO_PATH)).await.unwrap();
+        let mut body = response.into_body().collect().await.unwrap().to_bytes();
+        body.advance(5);
+        let message = BuildInfoResponse::decode(body).unwrap();
+
+        assert_eq!(message.name, "app");
+        assert_eq!(message.version, "1.2.3");
+        assert_eq!(message.features, vec!["tls"]);
+    }
+
+    #[tokio::test]
+    async fn unknown_method_is_unimplemented() {
+        let server = BuildInfoServer {
+            info: Arc::new(BuildInfoResponse::default()),
+        };
+
+        let response = server
+            .oneshot(request("/server_kit.v1.BuildInfo/Other"))
+            .await
+            .unwrap();
+        let status = Status::from_header_map(response.headers()).unwrap();
+        assert_eq!(status.code(), tonic::Code::Unimplemented);
+    }
+}
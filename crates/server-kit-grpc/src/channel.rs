@@ -1,14 +1,62 @@
 //! Channel extension trait for gRPC clients.
 
+use std::path::Path;
+use std::task::{Context, Poll};
 use std::time::Duration;
+
+use server_kit::{Deadline, UNIX_SCHEME};
 use tonic::transport::{Channel, Endpoint};
+use tower::Service;
 
 use crate::config::ChannelConfig;
 use crate::error::Error;
+use crate::headers;
+use crate::interceptor::{encode_grpc_timeout, parse_grpc_timeout};
+
+/// Authority sent to servers reached over a Unix domain socket.
+const UNIX_AUTHORITY: &str = "http://localhost";
+
+/// Socket path of a `unix:///path` endpoint.
+fn unix_socket_path(endpoint: &str) -> Option<&Path> {
+    endpoint.strip_prefix(UNIX_SCHEME).map(Path::new)
+}
+
+/// Connector dialing a Unix domain socket, whatever the request URI.
+#[cfg(unix)]
+fn unix_connector(
+    path: &Path,
+) -> impl tower::Service<
+    http::Uri,
+    Response = hyper_util::rt::TokioIo<tokio::net::UnixStream>,
+    Error = std::io::Error,
+    Future = impl Send,
+> + Send
+       + 'static {
+    let path = path.to_path_buf();
+    tower::service_fn(move |_: http::Uri| {
+        let path = path.clone();
+        async move {
+            let stream = tokio::net::UnixStream::connect(path).await?;
+            Ok(hyper_util::rt::TokioIo::new(stream))
+        }
+    })
+}
+
+#[cfg(not(unix))]
+fn unsupported_unix_endpoint() -> Error {
+    Error::InvalidEndpoint("Unix domain sockets are not supported on this platform".to_string())
+}
 
 /// Build an endpoint from configuration.
+///
+/// `unix://` endpoints get a placeholder `http://localhost` URI; the socket
+/// is dialed by [`unix_connector`].
 fn build_endpoint(config: &ChannelConfig) -> Result<Endpoint, Error> {
-    let mut endpoint = Endpoint::from_shared(config.endpoint.clone())
+    let uri = match unix_socket_path(&config.endpoint) {
+        Some(_) => UNIX_AUTHORITY.to_string(),
+        None => config.endpoint.clone(),
+    };
+    let mut endpoint = Endpoint::from_shared(uri)
         .map_err(|e| Error::InvalidEndpoint(e.to_string()))?
         .timeout(Duration::from_secs(config.timeout_secs))
         .connect_timeout(Duration::from_secs(config.connect_timeout_secs));
@@ -39,6 +87,69 @@ fn build_endpoint(config: &ChannelConfig) -> Result<Endpoint, Error> {
     Ok(endpoint)
 }
 
+/// Channel forwarding the current call's remaining time budget.
+///
+/// Calls made while a [`Deadline`] is current, i.e. from handlers behind
+/// `RequestTimeoutLayer` in either server crate, send the time left as
+/// `grpc-timeout`, or keep the one set with `Request::set_timeout` if it is
+/// shorter. The downstream server then gives up when the caller would.
+/// Generated clients accept it like a [`Channel`].
+///
+/// ```ignore
+/// let channel = Channel::connect_with_deadline(&config).await?;
+/// let client = MyServiceClient::new(channel);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DeadlineChannel<S = Channel> {
+    inner: S,
+}
+
+impl<S> DeadlineChannel<S> {
+    pub fn new(inner: S) -> Self {
+        Self { inner }
+    }
+
+    pub fn get_ref(&self) -> &S {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, B> Service<http::Request<B>> for DeadlineChannel<S>
+where
+    S: Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        if let Some(deadline) = Deadline::current() {
+            let remaining = deadline.remaining();
+            let requested = req
+                .headers()
+                .get(headers::GRPC_TIMEOUT.as_str())
+                .and_then(|v| v.to_str().ok())
+                .and_then(parse_grpc_timeout);
+            if requested.is_none_or(|requested| remaining < requested) {
+                let value = encode_grpc_timeout(remaining);
+                if let Ok(value) = http::HeaderValue::from_str(&value) {
+                    req.headers_mut()
+                        .insert(headers::GRPC_TIMEOUT.as_str(), value);
+                }
+            }
+        }
+        self.inner.call(req)
+    }
+}
+
 /// Extension trait for Channel (mirrors RouterExt pattern from server-kit).
 ///
 /// # Example
@@ -54,6 +165,10 @@ fn build_endpoint(config: &ChannelConfig) -> Result<Endpoint, Error> {
 /// let channel = Channel::connect(&config).await?;
 /// let client = MyServiceClient::new(channel);
 /// ```
+///
+/// Use [`connect_with_deadline`](Self::connect_with_deadline) or
+/// [`connect_lazy_with_deadline`](Self::connect_lazy_with_deadline) to have
+/// calls made while handling a request carry its remaining deadline.
 pub trait ChannelExt: Sized {
     /// Connect to server with config (eager connection).
     ///
@@ -67,17 +182,53 @@ pub trait ChannelExt: Sized {
     /// This creates a channel that will connect when the first request is made.
     /// Useful when you want to create the client but delay the actual connection.
     fn connect_lazy(config: &ChannelConfig) -> Result<Channel, Error>;
+
+    /// Connect like [`connect`](Self::connect), forwarding the current
+    /// call's remaining time budget through a [`DeadlineChannel`].
+    fn connect_with_deadline(
+        config: &ChannelConfig,
+    ) -> impl std::future::Future<Output = Result<DeadlineChannel, Error>> + Send;
+
+    /// Connect like [`connect_lazy`](Self::connect_lazy), forwarding the
+    /// current call's remaining time budget through a [`DeadlineChannel`].
+    fn connect_lazy_with_deadline(config: &ChannelConfig) -> Result<DeadlineChannel, Error>;
 }
 
 impl ChannelExt for Channel {
     async fn connect(config: &ChannelConfig) -> Result<Channel, Error> {
         let endpoint = build_endpoint(config)?;
-        endpoint.connect().await.map_err(Error::from)
+        let channel = match unix_socket_path(&config.endpoint) {
+            #[cfg(unix)]
+            Some(path) => {
+                endpoint
+                    .connect_with_connector(unix_connector(path))
+                    .await?
+            }
+            #[cfg(not(unix))]
+            Some(_) => return Err(unsupported_unix_endpoint()),
+            None => endpoint.connect().await?,
+        };
+        Ok(channel)
     }
 
     fn connect_lazy(config: &ChannelConfig) -> Result<Channel, Error> {
         let endpoint = build_endpoint(config)?;
-        Ok(endpoint.connect_lazy())
+        let channel = match unix_socket_path(&config.endpoint) {
+            #[cfg(unix)]
+            Some(path) => endpoint.connect_with_connector_lazy(unix_connector(path)),
+            #[cfg(not(unix))]
+            Some(_) => return Err(unsupported_unix_endpoint()),
+            None => endpoint.connect_lazy(),
+        };
+        Ok(channel)
+    }
+
+    async fn connect_with_deadline(config: &ChannelConfig) -> Result<DeadlineChannel, Error> {
+        Self::connect(config).await.map(DeadlineChannel::new)
+    }
+
+    fn connect_lazy_with_deadline(config: &ChannelConfig) -> Result<DeadlineChannel, Error> {
+        Self::connect_lazy(config).map(DeadlineChannel::new)
     }
 }
 
@@ -111,4 +262,142 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn deadline_channel_forwards_the_remaining_budget() {
+        use tower::ServiceExt;
+
+        let svc = DeadlineChannel::new(tower::service_fn(|req: http::Request<()>| async move {
+            let timeout = req
+                .headers()
+                .get("grpc-timeout")
+                .map(|v| v.to_str().unwrap());
+            Ok::<_, std::convert::Infallible>(timeout.and_then(parse_grpc_timeout))
+        }));
+        let request = |timeout: Option<&str>| {
+            let mut req = http::Request::new(());
+            if let Some(timeout) = timeout {
+                req.headers_mut()
+                    .insert("grpc-timeout", timeout.parse().unwrap());
+            }
+            req
+        };
+
+        assert_eq!(svc.clone().oneshot(request(None)).await.unwrap(), None);
+
+        let deadline = Deadline::after(Duration::from_secs(5));
+        let (forwarded, shorter) = deadline
+            .scope(async {
+                (
+                    svc.clone().oneshot(request(None)).await.unwrap(),
+                    svc.clone().oneshot(request(Some("100m"))).await.unwrap(),
+                )
+            })
+            .await;
+        let forwarded = forwarded.unwrap();
+        assert!(forwarded <= Duration::from_secs(5));
+        assert!(forwarded > Duration::from_secs(4));
+        assert_eq!(shorter, Some(Duration::from_millis(100)));
+    }
+
+    #[cfg(feature = "health")]
+    #[tokio::test]
+    async fn connect_with_deadline_sends_grpc_timeout() {
+        use crate::{GrpcServerConfig, RouterExt};
+        use std::sync::{Arc, Mutex};
+        use tonic_health::pb::health_client::HealthClient;
+        use tonic_health::pb::HealthCheckRequest;
+
+        let config = GrpcServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            ..Default::default()
+        };
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let capture = {
+            let seen = Arc::clone(&seen);
+            tower::util::MapRequestLayer::new(move |req: http::Request<tonic::body::BoxBody>| {
+                let timeout = req
+                    .headers()
+                    .get("grpc-timeout")
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_grpc_timeout);
+                seen.lock().unwrap().push(timeout);
+                req
+            })
+        };
+        let (_, health) = tonic_health::server::health_reporter();
+        let server = tonic::transport::Server::builder()
+            .layer(capture)
+            .add_service(health)
+            .spawn(&config)
+            .await
+            .unwrap();
+
+        let channel = Channel::connect_with_deadline(&ChannelConfig {
+            endpoint: format!("http://{}", server.local_addr().as_tcp().unwrap()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let mut client = HealthClient::new(channel);
+        client.check(HealthCheckRequest::default()).await.unwrap();
+        Deadline::after(Duration::from_secs(5))
+            .scope(client.check(HealthCheckRequest::default()))
+            .await
+            .unwrap();
+
+        let seen = seen.lock().unwrap().clone();
+        assert_eq!(seen.len(), 2);
+        assert_eq!(seen[0], None);
+        let forwarded = seen[1].unwrap();
+        assert!(forwarded <= Duration::from_secs(5));
+        assert!(forwarded > Duration::from_secs(4));
+
+        server.stop().await.unwrap();
+    }
+
+    #[test]
+    fn unix_endpoint_path() {
+        assert_eq!(
+            unix_socket_path("unix:///run/app.sock"),
+            Some(Path::new("/run/app.sock"))
+        );
+        assert_eq!(unix_socket_path("http://[::1]:50051"), None);
+    }
+
+    #[cfg(all(unix, feature = "health"))]
+    #[tokio::test]
+    async fn connects_over_unix_socket() {
+        use crate::{GrpcServerConfig, RouterExt};
+        use tonic_health::pb::health_client::HealthClient;
+        use tonic_health::pb::HealthCheckRequest;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("grpc.sock");
+        let config = GrpcServerConfig {
+            host: format!("unix://{}", path.display()),
+            ..Default::default()
+        };
+        let (_, health) = tonic_health::server::health_reporter();
+        let server = tonic::transport::Server::builder()
+            .add_service(health)
+            .spawn(&config)
+            .await
+            .unwrap();
+        assert_eq!(server.local_addr().as_unix(), Some(path.as_path()));
+
+        let channel = Channel::connect(&ChannelConfig {
+            endpoint: format!("unix://{}", path.display()),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let response = HealthClient::new(channel)
+            .check(HealthCheckRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().status, 1);
+
+        server.stop().await.unwrap();
+    }
 }
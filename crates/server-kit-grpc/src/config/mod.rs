@@ -7,4 +7,10 @@ pub use channel::{ChannelConfig, ChannelConfigBuilder};
 pub use server::GrpcServerConfig;
 
 // Re-export from core
-pub use server_kit::{ConfigBuilder, ConfigError, Environment};
+pub use server_kit::{
+    AccessLogConfig, AccessLogFormat, AccessLogOutput, AdaptiveConcurrencyConfig,
+    AuthorizationConfig, ConcurrencyConfig, ConfigBuilder, ConfigError, Environment,
+    ForwardedHeader, IpFilterConfig, IpNetwork, ProxyProtocolConfig, RateLimitConfig,
+    RateLimitKey, RateLimitQuota, RouteConcurrency, RoutePolicy, RouteRateLimit, RouteTimeout,
+    TrustedProxies,
+};
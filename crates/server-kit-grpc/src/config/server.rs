@@ -1,26 +1,70 @@
 //! gRPC server configuration.
 
 use serde::{Deserialize, Serialize};
+use server_kit::ListenAddr;
 use std::net::SocketAddr;
 use std::time::Duration;
 
-pub use server_kit::{ConfigBuilder, Environment};
+pub use server_kit::{
+    AccessLogConfig, AuthorizationConfig, ConcurrencyConfig, ConfigBuilder, Environment,
+    IpFilterConfig, ProxyProtocolConfig, RateLimitConfig, RouteTimeout, TrustedProxies,
+};
 
 /// gRPC server configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct GrpcServerConfig {
     pub environment: Environment,
+    /// Host to bind, or `unix:///path/to/socket` to listen on a Unix
+    /// domain socket (the port is then ignored).
     pub host: String,
     pub port: u16,
-    /// Request timeout in seconds.
+    /// `FileDescriptorName=` of the systemd socket to serve on when started
+    /// by socket activation. Without a name the first inherited socket is
+    /// used; with none inherited, `host` and `port` are bound as usual.
+    pub listen_fd_name: Option<String>,
+    /// Call timeout in seconds, applied by `ServerExt::with_timeouts`
+    /// together with the client's `grpc-timeout`; `0` disables it.
     pub request_timeout_secs: u64,
+    /// Timeout overrides for methods starting with a prefix, such as
+    /// `/pkg.Service/`; the first match wins.
+    pub method_timeouts: Vec<RouteTimeout>,
     /// Maximum concurrent streams per connection.
     pub max_concurrent_streams: Option<u32>,
     /// TCP keepalive interval in seconds.
     pub tcp_keepalive_secs: Option<u64>,
     /// Enable TCP nodelay.
     pub tcp_nodelay: bool,
+    /// Hand listening sockets to a re-executed process on SIGUSR2, then
+    /// drain and exit once it is ready (Unix only).
+    pub hot_restart: bool,
+    /// How long the re-executed process has to become ready.
+    pub hot_restart_timeout_secs: u64,
+    /// How long in-flight calls may take to finish after shutdown starts.
+    pub shutdown_timeout_secs: u64,
+    /// Pre-stop delay: after the shutdown signal, health reports NOT_SERVING
+    /// but new connections are still accepted for this long.
+    pub shutdown_delay_secs: u64,
+    /// Access log settings, used by `AccessLogLayer::from_config`.
+    pub access_log: AccessLogConfig,
+    /// PROXY protocol settings for TCP listeners. Disabled by default.
+    pub proxy_protocol: ProxyProtocolConfig,
+    /// Proxies (CIDRs or IPs) whose `x-forwarded-for` metadata (or
+    /// `forwarded`, if configured) is believed by `ClientIpLayer`. Empty
+    /// means the peer is the client.
+    pub trusted_proxies: TrustedProxies,
+    /// Allow/deny rules for `IpFilterInterceptor::from_config`.
+    pub ip_filter: IpFilterConfig,
+    /// Rate limits applied by `ServerExt::with_rate_limit` when enabled.
+    /// Routes are gRPC paths, so `methods` prefixes like `/pkg.Service/`
+    /// cover a whole service.
+    pub rate_limit: RateLimitConfig,
+    /// Concurrency limits and load shedding applied by
+    /// `ServerExt::with_concurrency_limit`.
+    pub concurrency: ConcurrencyConfig,
+    /// Required scopes and roles per method prefix, enforced by
+    /// `ServerExt::with_authorization`.
+    pub authorization: AuthorizationConfig,
     /// Path to TLS certificate (PEM format).
     #[cfg(feature = "tls")]
     pub tls_cert_path: Option<String>,
@@ -38,10 +82,23 @@ impl Default for GrpcServerConfig {
             environment: Environment::default(),
             host: "[::1]".to_string(),
             port: 50051,
+            listen_fd_name: None,
             request_timeout_secs: 30,
+            method_timeouts: Vec::new(),
             max_concurrent_streams: None,
             tcp_keepalive_secs: Some(60),
             tcp_nodelay: true,
+            hot_restart: false,
+            hot_restart_timeout_secs: 30,
+            shutdown_timeout_secs: 30,
+            shutdown_delay_secs: 0,
+            access_log: AccessLogConfig::default(),
+            proxy_protocol: ProxyProtocolConfig::default(),
+            trusted_proxies: TrustedProxies::default(),
+            ip_filter: IpFilterConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            concurrency: ConcurrencyConfig::default(),
+            authorization: AuthorizationConfig::default(),
             #[cfg(feature = "tls")]
             tls_cert_path: None,
             #[cfg(feature = "tls")]
@@ -76,11 +133,31 @@ impl GrpcServerConfig {
         self.addr().parse()
     }
 
+    /// Address to listen on, TCP or Unix socket.
+    pub fn listen_addr(&self) -> ListenAddr {
+        ListenAddr::from_host_port(&self.host, self.port)
+    }
+
     /// Get the request timeout duration.
     pub fn request_timeout(&self) -> Duration {
         Duration::from_secs(self.request_timeout_secs)
     }
 
+    /// Get the hot restart readiness deadline.
+    pub fn hot_restart_timeout(&self) -> Duration {
+        Duration::from_secs(self.hot_restart_timeout_secs)
+    }
+
+    /// Get the shutdown drain deadline.
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+
+    /// Get the pre-stop delay.
+    pub fn shutdown_delay(&self) -> Duration {
+        Duration::from_secs(self.shutdown_delay_secs)
+    }
+
     /// Get the TCP keepalive duration.
     pub fn tcp_keepalive(&self) -> Option<Duration> {
         self.tcp_keepalive_secs.map(Duration::from_secs)
@@ -156,6 +233,8 @@ mod tests {
         assert_eq!(config.request_timeout_secs, 30);
         assert!(config.tcp_nodelay);
         assert_eq!(config.tcp_keepalive_secs, Some(60));
+        assert!(!config.hot_restart);
+        assert_eq!(config.hot_restart_timeout(), Duration::from_secs(30));
     }
 
     #[test]
@@ -180,6 +259,22 @@ mod tests {
         assert_eq!(addr.to_string(), "127.0.0.1:50051");
     }
 
+    #[test]
+    fn grpc_server_config_unix_socket() {
+        let config = GrpcServerConfig {
+            host: "unix:///run/app.sock".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.listen_addr(),
+            ListenAddr::Unix("/run/app.sock".into())
+        );
+        assert_eq!(
+            GrpcServerConfig::default().listen_addr(),
+            ListenAddr::Tcp("[::1]:50051".to_string())
+        );
+    }
+
     #[test]
     fn grpc_server_config_request_timeout() {
         let config = GrpcServerConfig {
@@ -189,6 +284,21 @@ mod tests {
         assert_eq!(config.request_timeout(), Duration::from_secs(60));
     }
 
+    #[test]
+    fn grpc_server_config_shutdown_settings() {
+        let config = GrpcServerConfig::default();
+        assert_eq!(config.shutdown_timeout(), Duration::from_secs(30));
+        assert_eq!(config.shutdown_delay(), Duration::ZERO);
+
+        let config = GrpcServerConfig {
+            shutdown_timeout_secs: 10,
+            shutdown_delay_secs: 5,
+            ..Default::default()
+        };
+        assert_eq!(config.shutdown_timeout(), Duration::from_secs(10));
+        assert_eq!(config.shutdown_delay(), Duration::from_secs(5));
+    }
+
     #[test]
     fn grpc_server_config_tcp_keepalive() {
         let config = GrpcServerConfig::default();
@@ -216,6 +326,222 @@ mod tests {
         assert_eq!(config.port, 50052);
     }
 
+    #[test]
+    fn grpc_server_config_access_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[access_log]\nenabled = true\nformat = \"$request_uri $grpc_status\"\noutput = \"tracing\"",
+        )
+        .unwrap();
+
+        let config: GrpcServerConfig = GrpcServerConfig::builder()
+            .with_config_file(&path)
+            .build()
+            .unwrap();
+
+        assert!(config.access_log.enabled);
+        assert_eq!(
+            config.access_log.format,
+            server_kit::AccessLogFormat::Custom("$request_uri $grpc_status".to_string())
+        );
+        assert_eq!(config.access_log.output, server_kit::AccessLogOutput::Tracing);
+    }
+
+    #[test]
+    fn grpc_server_config_proxy_protocol() {
+        let config = GrpcServerConfig::default();
+        assert!(!config.proxy_protocol.enabled);
+        assert!(config.proxy_protocol.is_trusted("10.0.0.1".parse().unwrap()));
+        assert!(!config.proxy_protocol.is_trusted("203.0.113.7".parse().unwrap()));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[proxy_protocol]\nenabled = true\ntrusted_sources = [\"203.0.113.0/24\"]\nheader_timeout_secs = 2",
+        )
+        .unwrap();
+
+        let config: GrpcServerConfig = GrpcServerConfig::builder()
+            .with_config_file(&path)
+            .build()
+            .unwrap();
+
+        assert!(config.proxy_protocol.enabled);
+        assert!(config.proxy_protocol.is_trusted("203.0.113.7".parse().unwrap()));
+        assert!(!config.proxy_protocol.is_trusted("10.0.0.1".parse().unwrap()));
+        assert_eq!(config.proxy_protocol.header_timeout(), Duration::from_secs(2));
+    }
+
+    #[test]
+    fn grpc_server_config_trusted_proxies() {
+        assert!(GrpcServerConfig::default().trusted_proxies.is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, "trusted_proxies = [\"10.0.0.0/8\"]").unwrap();
+
+        let config: GrpcServerConfig = GrpcServerConfig::builder()
+            .with_config_file(&path)
+            .build()
+            .unwrap();
+
+        assert!(config.trusted_proxies.is_trusted("10.1.1.1".parse().unwrap()));
+        assert!(!config.trusted_proxies.is_trusted("11.1.1.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn grpc_server_config_ip_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            "[ip_filter]\nallow = [\"10.8.0.0/16\"]\ndeny = [\"10.8.99.0/24\"]",
+        )
+        .unwrap();
+
+        let config: GrpcServerConfig = GrpcServerConfig::builder()
+            .with_config_file(&path)
+            .build()
+            .unwrap();
+
+        assert!(config.ip_filter.is_allowed("10.8.1.1".parse().unwrap()));
+        assert!(!config.ip_filter.is_allowed("10.8.99.1".parse().unwrap()));
+        assert!(!config.ip_filter.is_allowed("10.9.0.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn grpc_server_config_rate_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+[rate_limit]
+enabled = true
+requests = 50
+key = "subject"
+
+[[rate_limit.methods]]
+method = "/admin.Admin/"
+requests = 10
+period_secs = 60
+"#,
+        )
+        .unwrap();
+
+        let config: GrpcServerConfig = GrpcServerConfig::builder()
+            .with_config_file(&path)
+            .build()
+            .unwrap();
+
+        assert!(config.rate_limit.enabled);
+        assert_eq!(config.rate_limit.key, crate::RateLimitKey::Subject);
+        assert_eq!(
+            config.rate_limit.quota(),
+            Some(crate::RateLimitQuota::per_second(50))
+        );
+        assert_eq!(config.rate_limit.routes[0].prefix, "/admin.Admin/");
+        assert!(!GrpcServerConfig::default().rate_limit.enabled);
+    }
+
+    #[test]
+    fn grpc_server_config_concurrency() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+[concurrency]
+enabled = true
+max_in_flight = 100
+queue_timeout_ms = 200
+
+[[concurrency.methods]]
+method = "/reports.Reports/"
+max_in_flight = 2
+"#,
+        )
+        .unwrap();
+
+        let config: GrpcServerConfig = GrpcServerConfig::builder()
+            .with_config_file(&path)
+            .build()
+            .unwrap();
+
+        assert!(config.concurrency.enabled);
+        assert_eq!(config.concurrency.max_in_flight, 100);
+        assert_eq!(config.concurrency.queue_timeout(), Duration::from_millis(200));
+        assert_eq!(config.concurrency.routes[0].prefix, "/reports.Reports/");
+        assert!(!config.concurrency.adaptive.enabled);
+    }
+
+    #[test]
+    fn grpc_server_config_method_timeouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+request_timeout_secs = 10
+
+[[method_timeouts]]
+method = "/reports.Reports/"
+timeout_ms = 120000
+"#,
+        )
+        .unwrap();
+
+        let config: GrpcServerConfig = GrpcServerConfig::builder()
+            .with_config_file(&path)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.request_timeout(), Duration::from_secs(10));
+        assert_eq!(config.method_timeouts[0].prefix, "/reports.Reports/");
+        assert_eq!(
+            config.method_timeouts[0].timeout(),
+            Some(Duration::from_secs(120))
+        );
+    }
+
+    #[test]
+    fn grpc_server_config_authorization() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(
+            &path,
+            r#"
+[[authorization.methods]]
+method = "/orders.Orders/CreateOrder"
+scopes = ["orders:write"]
+
+[[authorization.methods]]
+method = "/admin.Admin/"
+roles = ["admin"]
+"#,
+        )
+        .unwrap();
+
+        let config: GrpcServerConfig = GrpcServerConfig::builder()
+            .with_config_file(&path)
+            .build()
+            .unwrap();
+
+        let policies = server_kit::PolicyMap::from_config(&config.authorization);
+        assert_eq!(
+            policies.policy_for("/orders.Orders/CreateOrder"),
+            Some(&server_kit::Policy::new().scopes(["orders:write"]))
+        );
+        assert_eq!(
+            policies.policy_for("/admin.Admin/Stats"),
+            Some(&server_kit::Policy::new().roles(["admin"]))
+        );
+        assert_eq!(policies.policy_for("/orders.Orders/GetOrder"), None);
+    }
+
     #[test]
     fn grpc_server_config_from_yaml() {
         let dir = tempfile::tempdir().unwrap();
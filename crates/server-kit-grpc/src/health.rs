@@ -7,10 +7,15 @@ pub use tonic_health::server::HealthReporter;
 #[cfg(feature = "health")]
 pub use tonic_health::ServingStatus;
 
+#[cfg(feature = "health")]
+static REPORTER: std::sync::OnceLock<HealthReporter> = std::sync::OnceLock::new();
+
 /// Create a health service and reporter.
 ///
 /// Returns a tuple of (HealthReporter, HealthService) that can be used with
-/// tonic's Server builder.
+/// tonic's Server builder. During shutdown, `serve_with`/`serve_at` set the
+/// overall server status (service `""`) of the first created service to
+/// `NOT_SERVING`.
 ///
 /// # Example
 ///
@@ -36,7 +41,20 @@ pub fn health_service() -> (
     tonic_health::server::HealthReporter,
     tonic_health::pb::health_server::HealthServer<impl tonic_health::pb::health_server::Health>,
 ) {
-    tonic_health::server::health_reporter()
+    let (reporter, service) = tonic_health::server::health_reporter();
+    let _ = REPORTER.set(reporter.clone());
+    (reporter, service)
+}
+
+/// Mark the overall server as not serving.
+#[cfg(feature = "health")]
+pub(crate) async fn set_not_serving() {
+    if let Some(reporter) = REPORTER.get() {
+        reporter
+            .clone()
+            .set_service_status("", ServingStatus::NotServing)
+            .await;
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,155 @@
+//! Access log layer for gRPC requests.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use server_kit::{AccessLog, AccessLogBody, AccessLogConfig, AccessLogRecord};
+use tonic::body::BoxBody;
+use tower::{Layer, Service};
+
+use super::ClientIp;
+
+/// Access log layer for gRPC requests.
+///
+/// Writes one line per call once the response stream has finished, including
+/// the `grpc-status` from the trailers.
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit_grpc::interceptor::AccessLogLayer;
+/// use tonic::transport::Server;
+///
+/// Server::builder()
+///     .layer(AccessLogLayer::from_config(&config.access_log)?)
+///     .add_service(my_service)
+///     .serve_with(&config)
+///     .await?;
+/// ```
+#[derive(Clone)]
+pub struct AccessLogLayer {
+    log: Arc<AccessLog>,
+}
+
+impl AccessLogLayer {
+    pub fn new(log: AccessLog) -> Self {
+        Self { log: Arc::new(log) }
+    }
+
+    /// Create the layer from the `access_log` config section.
+    pub fn from_config(config: &AccessLogConfig) -> std::io::Result<Self> {
+        AccessLog::from_config(config).map(Self::new)
+    }
+}
+
+impl<S> Layer<S> for AccessLogLayer {
+    type Service = AccessLogService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AccessLogService {
+            inner,
+            log: Arc::clone(&self.log),
+        }
+    }
+}
+
+/// Access log service wrapper.
+#[derive(Clone)]
+pub struct AccessLogService<S> {
+    inner: S,
+    log: Arc<AccessLog>,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for AccessLogService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: http_body::Body<Data = bytes::Bytes> + Send + 'static,
+    ResBody::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let start = Instant::now();
+        let mut record = AccessLogRecord::from_request(&req);
+        record.remote_addr = ClientIp::from_extensions(req.extensions());
+
+        let log = Arc::clone(&self.log);
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let response = inner.call(req).await?;
+            record.set_response(&response);
+
+            Ok(response
+                .map(|body| tonic::body::boxed(AccessLogBody::new(body, log, record, start))))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::Request as HttpRequest;
+    use http_body_util::BodyExt;
+    use server_kit::AccessLogFormat;
+    use std::convert::Infallible;
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct MockService;
+
+    impl<B> Service<HttpRequest<B>> for MockService {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: HttpRequest<B>) -> Self::Future {
+            let response = http::Response::builder()
+                .header("grpc-status", "5")
+                .body(tonic::body::empty_body())
+                .unwrap();
+
+            std::future::ready(Ok(response))
+        }
+    }
+
+    #[tokio::test]
+    async fn logs_grpc_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let log = AccessLog::file(
+            AccessLogFormat::Custom("$request_uri $status $grpc_status $request_id".into()),
+            &path,
+        )
+        .unwrap();
+
+        let service = AccessLogLayer::new(log).layer(MockService);
+        let req = HttpRequest::builder()
+            .uri("/greeter.Greeter/SayHello")
+            .header("x-request-id", "req-42")
+            .body(())
+            .unwrap();
+
+        let response = service.oneshot(req).await.unwrap();
+        response.into_body().collect().await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, "/greeter.Greeter/SayHello 200 5 req-42\n");
+    }
+}
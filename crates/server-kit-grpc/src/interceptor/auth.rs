@@ -1,17 +1,212 @@
 //! Authentication interceptor.
 
-use tonic::{Request, Status};
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, SystemTime};
+
+use server_kit::{Permissions, Subject, TokenCache};
+use tonic::{Code, Request, Status};
 
 /// Trait for validating authentication tokens.
+///
+/// Every `TokenValidator` is also an [`AsyncTokenValidator`], usable with
+/// [`AuthLayer`](crate::AuthLayer).
 pub trait TokenValidator: Clone + Send + Sync + 'static {
-    /// Validate a token, returning Ok(()) if valid or an error status.
-    fn validate(&self, token: &str) -> Result<(), Status>;
+    /// Who the token belongs to, e.g. decoded claims. [`AuthInterceptor`]
+    /// inserts it into the request extensions, where handlers read it with
+    /// [`RequestExt::identity`](crate::RequestExt::identity). Use `()` when
+    /// there is nothing to keep.
+    type Identity: Clone + Send + Sync + 'static;
+
+    /// Validate a token, returning the caller's identity or an error status.
+    #[allow(clippy::result_large_err)]
+    fn validate(&self, token: &str) -> Result<Self::Identity, Status>;
+
+    /// The [`Subject`] to insert next to the identity, used to rate limit
+    /// per user. None by default.
+    fn subject(&self, _identity: &Self::Identity) -> Option<String> {
+        None
+    }
+
+    /// The scopes and roles checked by
+    /// [`AuthorizeLayer`](crate::AuthorizeLayer). Empty by default, which
+    /// passes only policies requiring nothing beyond authentication.
+    fn permissions(&self, _identity: &Self::Identity) -> Permissions {
+        Permissions::default()
+    }
+
+    /// When the identity stops being valid, e.g. the token's `exp`.
+    /// [`CachedValidator`] keeps it no longer than that. None by default,
+    /// leaving it to the cache TTL.
+    fn expires_at(&self, _identity: &Self::Identity) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// Future returned by [`AsyncTokenValidator::validate`].
+pub type ValidateFuture<'a, I> = Pin<Box<dyn Future<Output = Result<I, Status>> + Send + 'a>>;
+
+/// Validates tokens asynchronously, e.g. against a session store or an
+/// introspection endpoint.
+///
+/// Interceptors can't await, so async validators run in an
+/// [`AuthLayer`](crate::AuthLayer). Wrap them in a [`CachedValidator`] to
+/// avoid a lookup per call.
+pub trait AsyncTokenValidator: Send + Sync + 'static {
+    /// See [`TokenValidator::Identity`].
+    type Identity: Clone + Send + Sync + 'static;
+
+    fn validate<'a>(&'a self, token: &'a str) -> ValidateFuture<'a, Self::Identity>;
+
+    /// See [`TokenValidator::subject`].
+    fn subject(&self, _identity: &Self::Identity) -> Option<String> {
+        None
+    }
+
+    /// See [`TokenValidator::permissions`].
+    fn permissions(&self, _identity: &Self::Identity) -> Permissions {
+        Permissions::default()
+    }
+
+    /// See [`TokenValidator::expires_at`].
+    fn expires_at(&self, _identity: &Self::Identity) -> Option<SystemTime> {
+        None
+    }
+}
+
+impl<V: TokenValidator> AsyncTokenValidator for V {
+    type Identity = V::Identity;
+
+    fn validate<'a>(&'a self, token: &'a str) -> ValidateFuture<'a, Self::Identity> {
+        Box::pin(std::future::ready(TokenValidator::validate(self, token)))
+    }
+
+    fn subject(&self, identity: &Self::Identity) -> Option<String> {
+        TokenValidator::subject(self, identity)
+    }
+
+    fn permissions(&self, identity: &Self::Identity) -> Permissions {
+        TokenValidator::permissions(self, identity)
+    }
+
+    fn expires_at(&self, identity: &Self::Identity) -> Option<SystemTime> {
+        TokenValidator::expires_at(self, identity)
+    }
+}
+
+/// Validator remembering the results of another one.
+///
+/// Accepted tokens are cached for `ttl`; `UNAUTHENTICATED` and
+/// `PERMISSION_DENIED` rejections for the negative TTL (10 seconds by
+/// default). Other errors, like `UNAVAILABLE` while the backing store is
+/// down, are never cached. Identities are dropped early when the inner
+/// validator's [`expires_at`](AsyncTokenValidator::expires_at) says they
+/// expire sooner.
+///
+/// # Example
+///
+/// ```ignore
+/// let validator = CachedValidator::new(introspection, Duration::from_secs(30));
+///
+/// Server::builder()
+///     .layer(AuthLayer::new(validator).exempt("/grpc.health.v1.Health/"))
+///     .add_service(svc)
+/// ```
+pub struct CachedValidator<V: AsyncTokenValidator> {
+    inner: V,
+    cache: TokenCache<V::Identity, Status>,
+}
+
+impl<V: AsyncTokenValidator> CachedValidator<V> {
+    pub fn new(inner: V, ttl: Duration) -> Self {
+        Self {
+            inner,
+            cache: TokenCache::new(ttl),
+        }
+    }
+
+    /// How long rejected tokens are remembered; zero disables negative
+    /// caching.
+    pub fn negative_ttl(mut self, ttl: Duration) -> Self {
+        self.cache = self.cache.negative_ttl(ttl);
+        self
+    }
+
+    /// Maximum number of cached accepted tokens, 10 000 by default.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.cache = self.cache.max_entries(max_entries);
+        self
+    }
+
+    /// Maximum number of cached rejected tokens, 1 000 by default. They
+    /// are kept apart from accepted ones, so junk tokens can't push valid
+    /// sessions out of the cache.
+    pub fn max_negative_entries(mut self, max_entries: usize) -> Self {
+        self.cache = self.cache.max_negative_entries(max_entries);
+        self
+    }
+
+    /// Forget the cached result for `token`.
+    pub fn invalidate(&self, token: &str) {
+        self.cache.invalidate(token);
+    }
+}
+
+impl<V: AsyncTokenValidator> AsyncTokenValidator for CachedValidator<V> {
+    type Identity = V::Identity;
+
+    fn validate<'a>(&'a self, token: &'a str) -> ValidateFuture<'a, Self::Identity> {
+        Box::pin(async move {
+            if let Some(result) = self.cache.get(token) {
+                return result;
+            }
+            let result = self.inner.validate(token).await;
+            let cacheable = match &result {
+                Ok(_) => true,
+                Err(status) => matches!(
+                    status.code(),
+                    Code::Unauthenticated | Code::PermissionDenied
+                ),
+            };
+            if cacheable {
+                let identity = result.as_ref().ok();
+                match identity.and_then(|identity| self.inner.expires_at(identity)) {
+                    Some(at) => self.cache.insert_until(token, result.clone(), at),
+                    None => self.cache.insert(token, result.clone()),
+                }
+            }
+            result
+        })
+    }
+
+    fn subject(&self, identity: &Self::Identity) -> Option<String> {
+        self.inner.subject(identity)
+    }
+
+    fn permissions(&self, identity: &Self::Identity) -> Permissions {
+        self.inner.permissions(identity)
+    }
+
+    fn expires_at(&self, identity: &Self::Identity) -> Option<SystemTime> {
+        self.inner.expires_at(identity)
+    }
+}
+
+/// Bearer token from the `authorization` metadata.
+#[allow(clippy::result_large_err)]
+fn bearer_token<T>(req: &Request<T>) -> Result<&str, Status> {
+    req.metadata()
+        .get("authorization")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .ok_or_else(|| Status::unauthenticated("Missing authorization header"))
 }
 
 /// Authentication interceptor.
 ///
 /// Extracts the bearer token from the `authorization` header
-/// and validates it using the provided validator.
+/// and validates it using the provided validator. The identity it returns
+/// is added to the request extensions.
 ///
 /// # Example
 ///
@@ -25,9 +220,11 @@ pub trait TokenValidator: Clone + Send + Sync + 'static {
 /// }
 ///
 /// impl TokenValidator for MyValidator {
-///     fn validate(&self, token: &str) -> Result<(), Status> {
+///     type Identity = User;
+///
+///     fn validate(&self, token: &str) -> Result<User, Status> {
 ///         if token == self.secret {
-///             Ok(())
+///             Ok(User::service_account())
 ///         } else {
 ///             Err(Status::unauthenticated("Invalid token"))
 ///         }
@@ -36,6 +233,9 @@ pub trait TokenValidator: Clone + Send + Sync + 'static {
 ///
 /// let interceptor = AuthInterceptor::new(MyValidator { secret: "secret".into() });
 /// let svc = MyServiceServer::with_interceptor(my_impl, interceptor.into_fn());
+///
+/// // In the handler:
+/// let user = request.identity::<User>();
 /// ```
 #[derive(Clone)]
 pub struct AuthInterceptor<V> {
@@ -48,16 +248,15 @@ impl<V: TokenValidator> AuthInterceptor<V> {
     }
 
     /// Create an interceptor function for use with `with_interceptor`.
+    #[allow(clippy::result_large_err)]
     pub fn into_fn(self) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone {
-        move |req: Request<()>| {
-            let token = req
-                .metadata()
-                .get("authorization")
-                .and_then(|v| v.to_str().ok())
-                .and_then(|v| v.strip_prefix("Bearer "))
-                .ok_or_else(|| Status::unauthenticated("Missing authorization header"))?;
-
-            self.validator.validate(token)?;
+        move |mut req: Request<()>| {
+            let identity = self.validator.validate(bearer_token(&req)?)?;
+            if let Some(subject) = self.validator.subject(&identity) {
+                req.extensions_mut().insert(Subject(subject));
+            }
+            req.extensions_mut().insert(self.validator.permissions(&identity));
+            req.extensions_mut().insert(identity);
             Ok(req)
         }
     }
@@ -65,6 +264,8 @@ impl<V: TokenValidator> AuthInterceptor<V> {
 
 /// Create a simple bearer token validation interceptor.
 ///
+/// Whatever `validate` returns is added to the request extensions.
+///
 /// # Example
 ///
 /// ```ignore
@@ -80,19 +281,15 @@ impl<V: TokenValidator> AuthInterceptor<V> {
 ///
 /// let svc = MyServiceServer::with_interceptor(my_impl, interceptor);
 /// ```
-pub fn bearer_auth<F>(validate: F) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone
+#[allow(clippy::result_large_err)]
+pub fn bearer_auth<F, I>(validate: F) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone
 where
-    F: Fn(&str) -> Result<(), Status> + Clone + Send + Sync + 'static,
+    F: Fn(&str) -> Result<I, Status> + Clone + Send + Sync + 'static,
+    I: Clone + Send + Sync + 'static,
 {
-    move |req: Request<()>| {
-        let token = req
-            .metadata()
-            .get("authorization")
-            .and_then(|v| v.to_str().ok())
-            .and_then(|v| v.strip_prefix("Bearer "))
-            .ok_or_else(|| Status::unauthenticated("Missing authorization header"))?;
-
-        validate(token)?;
+    move |mut req: Request<()>| {
+        let identity = validate(bearer_token(&req)?)?;
+        req.extensions_mut().insert(identity);
         Ok(req)
     }
 }
@@ -102,15 +299,23 @@ mod tests {
     use super::*;
     use tonic::Code;
 
+    #[allow(clippy::result_large_err)]
+    fn accept_valid(token: &str) -> Result<(), Status> {
+        if token == "valid" {
+            Ok(())
+        } else {
+            Err(Status::unauthenticated("invalid"))
+        }
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn accept_any(_token: &str) -> Result<(), Status> {
+        Ok(())
+    }
+
     #[test]
     fn auth_interceptor_valid_token() {
-        let interceptor = bearer_auth(|token| {
-            if token == "valid" {
-                Ok(())
-            } else {
-                Err(Status::unauthenticated("invalid"))
-            }
-        });
+        let interceptor = bearer_auth(accept_valid);
 
         let mut req = Request::new(());
         req.metadata_mut()
@@ -121,13 +326,7 @@ mod tests {
 
     #[test]
     fn auth_interceptor_invalid_token() {
-        let interceptor = bearer_auth(|token| {
-            if token == "valid" {
-                Ok(())
-            } else {
-                Err(Status::unauthenticated("invalid"))
-            }
-        });
+        let interceptor = bearer_auth(accept_valid);
 
         let mut req = Request::new(());
         req.metadata_mut()
@@ -140,7 +339,7 @@ mod tests {
 
     #[test]
     fn auth_interceptor_missing_token() {
-        let interceptor = bearer_auth(|_| Ok(()));
+        let interceptor = bearer_auth(accept_any);
         let req = Request::new(());
 
         let result = interceptor(req);
@@ -150,7 +349,7 @@ mod tests {
 
     #[test]
     fn auth_interceptor_wrong_scheme() {
-        let interceptor = bearer_auth(|_| Ok(()));
+        let interceptor = bearer_auth(accept_any);
 
         let mut req = Request::new(());
         req.metadata_mut()
@@ -167,6 +366,8 @@ mod tests {
         struct TestValidator;
 
         impl TokenValidator for TestValidator {
+            type Identity = ();
+
             fn validate(&self, token: &str) -> Result<(), Status> {
                 if token == "secret" {
                     Ok(())
@@ -184,4 +385,41 @@ mod tests {
 
         assert!(interceptor(req).is_ok());
     }
+
+    #[test]
+    fn auth_interceptor_inserts_identity() {
+        use crate::RequestExt;
+
+        #[derive(Debug, Clone, PartialEq)]
+        struct User(String);
+
+        #[derive(Clone)]
+        struct Users;
+
+        impl TokenValidator for Users {
+            type Identity = User;
+
+            fn validate(&self, token: &str) -> Result<User, Status> {
+                Ok(User(token.to_string()))
+            }
+
+            fn subject(&self, user: &User) -> Option<String> {
+                Some(user.0.clone())
+            }
+        }
+
+        let interceptor = AuthInterceptor::new(Users).into_fn();
+
+        let mut req = Request::new(());
+        req.metadata_mut()
+            .insert("authorization", "Bearer ada".parse().unwrap());
+
+        let req = interceptor(req).unwrap();
+        assert_eq!(req.identity::<User>(), Some(&User("ada".to_string())));
+        assert_eq!(
+            req.extensions().get::<Subject>(),
+            Some(&Subject("ada".to_string()))
+        );
+        assert_eq!(req.identity::<String>(), None);
+    }
 }
@@ -0,0 +1,232 @@
+//! Authentication layer.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use server_kit::Subject;
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower::{Layer, Service};
+
+use super::AsyncTokenValidator;
+
+/// Layer validating the bearer token of every call with an
+/// [`AsyncTokenValidator`].
+///
+/// Unlike [`AuthInterceptor`](super::AuthInterceptor), the validator can
+/// await, and one layer covers all services. The identity it returns is
+/// added to the request extensions
+/// ([`RequestExt::identity`](crate::RequestExt::identity)), with a
+/// [`Subject`] when the validator provides one, and the caller's
+/// [`Permissions`](server_kit::Permissions) for
+/// [`AuthorizeLayer`](crate::AuthorizeLayer). Calls without a valid token
+/// fail with the validator's status, `UNAUTHENTICATED` when there is none.
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit_grpc::{AuthLayer, CachedValidator};
+///
+/// let validator = CachedValidator::new(sessions, Duration::from_secs(30));
+///
+/// Server::builder()
+///     .with_default_layers()
+///     .layer(AuthLayer::new(validator).exempt("/grpc.health.v1.Health/"))
+///     .add_service(svc)
+/// ```
+pub struct AuthLayer<V> {
+    validator: Arc<V>,
+    exempt: Arc<Vec<String>>,
+}
+
+impl<V> Clone for AuthLayer<V> {
+    fn clone(&self) -> Self {
+        Self {
+            validator: Arc::clone(&self.validator),
+            exempt: Arc::clone(&self.exempt),
+        }
+    }
+}
+
+impl<V: AsyncTokenValidator> AuthLayer<V> {
+    pub fn new(validator: V) -> Self {
+        Self {
+            validator: Arc::new(validator),
+            exempt: Arc::new(Vec::new()),
+        }
+    }
+
+    /// Let calls to methods starting with `prefix` through without a token,
+    /// e.g. `/grpc.health.v1.Health/`.
+    pub fn exempt(mut self, prefix: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.exempt).push(prefix.into());
+        self
+    }
+}
+
+impl<S, V> Layer<S> for AuthLayer<V> {
+    type Service = AuthService<S, V>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            validator: Arc::clone(&self.validator),
+            exempt: Arc::clone(&self.exempt),
+        }
+    }
+}
+
+/// Service created by [`AuthLayer`].
+pub struct AuthService<S, V> {
+    inner: S,
+    validator: Arc<V>,
+    exempt: Arc<Vec<String>>,
+}
+
+impl<S: Clone, V> Clone for AuthService<S, V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            validator: Arc::clone(&self.validator),
+            exempt: Arc::clone(&self.exempt),
+        }
+    }
+}
+
+impl<S, V, ReqBody> Service<http::Request<ReqBody>> for AuthService<S, V>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    V: AsyncTokenValidator,
+    ReqBody: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let path = req.uri().path();
+        if self
+            .exempt
+            .iter()
+            .any(|prefix| path.starts_with(prefix.as_str()))
+        {
+            return Box::pin(inner.call(req));
+        }
+
+        let token = req
+            .headers()
+            .get(http::header::AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.strip_prefix("Bearer "))
+            .map(str::to_string);
+        let validator = Arc::clone(&self.validator);
+
+        Box::pin(async move {
+            let Some(token) = token else {
+                let status = Status::unauthenticated("Missing authorization header");
+                return Ok(status.into_http());
+            };
+            let identity = match validator.validate(&token).await {
+                Ok(identity) => identity,
+                Err(status) => return Ok(status.into_http()),
+            };
+            if let Some(subject) = validator.subject(&identity) {
+                req.extensions_mut().insert(Subject(subject));
+            }
+            req.extensions_mut().insert(validator.permissions(&identity));
+            req.extensions_mut().insert(identity);
+            inner.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interceptor::{CachedValidator, ValidateFuture};
+    use std::convert::Infallible;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use tonic::Code;
+    use tower::ServiceExt;
+
+    #[derive(Default)]
+    struct Sessions {
+        lookups: AtomicUsize,
+    }
+
+    impl AsyncTokenValidator for Arc<Sessions> {
+        type Identity = String;
+
+        fn validate<'a>(&'a self, token: &'a str) -> ValidateFuture<'a, String> {
+            Box::pin(async move {
+                self.lookups.fetch_add(1, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+                match token {
+                    "down" => Err(Status::unavailable("session store unreachable")),
+                    _ => token
+                        .strip_prefix("session-")
+                        .map(str::to_string)
+                        .ok_or_else(|| Status::unauthenticated("Unknown session")),
+                }
+            })
+        }
+
+        fn subject(&self, user: &String) -> Option<String> {
+            Some(user.clone())
+        }
+    }
+
+    fn status(response: &http::Response<BoxBody>) -> Status {
+        Status::from_header_map(response.headers()).unwrap_or_else(|| Status::ok(""))
+    }
+
+    fn request(path: &str, token: Option<&str>) -> http::Request<()> {
+        let mut builder = http::Request::builder().uri(path);
+        if let Some(token) = token {
+            builder = builder.header("authorization", format!("Bearer {token}"));
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn validates_calls_and_inserts_the_identity() {
+        let sessions = Arc::new(Sessions::default());
+        let validator = CachedValidator::new(Arc::clone(&sessions), Duration::from_secs(60));
+        let svc = AuthLayer::new(validator)
+            .exempt("/grpc.health.v1.Health/")
+            .layer(tower::service_fn(|req: http::Request<()>| async move {
+                let user = req.extensions().get::<String>().cloned();
+                let subject = req.extensions().get::<Subject>().cloned();
+                assert_eq!(user, subject.map(|Subject(subject)| subject));
+                Ok::<_, Infallible>(http::Response::new(tonic::body::empty_body()))
+            }));
+
+        const SAY_HELLO: &str = "/greeter.Greeter/SayHello";
+        let calls = [
+            (SAY_HELLO, Some("session-ada"), Code::Ok),
+            (SAY_HELLO, Some("session-ada"), Code::Ok),
+            (SAY_HELLO, Some("forged"), Code::Unauthenticated),
+            (SAY_HELLO, Some("forged"), Code::Unauthenticated),
+            (SAY_HELLO, Some("down"), Code::Unavailable),
+            (SAY_HELLO, Some("down"), Code::Unavailable),
+            (SAY_HELLO, None, Code::Unauthenticated),
+            ("/grpc.health.v1.Health/Check", None, Code::Ok),
+        ];
+        for (path, token, code) in calls {
+            let response = svc.clone().oneshot(request(path, token)).await.unwrap();
+            assert_eq!(status(&response).code(), code, "{path} {token:?}");
+        }
+        // One lookup each for the valid and forged tokens, two while down.
+        assert_eq!(sessions.lookups.load(Ordering::SeqCst), 4);
+    }
+}
@@ -0,0 +1,156 @@
+//! Authorization layer.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use server_kit::{AuthorizationConfig, Permissions, PolicyMap};
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower::{Layer, Service};
+
+/// Layer checking the [`Permissions`] inserted by
+/// [`AuthLayer`](crate::AuthLayer) against per-method policies.
+///
+/// Policies are keyed by gRPC path prefix, e.g. `/pkg.Service/Method` for
+/// one method or `/pkg.Service/` for a whole service; the first match wins
+/// and methods without one aren't checked. Calls without permissions fail
+/// with `UNAUTHENTICATED`, calls the policy doesn't allow with
+/// `PERMISSION_DENIED`. Interceptors run after layers, so this only sees
+/// permissions from an `AuthLayer` added before it.
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit_grpc::{AuthLayer, AuthorizeLayer, Policy, PolicyMap};
+///
+/// let policies = PolicyMap::new()
+///     .route("/orders.Orders/CreateOrder", Policy::new().scopes(["orders:write"]))
+///     .route("/orders.Orders/", Policy::new().scopes(["orders:read"]))
+///     .route("/admin.Admin/", Policy::new().roles(["admin"]));
+///
+/// Server::builder()
+///     .layer(AuthLayer::new(validator))
+///     .layer(AuthorizeLayer::new(policies))
+///     .add_service(svc)
+/// ```
+#[derive(Debug, Clone)]
+pub struct AuthorizeLayer {
+    policies: Arc<PolicyMap>,
+}
+
+impl AuthorizeLayer {
+    pub fn new(policies: PolicyMap) -> Self {
+        Self {
+            policies: Arc::new(policies),
+        }
+    }
+
+    /// Use the method policies of the `authorization` config section.
+    pub fn from_config(config: &AuthorizationConfig) -> Self {
+        Self::new(PolicyMap::from_config(config))
+    }
+}
+
+impl<S> Layer<S> for AuthorizeLayer {
+    type Service = AuthorizeService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthorizeService {
+            inner,
+            policies: Arc::clone(&self.policies),
+        }
+    }
+}
+
+/// Service created by [`AuthorizeLayer`].
+#[derive(Debug, Clone)]
+pub struct AuthorizeService<S> {
+    inner: S,
+    policies: Arc<PolicyMap>,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for AuthorizeService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        if let Some(policy) = self.policies.policy_for(req.uri().path()) {
+            let status = match req.extensions().get::<Permissions>() {
+                None => Some(Status::unauthenticated("Missing authorization header")),
+                Some(permissions) if !policy.allows(permissions) => {
+                    Some(Status::permission_denied("Insufficient permissions"))
+                }
+                Some(_) => None,
+            };
+            if let Some(status) = status {
+                return Box::pin(async move { Ok(status.into_http()) });
+            }
+        }
+        Box::pin(inner.call(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use server_kit::Policy;
+    use std::convert::Infallible;
+    use tonic::Code;
+    use tower::ServiceExt;
+
+    fn status(response: &http::Response<BoxBody>) -> Status {
+        Status::from_header_map(response.headers()).unwrap_or_else(|| Status::ok(""))
+    }
+
+    fn request(path: &str, permissions: Option<Permissions>) -> http::Request<()> {
+        let mut req = http::Request::builder().uri(path).body(()).unwrap();
+        if let Some(permissions) = permissions {
+            req.extensions_mut().insert(permissions);
+        }
+        req
+    }
+
+    #[tokio::test]
+    async fn checks_method_policies() {
+        const CREATE_ORDER: &str = "/orders.Orders/CreateOrder";
+        let policies = PolicyMap::new()
+            .route(CREATE_ORDER, Policy::new().scopes(["orders:write"]))
+            .route("/orders.Orders/", Policy::new().scopes(["orders:read"]))
+            .route("/admin.Admin/", Policy::new().roles(["admin"]));
+        let svc =
+            AuthorizeLayer::new(policies).layer(tower::service_fn(|_: http::Request<()>| async {
+                Ok::<_, Infallible>(http::Response::new(tonic::body::empty_body()))
+            }));
+
+        let reader = || Some(Permissions::new().scopes(["orders:read"]));
+        let admin = || Some(Permissions::new().roles(["admin"]));
+        let calls = [
+            ("/orders.Orders/GetOrder", reader(), Code::Ok),
+            (CREATE_ORDER, reader(), Code::PermissionDenied),
+            ("/orders.Orders/GetOrder", None, Code::Unauthenticated),
+            ("/admin.Admin/Stats", admin(), Code::Ok),
+            ("/admin.Admin/Stats", reader(), Code::PermissionDenied),
+            ("/greeter.Greeter/SayHello", None, Code::Ok),
+        ];
+        for (path, permissions, code) in calls {
+            let req = request(path, permissions);
+            let response = svc.clone().oneshot(req).await.unwrap();
+            assert_eq!(status(&response).code(), code, "{path}");
+        }
+    }
+}
@@ -0,0 +1,156 @@
+//! Client IP resolution layer.
+
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use server_kit::{ProxiedAddr, TrustedProxies};
+use tonic::transport::server::TcpConnectInfo;
+use tower::{Layer, Service};
+
+/// Client IP resolved by [`ClientIpLayer`], read by `RequestExt::client_ip`.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ClientIp(pub(crate) IpAddr);
+
+impl ClientIp {
+    /// The resolved client IP, or the connection's client address without
+    /// the layer. `None` for Unix socket connections.
+    pub(crate) fn from_extensions(extensions: &http::Extensions) -> Option<IpAddr> {
+        extensions
+            .get::<ClientIp>()
+            .map(|ClientIp(ip)| *ip)
+            .or_else(|| peer_addr(extensions).map(|addr| addr.ip().to_canonical()))
+    }
+}
+
+/// The PROXY protocol source if there is one, otherwise the TCP peer.
+pub(crate) fn peer_addr(extensions: &http::Extensions) -> Option<SocketAddr> {
+    if let Some(addr) = extensions.get::<ProxiedAddr>() {
+        return Some(addr.client());
+    }
+    let addr = extensions
+        .get::<TcpConnectInfo>()
+        .and_then(|info| info.remote_addr());
+
+    #[cfg(feature = "tls")]
+    let addr = addr.or_else(|| {
+        use tonic::transport::server::TlsConnectInfo;
+
+        extensions
+            .get::<TlsConnectInfo<ProxiedAddr>>()
+            .map(|info| info.get_ref().client())
+            .or_else(|| {
+                extensions
+                    .get::<TlsConnectInfo<TcpConnectInfo>>()
+                    .and_then(|info| info.get_ref().remote_addr())
+            })
+    });
+
+    addr
+}
+
+/// Layer that resolves the client IP from the `x-forwarded-for` (or
+/// configured `forwarded`) metadata when the call came through one of the
+/// trusted proxies.
+///
+/// Add it outside the layers that use the client IP (access log, rate
+/// limiting, IP filtering); handlers and interceptors read the result with
+/// `RequestExt::client_ip`.
+///
+/// # Example
+///
+/// ```ignore
+/// Server::builder()
+///     .layer(ClientIpLayer::new(config.trusted_proxies.clone()))
+///     .layer(AccessLogLayer::from_config(&config.access_log)?)
+///     .add_service(svc)
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ClientIpLayer {
+    trusted: Arc<TrustedProxies>,
+}
+
+impl ClientIpLayer {
+    pub fn new(trusted: TrustedProxies) -> Self {
+        Self {
+            trusted: Arc::new(trusted),
+        }
+    }
+}
+
+impl<S> Layer<S> for ClientIpLayer {
+    type Service = ClientIpService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClientIpService {
+            inner,
+            trusted: Arc::clone(&self.trusted),
+        }
+    }
+}
+
+/// Service created by [`ClientIpLayer`].
+#[derive(Debug, Clone)]
+pub struct ClientIpService<S> {
+    inner: S,
+    trusted: Arc<TrustedProxies>,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for ClientIpService<S>
+where
+    S: Service<http::Request<ReqBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        if let Some(peer) = peer_addr(req.extensions()) {
+            let ip = self.trusted.resolve(peer.ip(), req.headers());
+            req.extensions_mut().insert(ClientIp(ip));
+        }
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn resolves_from_forwarded_metadata() {
+        let trusted = ["10.0.0.0/8".parse().unwrap()].into_iter().collect();
+        let svc = ClientIpLayer::new(trusted).layer(tower::service_fn(
+            |req: http::Request<()>| async move {
+                Ok::<_, std::convert::Infallible>(ClientIp::from_extensions(req.extensions()))
+            },
+        ));
+
+        let mut req = http::Request::builder()
+            .header("x-forwarded-for", "6.6.6.6, 203.0.113.7")
+            .body(())
+            .unwrap();
+        req.extensions_mut().insert(TcpConnectInfo {
+            local_addr: None,
+            remote_addr: Some("10.0.0.1:5000".parse().unwrap()),
+        });
+        let ip = svc.clone().oneshot(req).await.unwrap();
+        assert_eq!(ip, Some("203.0.113.7".parse().unwrap()));
+
+        let mut req = http::Request::builder()
+            .header("x-forwarded-for", "203.0.113.7")
+            .body(())
+            .unwrap();
+        req.extensions_mut().insert(TcpConnectInfo {
+            local_addr: None,
+            remote_addr: Some("198.51.100.1:5000".parse().unwrap()),
+        });
+        let ip = svc.oneshot(req).await.unwrap();
+        assert_eq!(ip, Some("198.51.100.1".parse().unwrap()));
+    }
+}
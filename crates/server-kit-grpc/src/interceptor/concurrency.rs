@@ -0,0 +1,193 @@
+//! Concurrency limiting layer.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use server_kit::{ConcurrencyConfig, ConcurrencyError, ConcurrencyLimits};
+use tonic::body::BoxBody;
+use tonic::Code;
+use tower::{Layer, Service};
+
+use crate::with_retry_delay;
+
+/// Layer capping the number of calls processed at once, globally and per
+/// method prefix.
+///
+/// Calls over the limit wait in a bounded queue. A full queue fails the call
+/// with `RESOURCE_EXHAUSTED`, a queue timeout with `UNAVAILABLE`; both carry
+/// `google.rpc.RetryInfo` details. The slot is held until the handler
+/// returns its response, so server streams only count while they start.
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit_grpc::{ConcurrencyLimitLayer, ConcurrencyLimiter, ConcurrencyLimits};
+///
+/// let limits = ConcurrencyLimits::new()
+///     .global(ConcurrencyLimiter::new("*", 200))
+///     .route("/reports.Reports/", ConcurrencyLimiter::new("/reports.Reports/", 4));
+///
+/// Server::builder()
+///     .layer(ConcurrencyLimitLayer::new(limits))
+///     .add_service(svc)
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimitLayer {
+    limits: Arc<ConcurrencyLimits>,
+}
+
+impl ConcurrencyLimitLayer {
+    pub fn new(limits: ConcurrencyLimits) -> Self {
+        #[cfg(feature = "metrics")]
+        let limits = limits.observe(|name, stats| {
+            metrics::gauge!("grpc_concurrency_queue_depth", "limit" => name.to_string())
+                .set(stats.queued as f64);
+            metrics::gauge!("grpc_concurrency_limit", "limit" => name.to_string())
+                .set(stats.limit as f64);
+        });
+        Self {
+            limits: Arc::new(limits),
+        }
+    }
+
+    /// Build the limits from the `concurrency` config section.
+    pub fn from_config(config: &ConcurrencyConfig) -> Self {
+        Self::new(ConcurrencyLimits::from_config(config))
+    }
+
+    /// The limits, e.g. to report their current state.
+    pub fn limits(&self) -> &Arc<ConcurrencyLimits> {
+        &self.limits
+    }
+}
+
+impl<S> Layer<S> for ConcurrencyLimitLayer {
+    type Service = ConcurrencyLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConcurrencyLimitService {
+            inner,
+            limits: Arc::clone(&self.limits),
+        }
+    }
+}
+
+/// Service created by [`ConcurrencyLimitLayer`].
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimitService<S> {
+    inner: S,
+    limits: Arc<ConcurrencyLimits>,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for ConcurrencyLimitService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let limits = Arc::clone(&self.limits);
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let method = req.uri().path().to_string();
+
+        Box::pin(async move {
+            let _permit = match limits.acquire(&method).await {
+                Ok(permit) => permit,
+                Err(e) => {
+                    tracing::debug!(method = %method, reason = e.as_str(), "Call shed");
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!("grpc_concurrency_rejections_total", "reason" => e.as_str())
+                        .increment(1);
+
+                    let code = match e {
+                        ConcurrencyError::QueueFull => Code::ResourceExhausted,
+                        ConcurrencyError::QueueTimeout => Code::Unavailable,
+                    };
+                    let status =
+                        with_retry_delay(code, "Server is overloaded", limits.retry_after_delay());
+                    return Ok(status.into_http());
+                }
+            };
+            inner.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use server_kit::{ConcurrencyLimiter, RouteConcurrency};
+    use std::convert::Infallible;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn status(response: &http::Response<BoxBody>) -> tonic::Status {
+        tonic::Status::from_header_map(response.headers()).unwrap_or_else(|| tonic::Status::ok(""))
+    }
+
+    fn slow_service() -> impl Service<
+        http::Request<()>,
+        Response = http::Response<BoxBody>,
+        Error = Infallible,
+        Future = impl Send,
+    > + Clone {
+        tower::service_fn(|_req: http::Request<()>| async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok::<_, Infallible>(http::Response::new(tonic::body::empty_body()))
+        })
+    }
+
+    fn request(path: &str) -> http::Request<()> {
+        http::Request::builder().uri(path).body(()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn full_queue_is_resource_exhausted() {
+        let layer = ConcurrencyLimitLayer::from_config(&ConcurrencyConfig {
+            enabled: true,
+            max_queue: 0,
+            routes: vec![RouteConcurrency {
+                prefix: "/reports.Reports/".to_string(),
+                max_in_flight: 1,
+            }],
+            ..Default::default()
+        });
+        let svc = layer.layer(slow_service());
+
+        let (first, second, other) = tokio::join!(
+            svc.clone().oneshot(request("/reports.Reports/Daily")),
+            svc.clone().oneshot(request("/reports.Reports/Weekly")),
+            svc.clone().oneshot(request("/greeter.Greeter/SayHello")),
+        );
+        assert_eq!(status(&first.unwrap()).code(), Code::Ok);
+        let shed = status(&second.unwrap());
+        assert_eq!(shed.code(), Code::ResourceExhausted);
+        assert_eq!(crate::retry_delay(&shed), Some(Duration::from_secs(1)));
+        assert_eq!(status(&other.unwrap()).code(), Code::Ok);
+    }
+
+    #[tokio::test]
+    async fn queue_timeout_is_unavailable() {
+        let limits = ConcurrencyLimits::new()
+            .global(ConcurrencyLimiter::new("*", 1).queue_timeout(Duration::from_millis(10)));
+        let svc = ConcurrencyLimitLayer::new(limits).layer(slow_service());
+
+        let (first, second) = tokio::join!(
+            svc.clone().oneshot(request("/greeter.Greeter/SayHello")),
+            svc.clone().oneshot(request("/greeter.Greeter/SayHello")),
+        );
+        assert_eq!(status(&first.unwrap()).code(), Code::Ok);
+        assert_eq!(status(&second.unwrap()).code(), Code::Unavailable);
+    }
+}
@@ -0,0 +1,126 @@
+//! In-flight request tracking for gRPC graceful shutdown.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use server_kit::{InFlight, InFlightBody};
+use tonic::body::BoxBody;
+use tower::{Layer, Service};
+
+/// Layer that counts in-flight calls for graceful shutdown.
+///
+/// A call is counted until its response stream has finished. The default
+/// layers use the process-wide [`InFlight::global`] counter, which
+/// `serve_with`/`serve_at` log while draining.
+#[derive(Clone)]
+pub struct InFlightLayer {
+    in_flight: InFlight,
+}
+
+impl Default for InFlightLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InFlightLayer {
+    /// Count calls in the process-wide counter.
+    pub fn new() -> Self {
+        Self::with_counter(InFlight::global().clone())
+    }
+
+    /// Count calls in a custom counter.
+    pub fn with_counter(in_flight: InFlight) -> Self {
+        Self { in_flight }
+    }
+}
+
+impl<S> Layer<S> for InFlightLayer {
+    type Service = InFlightService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        InFlightService {
+            inner,
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
+
+/// In-flight tracking service wrapper.
+#[derive(Clone)]
+pub struct InFlightService<S> {
+    inner: S,
+    in_flight: InFlight,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for InFlightService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: http_body::Body<Data = bytes::Bytes> + Send + 'static,
+    ResBody::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let guard = self.in_flight.track();
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let response = inner.call(req).await?;
+            Ok(response.map(|body| tonic::body::boxed(InFlightBody::new(body, guard))))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::Request as HttpRequest;
+    use http_body_util::BodyExt;
+    use std::convert::Infallible;
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct MockService;
+
+    impl<B> Service<HttpRequest<B>> for MockService {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: HttpRequest<B>) -> Self::Future {
+            std::future::ready(Ok(http::Response::new(tonic::body::empty_body())))
+        }
+    }
+
+    #[tokio::test]
+    async fn counts_until_stream_finishes() {
+        let in_flight = InFlight::new();
+        let service = InFlightLayer::with_counter(in_flight.clone()).layer(MockService);
+
+        let req = HttpRequest::builder()
+            .uri("/greeter.Greeter/SayHello")
+            .body(())
+            .unwrap();
+
+        let response = service.oneshot(req).await.unwrap();
+        assert_eq!(in_flight.count(), 1);
+
+        response.into_body().collect().await.unwrap();
+        assert_eq!(in_flight.count(), 0);
+    }
+}
@@ -0,0 +1,108 @@
+//! IP filter interceptor.
+
+use server_kit::{IpFilter, IpFilterConfig};
+use tonic::{Request, Status};
+
+use crate::RequestExt;
+
+/// Interceptor that rejects calls whose client IP fails the filter rules
+/// with `PERMISSION_DENIED`.
+///
+/// The client IP comes from `RequestExt::client_ip`, so add `ClientIpLayer`
+/// to the server when running behind proxies. Keep the [`IpFilter`] handle
+/// to reload the rules at runtime.
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit_grpc::{IpFilter, IpFilterInterceptor};
+///
+/// let filter = IpFilter::new(config.ip_filter.clone());
+/// let interceptor = IpFilterInterceptor::new(filter.clone());
+/// let admin = AdminServer::with_interceptor(admin_impl, interceptor.into_fn());
+///
+/// // later, after re-reading the config
+/// filter.reload(new_config.ip_filter);
+/// ```
+#[derive(Debug, Clone)]
+pub struct IpFilterInterceptor {
+    filter: IpFilter,
+}
+
+impl IpFilterInterceptor {
+    pub fn new(filter: IpFilter) -> Self {
+        Self { filter }
+    }
+
+    /// Create the interceptor from the `ip_filter` config section.
+    pub fn from_config(config: &IpFilterConfig) -> Self {
+        Self::new(IpFilter::new(config.clone()))
+    }
+
+    /// The filter, for reloading its rules.
+    pub fn filter(&self) -> &IpFilter {
+        &self.filter
+    }
+
+    /// Create an interceptor function for use with `with_interceptor`.
+    #[allow(clippy::result_large_err)]
+    pub fn into_fn(self) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone {
+        move |req: Request<()>| self.check(req)
+    }
+
+    #[allow(clippy::result_large_err)]
+    fn check(&self, req: Request<()>) -> Result<Request<()>, Status> {
+        let ip = req.client_ip();
+        if self.filter.check(ip) {
+            return Ok(req);
+        }
+
+        tracing::warn!(
+            client_ip = ip.map(|ip| ip.to_string()).as_deref().unwrap_or("-"),
+            "Call rejected by IP filter"
+        );
+        #[cfg(feature = "metrics")]
+        metrics::counter!("grpc_ip_filter_rejections_total").increment(1);
+
+        Err(Status::permission_denied("Access denied"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::transport::server::TcpConnectInfo;
+    use tonic::Code;
+
+    fn request(peer: &str) -> Request<()> {
+        let mut req = Request::new(());
+        req.extensions_mut().insert(TcpConnectInfo {
+            local_addr: None,
+            remote_addr: Some(peer.parse().unwrap()),
+        });
+        req
+    }
+
+    #[test]
+    fn rejects_and_reloads() {
+        let interceptor = IpFilterInterceptor::from_config(&IpFilterConfig {
+            allow: vec!["10.0.0.0/8".parse().unwrap()],
+            deny: Vec::new(),
+        });
+        let filter = interceptor.filter().clone();
+        let check = interceptor.into_fn();
+
+        assert!(check(request("10.1.2.3:4000")).is_ok());
+        let status = check(request("192.168.1.5:4000")).unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        // Without a client address an allow list can't be satisfied.
+        assert!(check(Request::new(())).is_err());
+
+        filter.reload(IpFilterConfig {
+            allow: Vec::new(),
+            deny: vec!["10.0.0.0/8".parse().unwrap()],
+        });
+        assert!(check(request("192.168.1.5:4000")).is_ok());
+        assert!(check(request("10.1.2.3:4000")).is_err());
+    }
+}
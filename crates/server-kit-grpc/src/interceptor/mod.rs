@@ -1,16 +1,43 @@
 //! Interceptors for gRPC requests.
 
+mod access_log;
 mod auth;
+mod auth_layer;
+mod authorize;
+mod client_ip;
+mod concurrency;
+mod in_flight;
+mod ip_filter;
+mod panic;
+#[cfg(feature = "ratelimit")]
+mod rate_limit;
 mod request_id;
+mod timeout;
 mod trace;
 
 #[cfg(feature = "metrics")]
 mod metrics;
 
-pub use auth::{bearer_auth, AuthInterceptor, TokenValidator};
+pub use access_log::AccessLogLayer;
+pub use auth::{
+    bearer_auth, AsyncTokenValidator, AuthInterceptor, CachedValidator, TokenValidator,
+    ValidateFuture,
+};
+pub use auth_layer::AuthLayer;
+pub use authorize::AuthorizeLayer;
+pub use client_ip::ClientIpLayer;
+pub(crate) use client_ip::ClientIp;
+pub use concurrency::ConcurrencyLimitLayer;
+pub use in_flight::InFlightLayer;
+pub use ip_filter::IpFilterInterceptor;
+pub use panic::PanicLayer;
+#[cfg(feature = "ratelimit")]
+pub use rate_limit::{reload_rate_limits, RateLimitLayer};
 pub use request_id::{
     request_id_interceptor, RequestIdInterceptor, RequestIdLayer, REQUEST_ID_HEADER,
 };
+pub use timeout::RequestTimeoutLayer;
+pub(crate) use timeout::{encode_grpc_timeout, parse_grpc_timeout};
 pub use trace::TraceLayer;
 
 #[cfg(feature = "metrics")]
@@ -0,0 +1,149 @@
+//! Panic handling layer for gRPC requests.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use server_kit::{CatchPanic, PanicReport};
+use tonic::body::BoxBody;
+use tonic::metadata::MetadataValue;
+use tonic::Status;
+use tower::{Layer, Service};
+
+use super::REQUEST_ID_HEADER;
+
+/// Layer that turns panics in gRPC handlers into `Status::internal`.
+///
+/// The panic message, location and backtrace are logged at ERROR inside the
+/// current request span together with the request ID. With the `metrics`
+/// feature, each panic increments `panics_total`.
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit_grpc::interceptor::PanicLayer;
+/// use tonic::transport::Server;
+///
+/// Server::builder()
+///     .layer(PanicLayer::new())
+///     .add_service(my_service)
+///     .serve(addr)
+///     .await?;
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct PanicLayer;
+
+impl PanicLayer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for PanicLayer {
+    type Service = PanicService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PanicService { inner }
+    }
+}
+
+/// Panic handling service wrapper.
+#[derive(Clone)]
+pub struct PanicService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for PanicService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let request_id = req
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            match CatchPanic::new(async move { inner.call(req).await }).await {
+                Ok(result) => result,
+                Err(report) => Ok(panic_status(&report, request_id).into_http()),
+            }
+        })
+    }
+}
+
+fn panic_status(report: &PanicReport, request_id: Option<String>) -> Status {
+    tracing::error!(
+        panic.message = %report.message,
+        panic.location = report.location.as_deref().unwrap_or("-"),
+        panic.thread = report.thread.as_deref().unwrap_or("-"),
+        panic.backtrace = report.backtrace.as_deref().unwrap_or("-"),
+        request_id = request_id.as_deref().unwrap_or("-"),
+        "handler panicked"
+    );
+
+    #[cfg(feature = "metrics")]
+    metrics::counter!("panics_total").increment(1);
+
+    let mut status = Status::internal("Internal error");
+    if let Some(value) = request_id.and_then(|id| MetadataValue::try_from(id).ok()) {
+        status.metadata_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::Request as HttpRequest;
+    use std::convert::Infallible;
+    use tower::ServiceExt;
+
+    #[derive(Clone)]
+    struct PanickingService;
+
+    impl<B> Service<HttpRequest<B>> for PanickingService {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, _req: HttpRequest<B>) -> Self::Future {
+            Box::pin(async { panic!("handler exploded") })
+        }
+    }
+
+    #[tokio::test]
+    async fn converts_panic_to_internal_status() {
+        let service = PanicLayer::new().layer(PanickingService);
+
+        let req = HttpRequest::builder()
+            .uri("/greeter.Greeter/SayHello")
+            .header(REQUEST_ID_HEADER, "req-9")
+            .body(())
+            .unwrap();
+
+        let response = service.oneshot(req).await.unwrap();
+        let status = Status::from_header_map(response.headers()).unwrap();
+        assert_eq!(status.code(), tonic::Code::Internal);
+        assert_eq!(status.message(), "Internal error");
+        assert_eq!(response.headers().get(REQUEST_ID_HEADER).unwrap(), "req-9");
+    }
+}
@@ -0,0 +1,237 @@
+//! Keyed rate limiting layer.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use server_kit::{KeyedRateLimiter, RateLimitConfig, RateLimitRequest};
+use tonic::body::BoxBody;
+use tower::{Layer, Service};
+
+use super::ClientIp;
+use crate::resource_exhausted;
+
+/// Section the limiters built from `GrpcServerConfig::rate_limit` register
+/// under.
+const SECTION: &str = "grpc.rate_limit";
+
+/// Reload the limiters built from the `rate_limit` config section by
+/// [`RateLimitLayer::from_config`], and so by `with_rate_limit`. HTTP
+/// limiters in the same process keep their rules. Returns the number of
+/// limiters reloaded.
+///
+/// Nothing calls this on its own: call it after re-reading the config file,
+/// e.g. on SIGHUP.
+pub fn reload_rate_limits(config: &RateLimitConfig) -> usize {
+    server_kit::reload_rate_limits(SECTION, config)
+}
+
+/// Layer that limits calls per key, e.g. per client IP, failing calls over
+/// their key's quota with `RESOURCE_EXHAUSTED`. The status carries
+/// `google.rpc.RetryInfo` details with the delay before the next allowed call,
+/// which clients read with [`retry_delay`](crate::retry_delay).
+///
+/// The route is the gRPC path (`/package.Service/Method`), so route groups
+/// can cover a whole service. Add it after `ClientIpLayer` so the client IP
+/// is resolved. Keying by `Subject` needs a layer before this one to insert
+/// it; interceptors run too late.
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit_grpc::{KeyedRateLimiter, RateLimitKey, RateLimitLayer, RateLimitQuota};
+///
+/// let limiter = KeyedRateLimiter::new(RateLimitKey::ClientIp)
+///     .quota(RateLimitQuota::per_second(50))
+///     .route_group("/admin.Admin/", RateLimitQuota::per_minute(10));
+///
+/// Server::builder()
+///     .layer(ClientIpLayer::new(config.trusted_proxies.clone()))
+///     .layer(RateLimitLayer::new(limiter))
+///     .add_service(svc)
+/// ```
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    limiter: Arc<KeyedRateLimiter>,
+}
+
+impl RateLimitLayer {
+    pub fn new(limiter: KeyedRateLimiter) -> Self {
+        Self {
+            limiter: Arc::new(limiter),
+        }
+    }
+
+    /// Build the limiter from the `rate_limit` config section and register
+    /// it with [`reload_rate_limits`].
+    pub fn from_config(config: &RateLimitConfig) -> Self {
+        Self {
+            limiter: KeyedRateLimiter::from_config(config).register(SECTION),
+        }
+    }
+
+    /// The limiter, e.g. to report how many keys it tracks or to
+    /// [`reload`](KeyedRateLimiter::reload) it.
+    pub fn limiter(&self) -> &Arc<KeyedRateLimiter> {
+        &self.limiter
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: Arc::clone(&self.limiter),
+        }
+    }
+}
+
+/// Service created by [`RateLimitLayer`].
+#[derive(Debug, Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Arc<KeyedRateLimiter>,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for RateLimitService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: http::Request<ReqBody>) -> Self::Future {
+        let limiter = Arc::clone(&self.limiter);
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            // Check on the parts: the body isn't `Sync`, so the request can't
+            // be borrowed across the store lookup.
+            let (parts, body) = req.into_parts();
+            let client_ip = ClientIp::from_extensions(&parts.extensions);
+            let limit = limiter
+                .check(&RateLimitRequest {
+                    route: parts.uri.path(),
+                    headers: &parts.headers,
+                    extensions: &parts.extensions,
+                    client_ip,
+                })
+                .await;
+
+            if let Some(retry_after) = limit.and_then(|limit| limit.retry_after) {
+                tracing::debug!(
+                    method = %parts.uri.path(),
+                    client_ip = client_ip.map(|ip| ip.to_string()).as_deref().unwrap_or("-"),
+                    "Call rejected by rate limit"
+                );
+                #[cfg(feature = "metrics")]
+                metrics::counter!("grpc_rate_limit_rejections_total").increment(1);
+
+                return Ok(resource_exhausted("Rate limit exceeded", retry_after).into_http());
+            }
+
+            inner.call(http::Request::from_parts(parts, body)).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use server_kit::{RateLimitKey, RateLimitQuota, RouteRateLimit};
+    use std::convert::Infallible;
+    use std::time::Duration;
+    use tonic::transport::server::TcpConnectInfo;
+    use tonic::Code;
+    use tower::ServiceExt;
+
+    fn request(path: &str, peer: &str) -> http::Request<()> {
+        let mut req = http::Request::builder().uri(path).body(()).unwrap();
+        req.extensions_mut().insert(TcpConnectInfo {
+            local_addr: None,
+            remote_addr: Some(peer.parse().unwrap()),
+        });
+        req
+    }
+
+    fn code(response: &http::Response<BoxBody>) -> Code {
+        response
+            .headers()
+            .get("grpc-status")
+            .map(|v| Code::from_bytes(v.as_bytes()))
+            .unwrap_or(Code::Ok)
+    }
+
+    #[tokio::test]
+    async fn limits_each_client_and_route_group() {
+        let limiter = KeyedRateLimiter::new(RateLimitKey::ClientIp)
+            .quota(RateLimitQuota::per_minute(100))
+            .route_group("/admin.Admin/", RateLimitQuota::per_minute(1));
+        let svc = RateLimitLayer::new(limiter).layer(tower::service_fn(
+            |_req: http::Request<()>| async {
+                Ok::<_, Infallible>(http::Response::new(tonic::body::empty_body()))
+            },
+        ));
+
+        let call = |path, peer| svc.clone().oneshot(request(path, peer));
+        let response = call("/admin.Admin/Stats", "10.0.0.1:4000").await.unwrap();
+        assert_eq!(code(&response), Code::Ok);
+        let response = call("/admin.Admin/Reset", "10.0.0.1:4001").await.unwrap();
+        assert_eq!(code(&response), Code::ResourceExhausted);
+        let status = tonic::Status::from_header_map(response.headers()).unwrap();
+        let delay = crate::retry_delay(&status).unwrap();
+        assert!(delay > Duration::from_secs(59) && delay <= Duration::from_secs(60));
+
+        let response = call("/admin.Admin/Stats", "10.0.0.2:4000").await.unwrap();
+        assert_eq!(code(&response), Code::Ok);
+        let response = call("/greeter.Greeter/SayHello", "10.0.0.1:4000")
+            .await
+            .unwrap();
+        assert_eq!(code(&response), Code::Ok);
+    }
+
+    #[tokio::test]
+    async fn applies_config_with_exemptions() {
+        let config = RateLimitConfig {
+            enabled: true,
+            routes: vec![RouteRateLimit {
+                prefix: "/admin.Admin/".to_string(),
+                requests: 1,
+                period_secs: 60,
+            }],
+            exempt_ips: vec!["10.9.0.0/16".parse().unwrap()],
+            ..Default::default()
+        };
+        let svc = RateLimitLayer::from_config(&config).layer(tower::service_fn(
+            |_req: http::Request<()>| async {
+                Ok::<_, Infallible>(http::Response::new(tonic::body::empty_body()))
+            },
+        ));
+
+        let call = |path, peer| svc.clone().oneshot(request(path, peer));
+        let response = call("/admin.Admin/Stats", "10.0.0.1:4000").await.unwrap();
+        assert_eq!(code(&response), Code::Ok);
+        let response = call("/admin.Admin/Stats", "10.0.0.1:4000").await.unwrap();
+        assert_eq!(code(&response), Code::ResourceExhausted);
+        // No global quota: other methods aren't limited.
+        let response = call("/greeter.Greeter/SayHello", "10.0.0.1:4000")
+            .await
+            .unwrap();
+        assert_eq!(code(&response), Code::Ok);
+        for _ in 0..2 {
+            let response = call("/admin.Admin/Stats", "10.9.0.1:4000").await.unwrap();
+            assert_eq!(code(&response), Code::Ok);
+        }
+    }
+}
@@ -64,6 +64,7 @@ where
 }
 
 /// gRPC interceptor that ensures every request has a request ID.
+#[allow(clippy::result_large_err)]
 pub fn request_id_interceptor(mut req: Request<()>) -> Result<Request<()>, Status> {
     if req.metadata().get(REQUEST_ID_HEADER).is_none() {
         let request_id = Uuid::new_v4().to_string();
@@ -81,10 +82,12 @@ impl RequestIdInterceptor {
         Self
     }
 
+    #[allow(clippy::result_large_err)]
     pub fn intercept(&self, req: Request<()>) -> Result<Request<()>, Status> {
         request_id_interceptor(req)
     }
 
+    #[allow(clippy::result_large_err)]
     pub fn into_fn(self) -> impl Fn(Request<()>) -> Result<Request<()>, Status> + Clone {
         move |req| request_id_interceptor(req)
     }
@@ -0,0 +1,236 @@
+//! Call timeout layer.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use server_kit::{Deadline, RequestTimeouts};
+use tonic::body::BoxBody;
+use tonic::Status;
+use tower::{Layer, Service};
+
+use crate::config::GrpcServerConfig;
+use crate::headers;
+
+/// Largest value allowed in a `grpc-timeout` header (8 digits).
+const MAX_TIMEOUT_VALUE: u64 = 99_999_999;
+
+/// Parse a `grpc-timeout` header value, e.g. `100m` or `5S`.
+pub(crate) fn parse_grpc_timeout(value: &str) -> Option<Duration> {
+    if value.len() < 2 || value.len() > 9 {
+        return None;
+    }
+    let (digits, unit) = value.split_at(value.len() - 1);
+    if !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = digits.parse().ok()?;
+    match unit {
+        "H" => Some(Duration::from_secs(amount * 60 * 60)),
+        "M" => Some(Duration::from_secs(amount * 60)),
+        "S" => Some(Duration::from_secs(amount)),
+        "m" => Some(Duration::from_millis(amount)),
+        "u" => Some(Duration::from_micros(amount)),
+        "n" => Some(Duration::from_nanos(amount)),
+        _ => None,
+    }
+}
+
+/// Encode a duration as a `grpc-timeout` header value, using the finest
+/// unit that fits and rounding up.
+pub(crate) fn encode_grpc_timeout(timeout: Duration) -> String {
+    let nanos = timeout.as_nanos();
+    let units: [(u128, &str); 6] = [
+        (1, "n"),
+        (1_000, "u"),
+        (1_000_000, "m"),
+        (1_000_000_000, "S"),
+        (60_000_000_000, "M"),
+        (3_600_000_000_000, "H"),
+    ];
+    for (scale, unit) in units {
+        let amount = nanos.div_ceil(scale);
+        if amount <= u128::from(MAX_TIMEOUT_VALUE) {
+            return format!("{amount}{unit}");
+        }
+    }
+    format!("{MAX_TIMEOUT_VALUE}H")
+}
+
+/// Layer enforcing a per-call timeout: the smaller of the client's
+/// `grpc-timeout` and the configured timeout for the method.
+///
+/// The call's [`Deadline`] is added to the request extensions (see
+/// [`RequestExt::deadline`](crate::RequestExt::deadline)) and the handler
+/// runs inside it, so calls made through [`ChannelExt`](crate::ChannelExt)
+/// channels forward the remaining time. Calls still running at the deadline
+/// fail with `DEADLINE_EXCEEDED`. Like tonic's own timeout, it covers the
+/// handler until it returns a response, not the streaming of its messages.
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit_grpc::{RequestTimeoutLayer, RequestTimeouts};
+///
+/// let timeouts = RequestTimeouts::new(Duration::from_secs(10))
+///     .route("/reports.Reports/", Duration::from_secs(120));
+///
+/// Server::builder()
+///     .layer(RequestTimeoutLayer::new(timeouts))
+///     .add_service(svc)
+/// ```
+#[derive(Debug, Clone)]
+pub struct RequestTimeoutLayer {
+    timeouts: Arc<RequestTimeouts>,
+}
+
+impl RequestTimeoutLayer {
+    pub fn new(timeouts: RequestTimeouts) -> Self {
+        Self {
+            timeouts: Arc::new(timeouts),
+        }
+    }
+
+    /// Use `request_timeout_secs` and the `method_timeouts` overrides.
+    pub fn from_config(config: &GrpcServerConfig) -> Self {
+        Self::new(RequestTimeouts::from_config(
+            config.request_timeout(),
+            &config.method_timeouts,
+        ))
+    }
+}
+
+impl<S> Layer<S> for RequestTimeoutLayer {
+    type Service = RequestTimeoutService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestTimeoutService {
+            inner,
+            timeouts: Arc::clone(&self.timeouts),
+        }
+    }
+}
+
+/// Service created by [`RequestTimeoutLayer`].
+#[derive(Debug, Clone)]
+pub struct RequestTimeoutService<S> {
+    inner: S,
+    timeouts: Arc<RequestTimeouts>,
+}
+
+impl<S, ReqBody> Service<http::Request<ReqBody>> for RequestTimeoutService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+{
+    type Response = http::Response<BoxBody>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: http::Request<ReqBody>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let requested = req
+            .headers()
+            .get(headers::GRPC_TIMEOUT.as_str())
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_grpc_timeout);
+        let configured = self.timeouts.for_route(req.uri().path());
+        let timeout = match (requested, configured) {
+            (Some(requested), Some(configured)) => Some(requested.min(configured)),
+            (requested, configured) => requested.or(configured),
+        };
+        let Some(timeout) = timeout else {
+            return Box::pin(inner.call(req));
+        };
+
+        let deadline = Deadline::after(timeout);
+        req.extensions_mut().insert(deadline);
+        Box::pin(async move {
+            match deadline.timeout(inner.call(req)).await {
+                Ok(response) => response,
+                Err(_) => Ok(Status::deadline_exceeded("Deadline exceeded").into_http()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+    use tonic::Code;
+    use tower::ServiceExt;
+
+    fn status(response: &http::Response<BoxBody>) -> tonic::Status {
+        tonic::Status::from_header_map(response.headers()).unwrap_or_else(|| tonic::Status::ok(""))
+    }
+
+    fn request(path: &str, grpc_timeout: Option<&str>) -> http::Request<()> {
+        let mut builder = http::Request::builder().uri(path);
+        if let Some(timeout) = grpc_timeout {
+            builder = builder.header("grpc-timeout", timeout);
+        }
+        builder.body(()).unwrap()
+    }
+
+    #[test]
+    fn grpc_timeout_round_trip() {
+        assert_eq!(parse_grpc_timeout("100m"), Some(Duration::from_millis(100)));
+        assert_eq!(parse_grpc_timeout("2H"), Some(Duration::from_secs(7200)));
+        assert_eq!(parse_grpc_timeout("m"), None);
+        assert_eq!(parse_grpc_timeout("123456789S"), None);
+        assert_eq!(parse_grpc_timeout("-1S"), None);
+        assert_eq!(parse_grpc_timeout("10x"), None);
+
+        assert_eq!(encode_grpc_timeout(Duration::from_millis(250)), "250000u");
+        assert_eq!(encode_grpc_timeout(Duration::from_secs(30)), "30000000u");
+        assert_eq!(
+            encode_grpc_timeout(Duration::from_secs(86_400)),
+            "86400000m"
+        );
+        for timeout in [Duration::from_nanos(1), Duration::from_secs(3_000_000)] {
+            let encoded = encode_grpc_timeout(timeout);
+            assert_eq!(parse_grpc_timeout(&encoded), Some(timeout));
+        }
+    }
+
+    #[tokio::test]
+    async fn uses_the_smaller_of_header_and_config() {
+        let timeouts = RequestTimeouts::new(Duration::from_secs(30))
+            .route("/reports.Reports/", Duration::from_millis(50));
+        let svc = RequestTimeoutLayer::new(timeouts).layer(tower::service_fn(
+            |req: http::Request<()>| async move {
+                let deadline = req.extensions().get::<Deadline>().copied().unwrap();
+                assert_eq!(Deadline::current(), Some(deadline));
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                Ok::<_, Infallible>(http::Response::new(tonic::body::empty_body()))
+            },
+        ));
+
+        let calls = [
+            ("/greeter.Greeter/SayHello", None, Code::Ok),
+            (
+                "/greeter.Greeter/SayHello",
+                Some("100m"),
+                Code::DeadlineExceeded,
+            ),
+            (
+                "/reports.Reports/Daily",
+                Some("10S"),
+                Code::DeadlineExceeded,
+            ),
+        ];
+        for (path, header, code) in calls {
+            let response = svc.clone().oneshot(request(path, header)).await.unwrap();
+            assert_eq!(status(&response).code(), code, "{path} {header:?}");
+        }
+    }
+}
@@ -54,13 +54,18 @@
 //! - `tls` - Enable TLS support
 //! - `metrics` - Enable Prometheus metrics collection
 //! - `reflection` - Enable gRPC server reflection
+//! - `ratelimit` - Enable keyed rate limiting
+//! - `ratelimit-redis` - Share rate limits across replicas through Redis
 //! - `full` - Enable all features
 
+mod build_info;
 mod channel;
 pub mod config;
 mod error;
 pub mod interceptor;
+mod proxy_protocol;
 mod request_ext;
+mod retry_info;
 mod server;
 
 #[cfg(feature = "health")]
@@ -69,28 +74,53 @@ pub mod health;
 #[cfg(feature = "reflection")]
 pub mod reflection;
 
-pub use config::{ChannelConfig, ChannelConfigBuilder, ConfigBuilder, ConfigError, Environment, GrpcServerConfig};
-pub use channel::ChannelExt;
+pub use config::{
+    AccessLogConfig, AccessLogFormat, AccessLogOutput, AdaptiveConcurrencyConfig,
+    AuthorizationConfig, ChannelConfig, ChannelConfigBuilder, ConcurrencyConfig, ConfigBuilder,
+    ConfigError, Environment, ForwardedHeader, GrpcServerConfig, IpFilterConfig, IpNetwork,
+    ProxyProtocolConfig, RateLimitConfig, RateLimitKey, RateLimitQuota, RouteConcurrency,
+    RoutePolicy, RouteRateLimit, RouteTimeout, TrustedProxies,
+};
+pub use build_info::{build_info_service, BuildInfoResponse, BuildInfoServer, GetBuildInfoRequest};
+pub use channel::{ChannelExt, DeadlineChannel};
 pub use server::{RouterExt, ServerExt, shutdown_signal};
 pub use request_ext::{headers, HeaderKey, RequestExt};
+pub use retry_info::{resource_exhausted, retry_delay, with_retry_delay};
 pub use error::{Error, GrpcError, ServerError};
 
 #[cfg(feature = "health")]
 pub use health::{health_service, HealthReporter, ServingStatus};
 
 pub use interceptor::{
-    bearer_auth, request_id_interceptor, AuthInterceptor, RequestIdInterceptor, RequestIdLayer,
-    TokenValidator, TraceLayer, REQUEST_ID_HEADER,
+    bearer_auth, request_id_interceptor, AccessLogLayer, AsyncTokenValidator, AuthInterceptor,
+    AuthLayer, AuthorizeLayer, CachedValidator, ClientIpLayer, ConcurrencyLimitLayer,
+    InFlightLayer, IpFilterInterceptor, PanicLayer, RequestIdInterceptor, RequestIdLayer,
+    RequestTimeoutLayer, TokenValidator, TraceLayer, ValidateFuture, REQUEST_ID_HEADER,
 };
 
 #[cfg(feature = "metrics")]
 pub use interceptor::MetricsLayer;
 
+#[cfg(feature = "ratelimit")]
+pub use interceptor::{reload_rate_limits, RateLimitLayer};
+#[cfg(feature = "ratelimit")]
+pub use server_kit::{
+    BackendFuture, KeyExtractor, KeyedRateLimiter, MemoryBackend, RateLimitBackend,
+    RateLimitBackendError, RateLimitRequest, RateLimitStatus, Subject,
+};
+#[cfg(feature = "ratelimit-redis")]
+pub use server_kit::RedisBackend;
+
 #[cfg(feature = "reflection")]
 pub use reflection::{reflection_service, reflection_service_v1alpha};
 
 pub use tonic::{Code, Request, Response, Status};
-pub use server_kit::LogFormat;
+pub use server_kit::{
+    build_info, install_panic_hook, AccessLog, BuildInfo, ConcurrencyError, ConcurrencyLimiter,
+    ConcurrencyLimits, ConcurrencyPermit, ConcurrencyStats, Deadline, DeadlineExceeded, IpFilter,
+    ListenAddr, Listener, LocalAddr, LogFormat, PanicHook, Permissions, Policy, PolicyMap,
+    ProxiedAddr, RequestTimeouts, ServerHandle, ShutdownController, ShutdownSignal, ShutdownToken,
+};
 
 #[cfg(feature = "tracing")]
 pub use server_kit::{init_logging, init_logging_from_env};
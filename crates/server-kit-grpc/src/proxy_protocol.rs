@@ -0,0 +1,117 @@
+//! Incoming connections for PROXY protocol listeners.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use server_kit::{ProxiedAddr, ProxyProtocolAcceptor};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tonic::transport::server::Connected;
+
+/// Stream of connections whose PROXY header has been read.
+pub(crate) struct ProxiedIncoming {
+    acceptor: ProxyProtocolAcceptor,
+    nodelay: bool,
+    keepalive: Option<Duration>,
+}
+
+impl ProxiedIncoming {
+    pub(crate) fn new(
+        acceptor: ProxyProtocolAcceptor,
+        nodelay: bool,
+        keepalive: Option<Duration>,
+    ) -> Self {
+        Self {
+            acceptor,
+            nodelay,
+            keepalive,
+        }
+    }
+}
+
+impl tokio_stream::Stream for ProxiedIncoming {
+    type Item = io::Result<ProxiedStream>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let (stream, addr) = std::task::ready!(self.acceptor.poll_accept(cx));
+        set_socket_options(&stream, self.nodelay, self.keepalive);
+        Poll::Ready(Some(Ok(ProxiedStream {
+            inner: stream,
+            addr,
+        })))
+    }
+}
+
+/// Same options `TcpIncoming` applies; failures are logged, not fatal.
+fn set_socket_options(stream: &TcpStream, nodelay: bool, keepalive: Option<Duration>) {
+    if nodelay {
+        if let Err(e) = stream.set_nodelay(true) {
+            tracing::warn!(error = %e, "Failed to set TCP nodelay");
+        }
+    }
+    if let Some(time) = keepalive {
+        let keepalive = socket2::TcpKeepalive::new().with_time(time);
+        if let Err(e) = socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+            tracing::warn!(error = %e, "Failed to set TCP keepalive");
+        }
+    }
+}
+
+/// A TCP connection accepted through a PROXY protocol listener.
+///
+/// Its connect info, [`ProxiedAddr`], is available from request extensions
+/// and through `RequestExt`.
+pub(crate) struct ProxiedStream {
+    inner: TcpStream,
+    addr: ProxiedAddr,
+}
+
+impl Connected for ProxiedStream {
+    type ConnectInfo = ProxiedAddr;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.addr
+    }
+}
+
+impl AsyncRead for ProxiedStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for ProxiedStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
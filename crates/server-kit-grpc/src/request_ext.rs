@@ -1,7 +1,11 @@
 //! Request extension trait for easy metadata access.
 
+use server_kit::{Deadline, ProxiedAddr};
+use std::net::{IpAddr, SocketAddr};
 use tonic::Request;
 
+use crate::interceptor::ClientIp;
+
 /// A type-safe header key.
 ///
 /// Use predefined constants from the [`headers`] module for common headers,
@@ -70,6 +74,29 @@ pub mod headers {
 pub trait RequestExt<T> {
     /// Get a header value using a type-safe [`HeaderKey`].
     fn header(&self, key: HeaderKey) -> Option<&str>;
+
+    /// Addresses from the PROXY header, when the server has
+    /// `proxy_protocol` enabled.
+    fn proxied_addr(&self) -> Option<ProxiedAddr>;
+
+    /// The client's address: the PROXY protocol source if there is one,
+    /// otherwise the TCP peer.
+    fn client_addr(&self) -> Option<SocketAddr>;
+
+    /// The client's IP as resolved by `ClientIpLayer` from forwarding
+    /// metadata set by trusted proxies, falling back to [`client_addr`].
+    ///
+    /// [`client_addr`]: RequestExt::client_addr
+    fn client_ip(&self) -> Option<IpAddr>;
+
+    /// The call's deadline, set by `RequestTimeoutLayer` from the client's
+    /// `grpc-timeout` and the configured timeout.
+    fn deadline(&self) -> Option<Deadline>;
+
+    /// The identity an `AuthInterceptor` stored for the call, e.g. decoded
+    /// token claims. `None` if the call wasn't authenticated or the
+    /// validator returns another type.
+    fn identity<I: Send + Sync + 'static>(&self) -> Option<&I>;
 }
 
 impl<T> RequestExt<T> for Request<T> {
@@ -78,6 +105,40 @@ impl<T> RequestExt<T> for Request<T> {
             .get(key.as_str())
             .and_then(|v| v.to_str().ok())
     }
+
+    fn proxied_addr(&self) -> Option<ProxiedAddr> {
+        let addr = self.extensions().get::<ProxiedAddr>().copied();
+
+        #[cfg(feature = "tls")]
+        let addr = addr.or_else(|| {
+            self.extensions()
+                .get::<tonic::transport::server::TlsConnectInfo<ProxiedAddr>>()
+                .map(|info| *info.get_ref())
+        });
+
+        addr
+    }
+
+    fn client_addr(&self) -> Option<SocketAddr> {
+        self.proxied_addr()
+            .map(|addr| addr.client())
+            .or_else(|| self.remote_addr())
+    }
+
+    fn client_ip(&self) -> Option<IpAddr> {
+        self.extensions()
+            .get::<ClientIp>()
+            .map(|ClientIp(ip)| *ip)
+            .or_else(|| self.client_addr().map(|addr| addr.ip().to_canonical()))
+    }
+
+    fn deadline(&self) -> Option<Deadline> {
+        self.extensions().get::<Deadline>().copied()
+    }
+
+    fn identity<I: Send + Sync + 'static>(&self) -> Option<&I> {
+        self.extensions().get::<I>()
+    }
 }
 
 #[cfg(test)]
@@ -116,6 +177,42 @@ mod tests {
         assert_eq!(request.header(CUSTOM), Some("custom-value"));
     }
 
+    #[test]
+    fn client_addr_prefers_proxy_source() {
+        let mut request = Request::new(());
+        assert_eq!(request.client_addr(), None);
+
+        let proxied = ProxiedAddr {
+            peer: "10.0.0.2:40000".parse().unwrap(),
+            source: Some("203.0.113.7:51234".parse().unwrap()),
+            destination: None,
+        };
+        request.extensions_mut().insert(proxied);
+        assert_eq!(request.proxied_addr(), Some(proxied));
+        assert_eq!(
+            request.client_addr(),
+            Some("203.0.113.7:51234".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn client_ip_prefers_resolved_ip() {
+        let mut request = Request::new(());
+        assert_eq!(request.client_ip(), None);
+
+        request.extensions_mut().insert(ProxiedAddr {
+            peer: "10.0.0.2:40000".parse().unwrap(),
+            source: Some("[::ffff:203.0.113.7]:51234".parse().unwrap()),
+            destination: None,
+        });
+        assert_eq!(request.client_ip(), Some("203.0.113.7".parse().unwrap()));
+
+        request
+            .extensions_mut()
+            .insert(ClientIp("198.51.100.1".parse().unwrap()));
+        assert_eq!(request.client_ip(), Some("198.51.100.1".parse().unwrap()));
+    }
+
     #[test]
     fn header_returns_none_when_missing() {
         let request = Request::new(());
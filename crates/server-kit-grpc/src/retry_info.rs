@@ -0,0 +1,141 @@
+//! `google.rpc.RetryInfo` error details.
+//!
+//! Rich error details travel in the `grpc-status-details-bin` trailer as an
+//! encoded `google.rpc.Status`. Only the messages needed for `RetryInfo` are
+//! defined here.
+
+use std::time::Duration;
+
+use prost::Message;
+use tonic::{Code, Status};
+
+const RETRY_INFO_TYPE_URL: &str = "type.googleapis.com/google.rpc.RetryInfo";
+
+/// `google.rpc.Status`
+#[derive(Clone, PartialEq, Message)]
+struct RpcStatus {
+    #[prost(int32, tag = "1")]
+    code: i32,
+    #[prost(string, tag = "2")]
+    message: String,
+    #[prost(message, repeated, tag = "3")]
+    details: Vec<Any>,
+}
+
+/// `google.protobuf.Any`
+#[derive(Clone, PartialEq, Message)]
+struct Any {
+    #[prost(string, tag = "1")]
+    type_url: String,
+    #[prost(bytes = "vec", tag = "2")]
+    value: Vec<u8>,
+}
+
+/// `google.rpc.RetryInfo`
+#[derive(Clone, PartialEq, Message)]
+struct RetryInfo {
+    #[prost(message, optional, tag = "1")]
+    retry_delay: Option<ProtoDuration>,
+}
+
+/// `google.protobuf.Duration`
+#[derive(Clone, PartialEq, Message)]
+struct ProtoDuration {
+    #[prost(int64, tag = "1")]
+    seconds: i64,
+    #[prost(int32, tag = "2")]
+    nanos: i32,
+}
+
+/// A `RESOURCE_EXHAUSTED` status telling the client when to retry.
+///
+/// # Example
+///
+/// ```ignore
+/// return Err(server_kit_grpc::resource_exhausted(
+///     "Quota exceeded",
+///     Duration::from_secs(30),
+/// ));
+/// ```
+pub fn resource_exhausted(message: impl Into<String>, retry_after: Duration) -> Status {
+    with_retry_delay(Code::ResourceExhausted, message, retry_after)
+}
+
+/// A status with `code` carrying `google.rpc.RetryInfo` details, e.g. an
+/// `UNAVAILABLE` for a shed call.
+pub fn with_retry_delay(code: Code, message: impl Into<String>, retry_after: Duration) -> Status {
+    let message = message.into();
+    let retry_info = RetryInfo {
+        retry_delay: Some(ProtoDuration {
+            seconds: retry_after.as_secs() as i64,
+            nanos: retry_after.subsec_nanos() as i32,
+        }),
+    };
+    let details = RpcStatus {
+        code: code as i32,
+        message: message.clone(),
+        details: vec![Any {
+            type_url: RETRY_INFO_TYPE_URL.to_string(),
+            value: retry_info.encode_to_vec(),
+        }],
+    };
+    Status::with_details(code, message, details.encode_to_vec().into())
+}
+
+/// The retry delay from a status's `google.rpc.RetryInfo` details, if any.
+///
+/// # Example
+///
+/// ```ignore
+/// match client.say_hello(request).await {
+///     Err(status) => {
+///         if let Some(delay) = server_kit_grpc::retry_delay(&status) {
+///             tokio::time::sleep(delay).await;
+///         }
+///     }
+///     Ok(response) => { /* ... */ }
+/// }
+/// ```
+pub fn retry_delay(status: &Status) -> Option<Duration> {
+    let details = RpcStatus::decode(status.details()).ok()?;
+    details
+        .details
+        .iter()
+        .filter(|any| any.type_url == RETRY_INFO_TYPE_URL)
+        .find_map(|any| RetryInfo::decode(any.value.as_slice()).ok()?.retry_delay)
+        .map(|delay| {
+            Duration::new(
+                delay.seconds.max(0) as u64,
+                delay.nanos.clamp(0, 999_999_999) as u32,
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_through_http() {
+        let status = resource_exhausted("Rate limit exceeded", Duration::from_millis(1500));
+        let response = status.into_http();
+        let status = Status::from_header_map(response.headers()).unwrap();
+
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert_eq!(status.message(), "Rate limit exceeded");
+        assert_eq!(retry_delay(&status), Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn no_delay_without_details() {
+        assert_eq!(retry_delay(&Status::resource_exhausted("busy")), None);
+        assert_eq!(
+            retry_delay(&Status::with_details(
+                Code::Internal,
+                "x",
+                vec![0xff, 0xff].into()
+            )),
+            None
+        );
+    }
+}
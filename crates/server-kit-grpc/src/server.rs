@@ -1,43 +1,205 @@
 //! Server extension traits for tonic.
 
+use std::future::Future;
 use std::net::SocketAddr;
-use tonic::transport::server::Router;
+use std::pin::Pin;
+
+use server_kit::{
+    Drain, InFlight, Listener, ProxyProtocolAcceptor, RestartRegistration, SdNotify, ServerHandle,
+    ShutdownController,
+};
+use tokio::net::TcpListener;
+use tonic::transport::server::{Router, TcpIncoming};
 
 use crate::config::GrpcServerConfig;
 use crate::error::ServerError;
-use crate::interceptor::{RequestIdLayer, TraceLayer};
+use crate::interceptor::{InFlightLayer, PanicLayer, RequestIdLayer, TraceLayer};
+use crate::proxy_protocol::ProxiedIncoming;
 
 /// Extension trait for `tonic::transport::Server`.
 pub trait ServerExt: Sized {
     type WithLayers;
 
-    /// Applies the default middleware stack (RequestIdLayer + TraceLayer).
+    /// Applies the default middleware stack
+    /// (InFlightLayer + RequestIdLayer + TraceLayer + PanicLayer).
     fn with_default_layers(self) -> Self::WithLayers;
+
+    /// Type returned by [`with_timeouts`](Self::with_timeouts).
+    type WithTimeouts;
+
+    /// Applies `request_timeout_secs` and `method_timeouts` through a
+    /// [`RequestTimeoutLayer`](crate::RequestTimeoutLayer), capped by the
+    /// client's `grpc-timeout`.
+    fn with_timeouts(self, config: &GrpcServerConfig) -> Self::WithTimeouts;
+
+    /// Type returned by [`with_concurrency_limit`](Self::with_concurrency_limit).
+    type WithConcurrencyLimit;
+
+    /// Applies the `concurrency` config section through a
+    /// [`ConcurrencyLimitLayer`](crate::ConcurrencyLimitLayer). Without
+    /// `enabled` no call is limited.
+    fn with_concurrency_limit(
+        self,
+        config: &server_kit::ConcurrencyConfig,
+    ) -> Self::WithConcurrencyLimit;
+
+    /// Type returned by [`with_authorization`](Self::with_authorization).
+    type WithAuthorization;
+
+    /// Applies the `authorization` config section through an
+    /// [`AuthorizeLayer`](crate::AuthorizeLayer). Add it after the
+    /// [`AuthLayer`](crate::AuthLayer) whose permissions it checks.
+    fn with_authorization(
+        self,
+        config: &server_kit::AuthorizationConfig,
+    ) -> Self::WithAuthorization;
+
+    /// Type returned by [`with_rate_limit`](Self::with_rate_limit).
+    #[cfg(feature = "ratelimit")]
+    type WithRateLimit;
+
+    /// Applies the `rate_limit` config section through a
+    /// [`RateLimitLayer`](crate::RateLimitLayer) registered with
+    /// [`reload_rate_limits`](crate::reload_rate_limits).
+    ///
+    /// Add it after `with_default_layers` and `ClientIpLayer`. Without
+    /// `enabled` no call is limited until a reload enables the section.
+    #[cfg(feature = "ratelimit")]
+    fn with_rate_limit(self, config: &server_kit::RateLimitConfig) -> Self::WithRateLimit;
 }
 
 impl<L> ServerExt for tonic::transport::server::Server<L> {
     type WithLayers = tonic::transport::server::Server<
-        tower::layer::util::Stack<TraceLayer, tower::layer::util::Stack<RequestIdLayer, L>>,
+        tower::layer::util::Stack<
+            PanicLayer,
+            tower::layer::util::Stack<
+                TraceLayer,
+                tower::layer::util::Stack<
+                    RequestIdLayer,
+                    tower::layer::util::Stack<InFlightLayer, L>,
+                >,
+            >,
+        >,
     >;
 
     fn with_default_layers(self) -> Self::WithLayers {
-        self.layer(RequestIdLayer::new()).layer(TraceLayer::new())
+        self.layer(InFlightLayer::new())
+            .layer(RequestIdLayer::new())
+            .layer(TraceLayer::new())
+            .layer(PanicLayer::new())
+    }
+
+    type WithTimeouts = tonic::transport::server::Server<
+        tower::layer::util::Stack<crate::interceptor::RequestTimeoutLayer, L>,
+    >;
+
+    fn with_timeouts(self, config: &GrpcServerConfig) -> Self::WithTimeouts {
+        self.layer(crate::interceptor::RequestTimeoutLayer::from_config(config))
+    }
+
+    type WithConcurrencyLimit = tonic::transport::server::Server<
+        tower::layer::util::Stack<crate::interceptor::ConcurrencyLimitLayer, L>,
+    >;
+
+    fn with_concurrency_limit(
+        self,
+        config: &server_kit::ConcurrencyConfig,
+    ) -> Self::WithConcurrencyLimit {
+        self.layer(crate::interceptor::ConcurrencyLimitLayer::from_config(config))
+    }
+
+    type WithAuthorization = tonic::transport::server::Server<
+        tower::layer::util::Stack<crate::interceptor::AuthorizeLayer, L>,
+    >;
+
+    fn with_authorization(
+        self,
+        config: &server_kit::AuthorizationConfig,
+    ) -> Self::WithAuthorization {
+        self.layer(crate::interceptor::AuthorizeLayer::from_config(config))
+    }
+
+    #[cfg(feature = "ratelimit")]
+    type WithRateLimit = tonic::transport::server::Server<
+        tower::layer::util::Stack<crate::interceptor::RateLimitLayer, L>,
+    >;
+
+    #[cfg(feature = "ratelimit")]
+    fn with_rate_limit(self, config: &server_kit::RateLimitConfig) -> Self::WithRateLimit {
+        self.layer(crate::interceptor::RateLimitLayer::from_config(config))
     }
 }
 
 /// Extension trait for `tonic::transport::server::Router`.
 pub trait RouterExt<L>: Sized {
     /// Serve the router using config with graceful shutdown.
+    ///
+    /// Under systemd socket activation the inherited socket named by
+    /// `listen_fd_name` is used instead of binding `host`/`port`, and
+    /// READY/STOPPING/WATCHDOG notifications are sent. With `hot_restart`,
+    /// SIGUSR2 hands the listening socket to a re-executed process and drains
+    /// this one once the new process is serving.
+    ///
+    /// On shutdown, health reports `NOT_SERVING`, the server keeps accepting
+    /// connections for `shutdown_delay_secs`, then waits up to
+    /// `shutdown_timeout_secs` for in-flight calls before closing the
+    /// remaining connections.
     fn serve_with(
         self,
         config: &(impl AsRef<GrpcServerConfig> + Sync),
     ) -> impl std::future::Future<Output = Result<(), ServerError>> + Send;
 
-    /// Serve at a specific address with graceful shutdown.
+    /// Serve at a specific address with graceful shutdown, using the default
+    /// shutdown timeout and no pre-stop delay.
     fn serve_at(
         self,
         addr: SocketAddr,
     ) -> impl std::future::Future<Output = Result<(), ServerError>> + Send;
+
+    /// Like `serve_with`, but shut down when `shutdown` is triggered.
+    ///
+    /// The controller's cleanup hooks run after in-flight calls have drained.
+    fn serve_with_controller(
+        self,
+        config: &(impl AsRef<GrpcServerConfig> + Sync),
+        shutdown: ShutdownController,
+    ) -> impl std::future::Future<Output = Result<(), ServerError>> + Send;
+
+    /// Like `serve_at`, but shut down when `shutdown` is triggered.
+    ///
+    /// Use `ShutdownController::without_signals` in tests to stop the server
+    /// deterministically.
+    fn serve_at_with_controller(
+        self,
+        addr: SocketAddr,
+        shutdown: ShutdownController,
+    ) -> impl std::future::Future<Output = Result<(), ServerError>> + Send;
+
+    /// Serve on an already bound TCP or Unix listener.
+    ///
+    /// `config.host` and `config.port` are ignored; TCP options still apply.
+    fn serve_on(
+        self,
+        listener: impl Into<Listener> + Send,
+        config: &(impl AsRef<GrpcServerConfig> + Sync),
+    ) -> impl std::future::Future<Output = Result<(), ServerError>> + Send;
+
+    /// Like `serve_on`, but shut down when `shutdown` is triggered.
+    fn serve_on_with_controller(
+        self,
+        listener: impl Into<Listener> + Send,
+        config: &(impl AsRef<GrpcServerConfig> + Sync),
+        shutdown: ShutdownController,
+    ) -> impl std::future::Future<Output = Result<(), ServerError>> + Send;
+
+    /// Bind (or inherit from systemd) and serve in a background task.
+    ///
+    /// The returned handle reports the bound address (useful with port `0`)
+    /// and stops the server.
+    fn spawn(
+        self,
+        config: &(impl AsRef<GrpcServerConfig> + Sync),
+    ) -> impl std::future::Future<Output = Result<ServerHandle<ServerError>, ServerError>> + Send;
 }
 
 impl<L> RouterExt<L> for Router<L>
@@ -49,7 +211,7 @@ where
         > + Clone
         + Send
         + 'static,
-    <L::Service as tower::Service<http::Request<tonic::body::BoxBody>>>::Future: Send,
+    <L::Service as tower::Service<http::Request<tonic::body::BoxBody>>>::Future: Send + 'static,
     <L::Service as tower::Service<http::Request<tonic::body::BoxBody>>>::Error:
         Into<Box<dyn std::error::Error + Send + Sync>> + Send,
 {
@@ -57,58 +219,287 @@ where
         self,
         config: &(impl AsRef<GrpcServerConfig> + Sync),
     ) -> Result<(), ServerError> {
-        let addr: SocketAddr = config
-            .as_ref()
-            .socket_addr()
-            .map_err(ServerError::InvalidAddress)?;
-        self.serve_at(addr).await
+        self.serve_with_controller(config, ShutdownController::new())
+            .await
     }
 
     async fn serve_at(self, addr: SocketAddr) -> Result<(), ServerError> {
-        tracing::info!(addr = %addr, "gRPC server listening");
+        self.serve_at_with_controller(addr, ShutdownController::new())
+            .await
+    }
 
-        self.serve_with_shutdown(addr, shutdown_signal())
+    async fn serve_with_controller(
+        self,
+        config: &(impl AsRef<GrpcServerConfig> + Sync),
+        shutdown: ShutdownController,
+    ) -> Result<(), ServerError> {
+        let config = config.as_ref();
+        let (listener, _restart) = listen(config).await?;
+        serve(self, listener, config, shutdown).await
+    }
+
+    async fn serve_at_with_controller(
+        self,
+        addr: SocketAddr,
+        shutdown: ShutdownController,
+    ) -> Result<(), ServerError> {
+        let listener = TcpListener::bind(addr).await.map_err(ServerError::Bind)?;
+        serve(
+            self,
+            listener.into(),
+            &GrpcServerConfig::default(),
+            shutdown,
+        )
+        .await
+    }
+
+    async fn serve_on(
+        self,
+        listener: impl Into<Listener> + Send,
+        config: &(impl AsRef<GrpcServerConfig> + Sync),
+    ) -> Result<(), ServerError> {
+        self.serve_on_with_controller(listener, config, ShutdownController::new())
             .await
-            .map_err(ServerError::Transport)?;
+    }
+
+    async fn serve_on_with_controller(
+        self,
+        listener: impl Into<Listener> + Send,
+        config: &(impl AsRef<GrpcServerConfig> + Sync),
+        shutdown: ShutdownController,
+    ) -> Result<(), ServerError> {
+        serve(self, listener.into(), config.as_ref(), shutdown).await
+    }
 
-        tracing::info!("gRPC server shutdown complete");
-        Ok(())
+    async fn spawn(
+        self,
+        config: &(impl AsRef<GrpcServerConfig> + Sync),
+    ) -> Result<ServerHandle<ServerError>, ServerError> {
+        let config = config.as_ref().clone();
+        let (listener, restart) = listen(&config).await?;
+        let local_addr = listener.local_addr().map_err(ServerError::Bind)?;
+
+        let shutdown = ShutdownController::new();
+        let task = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move {
+                let _restart = restart;
+                serve(self, listener, &config, shutdown).await
+            }
+        });
+        Ok(ServerHandle::new(local_addr, shutdown, task))
     }
 }
 
-/// Wait for shutdown signals (SIGINT, SIGTERM).
-pub async fn shutdown_signal() {
-    let ctrl_c = async {
-        tokio::signal::ctrl_c()
-            .await
-            .expect("Failed to install Ctrl+C handler");
-    };
+/// Bind or inherit the configured listener and offer it for hot restart if
+/// enabled.
+async fn listen(
+    config: &GrpcServerConfig,
+) -> Result<(Listener, Option<RestartRegistration>), ServerError> {
+    let addr = config.listen_addr();
+    let listener = Listener::bind_or_inherit(&addr, config.listen_fd_name.as_deref())
+        .await
+        .map_err(ServerError::Bind)?;
+    let restart = config
+        .hot_restart
+        .then(|| {
+            server_kit::register_for_restart(
+                &addr.to_string(),
+                &listener,
+                config.hot_restart_timeout(),
+            )
+        })
+        .transpose()
+        .map_err(ServerError::Bind)?;
+    Ok((listener, restart))
+}
 
-    #[cfg(unix)]
-    let terminate = async {
-        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
-            .expect("Failed to install SIGTERM handler")
-            .recv()
-            .await;
-    };
+async fn serve<L>(
+    router: Router<L>,
+    listener: Listener,
+    config: &GrpcServerConfig,
+    shutdown: ShutdownController,
+) -> Result<(), ServerError>
+where
+    L: tower::Layer<tonic::service::Routes> + Clone + Send + 'static,
+    L::Service: tower::Service<
+            http::Request<tonic::body::BoxBody>,
+            Response = http::Response<tonic::body::BoxBody>,
+        > + Clone
+        + Send
+        + 'static,
+    <L::Service as tower::Service<http::Request<tonic::body::BoxBody>>>::Future: Send + 'static,
+    <L::Service as tower::Service<http::Request<tonic::body::BoxBody>>>::Error:
+        Into<Box<dyn std::error::Error + Send + Sync>> + Send,
+{
+    let local_addr = listener.local_addr().map_err(ServerError::Bind)?;
+    tracing::info!(addr = %local_addr, "gRPC server listening");
+    server_kit::log_build_info();
+    #[cfg(feature = "metrics")]
+    crate::build_info::record_build_info();
 
-    #[cfg(not(unix))]
-    let terminate = std::future::pending::<()>();
+    let notify = SdNotify::global();
+    notify.ready(&format!("Listening on {}", local_addr));
+    let _watchdog = notify.spawn_watchdog();
+    server_kit::restart_ready();
 
-    tokio::select! {
-        _ = ctrl_c => {
-            tracing::info!("Received SIGINT, starting graceful shutdown...");
-        },
-        _ = terminate => {
-            tracing::info!("Received SIGTERM, starting graceful shutdown...");
-        },
+    let drain = Drain::new(InFlight::global().clone());
+    let signal = {
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown.wait().await;
+            notify.stopping();
+            #[cfg(feature = "health")]
+            crate::health::set_not_serving().await;
+        }
+    };
+    let graceful = drain.shutdown_after(signal, config.shutdown_delay());
+    let server: Pin<Box<dyn Future<Output = Result<(), tonic::transport::Error>> + Send>> =
+        match listener {
+            Listener::Tcp(listener) if config.proxy_protocol.enabled => {
+                tracing::info!("PROXY protocol enabled");
+                let acceptor = ProxyProtocolAcceptor::new(listener, &config.proxy_protocol)
+                    .map_err(ServerError::Bind)?;
+                let incoming =
+                    ProxiedIncoming::new(acceptor, config.tcp_nodelay, config.tcp_keepalive());
+                Box::pin(router.serve_with_incoming_shutdown(incoming, graceful))
+            }
+            Listener::Tcp(listener) => {
+                let incoming = TcpIncoming::from_listener(
+                    listener,
+                    config.tcp_nodelay,
+                    config.tcp_keepalive(),
+                )
+                .map_err(|e| ServerError::Bind(std::io::Error::other(e)))?;
+                Box::pin(router.serve_with_incoming_shutdown(incoming, graceful))
+            }
+            #[cfg(unix)]
+            Listener::Unix(_) if config.proxy_protocol.enabled => {
+                return Err(ServerError::Bind(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "PROXY protocol requires a TCP listener",
+                )));
+            }
+            #[cfg(unix)]
+            Listener::Unix(listener) => {
+                let incoming = tokio_stream::wrappers::UnixListenerStream::new(listener);
+                Box::pin(router.serve_with_incoming_shutdown(incoming, graceful))
+            }
+        };
+
+    let result = drain.run(server, config.shutdown_timeout()).await;
+    shutdown.run_hooks().await;
+    if let Some(result) = result {
+        result.map_err(ServerError::Transport)?;
     }
+
+    tracing::info!("gRPC server shutdown complete");
+    Ok(())
+}
+
+/// Wait for SIGINT, SIGTERM or `server_kit::request_shutdown`.
+///
+/// Equivalent to `ShutdownController::new().wait()`.
+pub async fn shutdown_signal() {
+    ShutdownController::new().wait().await;
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[tokio::test]
+    async fn serve_at_stops_when_triggered_from_code() {
+        let shutdown = ShutdownController::new().without_signals();
+        let router = tonic::transport::Server::builder()
+            .with_default_layers()
+            .add_routes(tonic::service::Routes::default());
+
+        let server = tokio::spawn(
+            router.serve_at_with_controller("127.0.0.1:0".parse().unwrap(), shutdown.clone()),
+        );
+
+        shutdown.shutdown();
+        server.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn spawn_reports_bound_port() {
+        let config = GrpcServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            ..Default::default()
+        };
+        let server = tonic::transport::Server::builder()
+            .add_routes(tonic::service::Routes::default())
+            .spawn(&config)
+            .await
+            .unwrap();
+
+        let addr = server.local_addr().as_tcp().unwrap();
+        assert_ne!(addr.port(), 0);
+        tokio::net::TcpStream::connect(addr).await.unwrap();
+
+        server.stop().await.unwrap();
+    }
+
+    #[cfg(feature = "health")]
+    #[tokio::test]
+    async fn proxy_protocol_exposes_client_address() {
+        use server_kit::ProxiedAddr;
+        use std::sync::{Arc, Mutex};
+        use tokio::io::AsyncWriteExt;
+        use tonic_health::pb::health_client::HealthClient;
+        use tonic_health::pb::HealthCheckRequest;
+
+        let mut config = GrpcServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            ..Default::default()
+        };
+        config.proxy_protocol.enabled = true;
+        config.proxy_protocol.trusted_sources = vec!["127.0.0.1".parse().unwrap()];
+
+        let seen = Arc::new(Mutex::new(None));
+        let capture = {
+            let seen = Arc::clone(&seen);
+            tower::util::MapRequestLayer::new(move |req: http::Request<tonic::body::BoxBody>| {
+                *seen.lock().unwrap() = req.extensions().get::<ProxiedAddr>().copied();
+                req
+            })
+        };
+        let (_, health) = tonic_health::server::health_reporter();
+        let server = tonic::transport::Server::builder()
+            .layer(capture)
+            .add_service(health)
+            .spawn(&config)
+            .await
+            .unwrap();
+        let addr = server.local_addr().as_tcp().unwrap();
+
+        let channel = tonic::transport::Endpoint::from_static("http://proxied")
+            .connect_with_connector(tower::service_fn(move |_| async move {
+                let mut stream = tokio::net::TcpStream::connect(addr).await?;
+                stream
+                    .write_all(b"PROXY TCP4 203.0.113.7 127.0.0.1 51234 443\r\n")
+                    .await?;
+                Ok::<_, std::io::Error>(hyper_util::rt::TokioIo::new(stream))
+            }))
+            .await
+            .unwrap();
+        let response = HealthClient::new(channel)
+            .check(HealthCheckRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().status, 1);
+
+        let proxied = seen.lock().unwrap().unwrap();
+        assert_eq!(proxied.client(), "203.0.113.7:51234".parse().unwrap());
+        assert_eq!(proxied.peer.ip(), addr.ip());
+
+        server.stop().await.unwrap();
+    }
+
     #[test]
     fn config_socket_addr_parsing() {
         let config = GrpcServerConfig {
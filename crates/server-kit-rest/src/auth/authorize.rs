@@ -0,0 +1,222 @@
+use axum::body::Body;
+use axum::extract::MatchedPath;
+use axum::http::Request;
+use axum::response::{IntoResponse, Response};
+use server_kit::{AuthorizationConfig, Permissions, Policy, PolicyMap};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+use super::AuthError;
+
+/// Require all of `scopes` on the routes the layer wraps.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use server_kit_rest::auth::{require_role, require_scopes, AuthExt};
+///
+/// let admin = Router::new()
+///     .route("/stats", get(stats))
+///     .route_layer(require_role("admin"));
+///
+/// let app = Router::new()
+///     .route("/orders", get(list_orders))
+///     .route(
+///         "/orders",
+///         post(create_order).route_layer(require_scopes(["orders:write"])),
+///     )
+///     .nest("/admin", admin)
+///     .with_jwt_auth(&config.jwt);
+/// ```
+pub fn require_scopes<I, S>(scopes: I) -> AuthorizeLayer
+where
+    I: IntoIterator<Item = S>,
+    S: Into<String>,
+{
+    AuthorizeLayer::policy(Policy::new().scopes(scopes))
+}
+
+/// Require `role` on the routes the layer wraps.
+pub fn require_role(role: impl Into<String>) -> AuthorizeLayer {
+    AuthorizeLayer::policy(Policy::new().roles([role.into()]))
+}
+
+/// Layer checking the [`Permissions`] inserted by
+/// [`AuthLayer`](super::AuthLayer) against a [`Policy`].
+///
+/// Requests without permissions, i.e. that weren't authenticated, get a 401
+/// and requests the policy doesn't allow a 403 ([`AuthError::Forbidden`]).
+/// It has to run inside the auth layer: add it with `route_layer` (or on a
+/// handler) before `with_auth`. Routes are matched on the route pattern, or
+/// the path for unmatched requests.
+#[derive(Debug, Clone)]
+pub struct AuthorizeLayer {
+    policies: Arc<PolicyMap>,
+}
+
+impl AuthorizeLayer {
+    pub fn new(policies: PolicyMap) -> Self {
+        Self {
+            policies: Arc::new(policies),
+        }
+    }
+
+    /// Apply `policy` to every request.
+    pub fn policy(policy: Policy) -> Self {
+        Self::new(PolicyMap::new().route("", policy))
+    }
+
+    /// Use the route policies of the `authorization` config section.
+    pub fn from_config(config: &AuthorizationConfig) -> Self {
+        Self::new(PolicyMap::from_config(config))
+    }
+}
+
+impl<S> Layer<S> for AuthorizeLayer {
+    type Service = AuthorizeService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthorizeService {
+            inner,
+            policies: Arc::clone(&self.policies),
+        }
+    }
+}
+
+/// Service created by [`AuthorizeLayer`].
+#[derive(Debug, Clone)]
+pub struct AuthorizeService<S> {
+    inner: S,
+    policies: Arc<PolicyMap>,
+}
+
+impl<S> Service<Request<Body>> for AuthorizeService<S>
+where
+    S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let route = req
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str())
+            .unwrap_or_else(|| req.uri().path());
+
+        if let Some(policy) = self.policies.policy_for(route) {
+            let error = match req.extensions().get::<Permissions>() {
+                None => Some(AuthError::MissingToken),
+                Some(permissions) if !policy.allows(permissions) => Some(AuthError::Forbidden),
+                Some(_) => None,
+            };
+            if let Some(error) = error {
+                return Box::pin(async move { Ok(error.into_response()) });
+            }
+        }
+        Box::pin(inner.call(req))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{AuthExt, TokenValidator};
+    use axum::http::StatusCode;
+    use axum::routing::{get, post};
+    use axum::Router;
+    use tower::ServiceExt;
+
+    /// Tokens are `scope,scope;role,role`.
+    #[derive(Clone)]
+    struct Grants;
+
+    impl TokenValidator for Grants {
+        type Identity = String;
+
+        fn validate(&self, token: &str) -> Result<String, AuthError> {
+            Ok(token.to_string())
+        }
+
+        fn permissions(&self, token: &String) -> Permissions {
+            let (scopes, roles) = token.split_once(';').unwrap_or((token, ""));
+            Permissions::new()
+                .scopes(scopes.split(',').filter(|s| !s.is_empty()))
+                .roles(roles.split(',').filter(|r| !r.is_empty()))
+        }
+    }
+
+    async fn status(app: &Router, method: &str, uri: &str, token: Option<&str>) -> StatusCode {
+        let mut req = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            req = req.header("Authorization", format!("Bearer {token}"));
+        }
+        let req = req.body(Body::empty()).unwrap();
+        app.clone().oneshot(req).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn checks_scopes_and_roles() {
+        let admin = Router::new()
+            .route("/stats", get(|| async { "stats" }))
+            .route_layer(require_role("admin"));
+        let app = Router::new()
+            .route("/orders", get(|| async { "orders" }))
+            .route(
+                "/orders",
+                post(|| async { "created" }).route_layer(require_scopes(["orders:write"])),
+            )
+            .nest("/admin", admin)
+            .with_auth(Grants);
+
+        let calls = [
+            ("GET", "/orders", Some(""), 200),
+            ("POST", "/orders", Some("orders:write"), 200),
+            ("POST", "/orders", Some("orders:read"), 403),
+            ("GET", "/admin/stats", Some(";admin"), 200),
+            ("GET", "/admin/stats", Some("orders:write"), 403),
+            ("GET", "/admin/stats", None, 401),
+        ];
+        for (method, uri, token, expected) in calls {
+            let status = status(&app, method, uri, token).await;
+            assert_eq!(status, expected, "{method} {uri} {token:?}");
+        }
+    }
+
+    #[tokio::test]
+    async fn loads_policies_from_config() {
+        let config: AuthorizationConfig = serde_json::from_value(serde_json::json!({
+            "routes": [
+                { "prefix": "/reports", "scopes": ["reports:read"], "roles": ["analyst", "admin"] },
+            ]
+        }))
+        .unwrap();
+        let app = Router::new()
+            .route("/reports/{id}", get(|| async { "report" }))
+            .route("/status", get(|| async { "up" }))
+            .with_authorization(&config)
+            .with_auth(Grants);
+
+        let calls = [
+            ("/reports/1", "reports:read;analyst", 200),
+            ("/reports/1", "reports:read", 403),
+            ("/reports/1", ";admin", 403),
+            ("/status", "", 200),
+        ];
+        for (uri, token, expected) in calls {
+            let status = status(&app, "GET", uri, Some(token)).await;
+            assert_eq!(status, expected, "{uri} {token}");
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use server_kit::{Permissions, TokenCache};
+use std::time::{Duration, SystemTime};
+
+use super::{AsyncTokenValidator, AuthError, ValidateFuture};
+
+/// Validator remembering the results of another one.
+///
+/// Accepted tokens are cached for `ttl`, rejected ones for the negative TTL
+/// (10 seconds by default). [`AuthError::Unavailable`] is never cached, so
+/// an outage of the backing store doesn't lock users out after it ends.
+/// Identities are dropped early when the inner validator's
+/// [`expires_at`](AsyncTokenValidator::expires_at) says they expire sooner.
+/// Keep `ttl` short enough that revoked sessions stop working in time, or
+/// call [`invalidate`](Self::invalidate) when revoking.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use server_kit_rest::auth::{AuthExt, CachedValidator};
+///
+/// let validator = CachedValidator::new(sessions, Duration::from_secs(30))
+///     .negative_ttl(Duration::from_secs(5));
+///
+/// Router::new()
+///     .route("/protected", get(handler))
+///     .with_auth(validator);
+/// ```
+pub struct CachedValidator<V: AsyncTokenValidator> {
+    inner: V,
+    cache: TokenCache<V::Identity, AuthError>,
+}
+
+impl<V: AsyncTokenValidator> CachedValidator<V> {
+    pub fn new(inner: V, ttl: Duration) -> Self {
+        Self {
+            inner,
+            cache: TokenCache::new(ttl),
+        }
+    }
+
+    /// How long rejected tokens are remembered; zero disables negative
+    /// caching.
+    pub fn negative_ttl(mut self, ttl: Duration) -> Self {
+        self.cache = self.cache.negative_ttl(ttl);
+        self
+    }
+
+    /// Maximum number of cached accepted tokens, 10 000 by default.
+    pub fn max_entries(mut self, max_entries: usize) -> Self {
+        self.cache = self.cache.max_entries(max_entries);
+        self
+    }
+
+    /// Maximum number of cached rejected tokens, 1 000 by default. They
+    /// are kept apart from accepted ones, so junk tokens can't push valid
+    /// sessions out of the cache.
+    pub fn max_negative_entries(mut self, max_entries: usize) -> Self {
+        self.cache = self.cache.max_negative_entries(max_entries);
+        self
+    }
+
+    /// Forget the cached result for `token`.
+    pub fn invalidate(&self, token: &str) {
+        self.cache.invalidate(token);
+    }
+}
+
+impl<V: AsyncTokenValidator> AsyncTokenValidator for CachedValidator<V> {
+    type Identity = V::Identity;
+
+    fn validate<'a>(&'a self, token: &'a str) -> ValidateFuture<'a, Self::Identity> {
+        Box::pin(async move {
+            if let Some(result) = self.cache.get(token) {
+                return result;
+            }
+            let result = self.inner.validate(token).await;
+            if !matches!(result, Err(AuthError::Unavailable(_))) {
+                let identity = result.as_ref().ok();
+                match identity.and_then(|identity| self.inner.expires_at(identity)) {
+                    Some(at) => self.cache.insert_until(token, result.clone(), at),
+                    None => self.cache.insert(token, result.clone()),
+                }
+            }
+            result
+        })
+    }
+
+    fn subject(&self, identity: &Self::Identity) -> Option<String> {
+        self.inner.subject(identity)
+    }
+
+    fn permissions(&self, identity: &Self::Identity) -> Permissions {
+        self.inner.permissions(identity)
+    }
+
+    fn expires_at(&self, identity: &Self::Identity) -> Option<SystemTime> {
+        self.inner.expires_at(identity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    /// Counts lookups; `down` tokens fail as if the store were unreachable
+    /// and the `short` session expires after 5 seconds.
+    #[derive(Default)]
+    struct Sessions {
+        lookups: AtomicUsize,
+    }
+
+    impl AsyncTokenValidator for Arc<Sessions> {
+        type Identity = String;
+
+        fn validate<'a>(&'a self, token: &'a str) -> ValidateFuture<'a, String> {
+            Box::pin(async move {
+                self.lookups.fetch_add(1, Ordering::SeqCst);
+                tokio::task::yield_now().await;
+                match token {
+                    "down" => Err(AuthError::Unavailable("store unreachable".into())),
+                    _ => match token.strip_prefix("session-") {
+                        Some(user) => Ok(user.to_string()),
+                        None => Err(AuthError::InvalidToken("unknown session".into())),
+                    },
+                }
+            })
+        }
+
+        fn expires_at(&self, user: &String) -> Option<SystemTime> {
+            (user == "short").then(|| SystemTime::now() + Duration::from_secs(5))
+        }
+    }
+
+    #[tokio::test]
+    async fn caches_accepted_and_rejected_tokens() {
+        let sessions = Arc::new(Sessions::default());
+        let validator = CachedValidator::new(Arc::clone(&sessions), Duration::from_secs(60));
+
+        for _ in 0..3 {
+            assert_eq!(validator.validate("session-ada").await.unwrap(), "ada");
+            assert!(matches!(
+                validator.validate("forged").await,
+                Err(AuthError::InvalidToken(_))
+            ));
+        }
+        assert_eq!(sessions.lookups.load(Ordering::SeqCst), 2);
+
+        validator.invalidate("session-ada");
+        validator.validate("session-ada").await.unwrap();
+        assert_eq!(sessions.lookups.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drops_identities_when_they_expire() {
+        let sessions = Arc::new(Sessions::default());
+        let validator = CachedValidator::new(Arc::clone(&sessions), Duration::from_secs(60));
+
+        validator.validate("session-ada").await.unwrap();
+        validator.validate("session-short").await.unwrap();
+        tokio::time::advance(Duration::from_secs(10)).await;
+        validator.validate("session-ada").await.unwrap();
+        validator.validate("session-short").await.unwrap();
+        assert_eq!(sessions.lookups.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn does_not_cache_unavailable() {
+        let sessions = Arc::new(Sessions::default());
+        let validator = CachedValidator::new(Arc::clone(&sessions), Duration::from_secs(60));
+
+        for _ in 0..2 {
+            assert!(matches!(
+                validator.validate("down").await,
+                Err(AuthError::Unavailable(_))
+            ));
+        }
+        assert_eq!(sessions.lookups.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn works_as_router_auth() {
+        use crate::auth::{Auth, AuthExt};
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use axum::routing::get;
+        use axum::Router;
+        use tower::ServiceExt;
+
+        let validator =
+            CachedValidator::new(Arc::new(Sessions::default()), Duration::from_secs(60));
+        let app = Router::new()
+            .route("/", get(|Auth(user): Auth<String>| async move { user }))
+            .with_auth(validator);
+
+        let status = |token: &'static str| {
+            let req = Request::builder()
+                .uri("/")
+                .header("Authorization", format!("Bearer {token}"))
+                .body(Body::empty())
+                .unwrap();
+            let app = app.clone();
+            async move { app.oneshot(req).await.unwrap().status() }
+        };
+        assert_eq!(status("session-ada").await, StatusCode::OK);
+        assert_eq!(status("forged").await, StatusCode::UNAUTHORIZED);
+        assert_eq!(status("down").await, StatusCode::SERVICE_UNAVAILABLE);
+    }
+}
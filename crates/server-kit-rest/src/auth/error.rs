@@ -2,12 +2,22 @@ use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use std::fmt;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum AuthError {
     MissingToken,
     InvalidToken(String),
     TokenExpired,
+    /// The token's `nbf` is in the future.
+    NotYetValid,
+    /// The token's `iss` isn't one of the accepted issuers.
+    InvalidIssuer,
+    /// The token isn't meant for this service: its `aud` doesn't match.
+    InvalidAudience,
+    /// The token lacks a required claim.
+    MissingClaim(String),
     Forbidden,
+    /// The token couldn't be checked, e.g. the session store is down.
+    Unavailable(String),
 }
 
 impl fmt::Display for AuthError {
@@ -16,7 +26,12 @@ impl fmt::Display for AuthError {
             Self::MissingToken => write!(f, "Missing authorization token"),
             Self::InvalidToken(msg) => write!(f, "Invalid token: {}", msg),
             Self::TokenExpired => write!(f, "Token has expired"),
+            Self::NotYetValid => write!(f, "Token is not valid yet"),
+            Self::InvalidIssuer => write!(f, "Token has an invalid issuer"),
+            Self::InvalidAudience => write!(f, "Token has an invalid audience"),
+            Self::MissingClaim(claim) => write!(f, "Token is missing claim: {}", claim),
             Self::Forbidden => write!(f, "Insufficient permissions"),
+            Self::Unavailable(msg) => write!(f, "Authentication unavailable: {}", msg),
         }
     }
 }
@@ -26,10 +41,15 @@ impl std::error::Error for AuthError {}
 impl IntoResponse for AuthError {
     fn into_response(self) -> Response {
         let status = match self {
-            Self::MissingToken | Self::InvalidToken(_) | Self::TokenExpired => {
-                StatusCode::UNAUTHORIZED
-            }
+            Self::MissingToken
+            | Self::InvalidToken(_)
+            | Self::TokenExpired
+            | Self::NotYetValid
+            | Self::InvalidIssuer
+            | Self::InvalidAudience
+            | Self::MissingClaim(_) => StatusCode::UNAUTHORIZED,
             Self::Forbidden => StatusCode::FORBIDDEN,
+            Self::Unavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
         };
 
         let body = serde_json::json!({
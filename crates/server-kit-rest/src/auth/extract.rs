@@ -0,0 +1,173 @@
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use std::convert::Infallible;
+use std::ops::Deref;
+
+use super::AuthError;
+
+/// Extractor for the identity [`AuthLayer`](super::AuthLayer) stored for the
+/// request, e.g. `Auth<Claims>` with JWT authentication.
+///
+/// Rejects with 401 when there is none, i.e. the route isn't behind the
+/// layer or the validator returns another type.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use server_kit_rest::auth::{Auth, Claims};
+///
+/// async fn profile(Auth(claims): Auth<Claims>) -> String {
+///     format!("Hello, {}", claims.sub)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Auth<T>(pub T);
+
+impl<T> Deref for Auth<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T, S> FromRequestParts<S> for Auth<T>
+where
+    T: Clone + Send + Sync + 'static,
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<T>()
+            .cloned()
+            .map(Auth)
+            .ok_or(AuthError::MissingToken)
+    }
+}
+
+/// Like [`Auth`], but `None` for unauthenticated requests, such as those let
+/// through by [`AuthLayer::optional`](super::AuthLayer::optional).
+#[derive(Debug, Clone)]
+pub struct OptionalAuth<T>(pub Option<T>);
+
+impl<T, S> FromRequestParts<S> for OptionalAuth<T>
+where
+    T: Clone + Send + Sync + 'static,
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(OptionalAuth(parts.extensions.get::<T>().cloned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::{AuthLayer, TokenValidator};
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use axum::routing::get;
+    use axum::{Extension, Router};
+    use server_kit::Subject;
+    use tower::ServiceExt;
+
+    #[derive(Debug, Clone, PartialEq)]
+    struct User {
+        name: String,
+    }
+
+    #[derive(Clone)]
+    struct Users;
+
+    impl TokenValidator for Users {
+        type Identity = User;
+
+        fn validate(&self, token: &str) -> Result<User, AuthError> {
+            match token.strip_prefix("user-") {
+                Some(name) => Ok(User {
+                    name: name.to_string(),
+                }),
+                None => Err(AuthError::InvalidToken("unknown token".into())),
+            }
+        }
+
+        fn subject(&self, user: &User) -> Option<String> {
+            Some(user.name.clone())
+        }
+    }
+
+    async fn call(app: &Router, token: Option<&str>) -> (StatusCode, String) {
+        let mut req = Request::builder().uri("/");
+        if let Some(token) = token {
+            req = req.header("Authorization", format!("Bearer {token}"));
+        }
+        let response = app
+            .clone()
+            .oneshot(req.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn handlers_receive_the_identity() {
+        let app =
+            Router::new()
+                .route(
+                    "/",
+                    get(
+                        |Auth(user): Auth<User>,
+                         Extension(Subject(subject)): Extension<Subject>| async move {
+                            assert_eq!(user.name, subject);
+                            user.name
+                        },
+                    ),
+                )
+                .layer(AuthLayer::new(Users));
+
+        assert_eq!(
+            call(&app, Some("user-ada")).await,
+            (StatusCode::OK, "ada".into())
+        );
+        assert_eq!(call(&app, Some("nobody")).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(call(&app, None).await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn optional_auth_lets_anonymous_requests_through() {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|OptionalAuth(user): OptionalAuth<User>| async move {
+                    user.map_or("anonymous".to_string(), |user| user.name)
+                }),
+            )
+            .layer(AuthLayer::optional(Users));
+
+        assert_eq!(
+            call(&app, Some("user-ada")).await,
+            (StatusCode::OK, "ada".into())
+        );
+        assert_eq!(call(&app, None).await, (StatusCode::OK, "anonymous".into()));
+        assert_eq!(call(&app, Some("nobody")).await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn auth_without_identity_is_unauthorized() {
+        let app = Router::new().route("/", get(|Auth(user): Auth<User>| async move { user.name }));
+
+        assert_eq!(
+            call(&app, Some("user-ada")).await.0,
+            StatusCode::UNAUTHORIZED
+        );
+    }
+}
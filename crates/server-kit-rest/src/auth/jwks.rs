@@ -0,0 +1,388 @@
+use axum::body::Bytes;
+use http_body_util::{BodyExt, Empty, Limited};
+use hyper_rustls::HttpsConnector;
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use jsonwebtoken::jwk::{AlgorithmParameters, EllipticCurve, Jwk, JwkSet, PublicKeyUse};
+use jsonwebtoken::{Algorithm, DecodingKey};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use super::error::AuthError;
+use super::jwt::JwtKeyError;
+
+/// Largest JWKS document accepted.
+const MAX_JWKS_BYTES: usize = 1024 * 1024;
+
+#[derive(Clone)]
+struct Key {
+    kid: Option<String>,
+    key: DecodingKey,
+    algorithm: Algorithm,
+}
+
+struct KeySet {
+    keys: Vec<Key>,
+    fetched: Instant,
+}
+
+/// Verification keys fetched from a JWKS endpoint, for
+/// [`JwtConfig::from_jwks`](super::JwtConfig::from_jwks).
+///
+/// Tokens pick their key with the `kid` header; a token without one needs a
+/// set with a single key. Keys are fetched on first use and kept for `ttl`
+/// (5 minutes by default). A token with an unknown `kid` triggers a refresh,
+/// so rotated keys are picked up without waiting for the TTL, but fetches
+/// are at least `min_refresh_interval` (30 seconds) apart, and concurrent
+/// requests share one fetch. When a fetch fails the previous keys stay in
+/// use, however old; only with no keys at all do requests fail with
+/// [`AuthError::Unavailable`].
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use server_kit_rest::auth::{AuthExt, Jwks, JwtConfig};
+///
+/// let jwks = Jwks::new("https://idp.example.com/.well-known/jwks.json")?
+///     .ttl(Duration::from_secs(600));
+///
+/// Router::new()
+///     .route("/protected", get(handler))
+///     .with_jwt_auth(&JwtConfig::from_jwks(jwks));
+/// ```
+pub struct Jwks {
+    url: http::Uri,
+    client: Client<HttpsConnector<HttpConnector>, Empty<Bytes>>,
+    ttl: Duration,
+    min_refresh_interval: Duration,
+    fetch_timeout: Duration,
+    pub(super) default_algorithm: Algorithm,
+    keys: RwLock<Option<Arc<KeySet>>>,
+    /// When the last fetch started; held while fetching.
+    last_fetch: tokio::sync::Mutex<Option<Instant>>,
+}
+
+impl fmt::Debug for Jwks {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jwks")
+            .field("url", &self.url)
+            .field("ttl", &self.ttl)
+            .field("min_refresh_interval", &self.min_refresh_interval)
+            .finish()
+    }
+}
+
+impl Jwks {
+    /// Fetch keys from `url`, over HTTPS (with the system's root
+    /// certificates) or plain HTTP.
+    pub fn new(url: &str) -> Result<Self, JwtKeyError> {
+        let url = url
+            .parse::<http::Uri>()
+            .map_err(|e| JwtKeyError::InvalidUrl(e.to_string()))?;
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let connector = hyper_rustls::HttpsConnectorBuilder::new()
+            .with_provider_and_native_roots(provider)
+            .map_err(JwtKeyError::Tls)?
+            .https_or_http()
+            .enable_http1()
+            .build();
+        Ok(Self {
+            url,
+            client: Client::builder(TokioExecutor::new()).build(connector),
+            ttl: Duration::from_secs(300),
+            min_refresh_interval: Duration::from_secs(30),
+            fetch_timeout: Duration::from_secs(5),
+            default_algorithm: Algorithm::RS256,
+            keys: RwLock::new(None),
+            last_fetch: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    /// How long fetched keys are used before fetching them again.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Minimum time between two fetches.
+    pub fn min_refresh_interval(mut self, interval: Duration) -> Self {
+        self.min_refresh_interval = interval;
+        self
+    }
+
+    /// How long a fetch may take, 5 seconds by default.
+    pub fn fetch_timeout(mut self, timeout: Duration) -> Self {
+        self.fetch_timeout = timeout;
+        self
+    }
+
+    /// Algorithm for RSA keys without an `alg` member, RS256 by default.
+    /// EC and Ed25519 keys follow from their curve.
+    pub fn default_algorithm(mut self, algorithm: Algorithm) -> Self {
+        self.default_algorithm = algorithm;
+        self
+    }
+
+    /// Fetch the keys now, e.g. at startup, regardless of the TTL and
+    /// refresh interval.
+    pub async fn refresh(&self) -> Result<(), AuthError> {
+        let mut last_fetch = self.last_fetch.lock().await;
+        *last_fetch = Some(Instant::now());
+        self.fetch().await
+    }
+
+    /// The key for `kid` from the keys fetched so far.
+    pub(crate) fn cached_key(&self, kid: Option<&str>) -> Option<(DecodingKey, Algorithm)> {
+        let keys = self.keys.read().unwrap().clone()?;
+        find(&keys, kid)
+    }
+
+    /// The key for `kid`, fetching the keys if they are stale or don't
+    /// have it.
+    pub(crate) async fn key(
+        &self,
+        kid: Option<&str>,
+    ) -> Result<(DecodingKey, Algorithm), AuthError> {
+        let keys = self.keys.read().unwrap().clone();
+        if let Some(keys) = &keys {
+            if keys.fetched.elapsed() < self.ttl {
+                if let Some(key) = find(keys, kid) {
+                    return Ok(key);
+                }
+            }
+        }
+
+        let fetched = self.refresh_if_due().await;
+        let Some(keys) = self.keys.read().unwrap().clone() else {
+            fetched?;
+            return Err(AuthError::Unavailable("JWKS not loaded yet".into()));
+        };
+        find(&keys, kid).ok_or_else(|| match kid {
+            Some(kid) => AuthError::InvalidToken(format!("unknown key id {kid:?}")),
+            None => AuthError::InvalidToken("token has no key id".into()),
+        })
+    }
+
+    async fn refresh_if_due(&self) -> Result<(), AuthError> {
+        let mut last_fetch = self.last_fetch.lock().await;
+        // Skip if a fetch just happened, possibly while waiting for the lock.
+        if last_fetch.is_some_and(|at| at.elapsed() < self.min_refresh_interval) {
+            return Ok(());
+        }
+        *last_fetch = Some(Instant::now());
+        self.fetch().await
+    }
+
+    async fn fetch(&self) -> Result<(), AuthError> {
+        let result = tokio::time::timeout(self.fetch_timeout, self.fetch_keys()).await;
+        let keys = match result {
+            Ok(Ok(keys)) => keys,
+            Ok(Err(e)) => {
+                // The error can name internal hosts, so it only goes to the
+                // log and the client gets a fixed message.
+                tracing::warn!(url = %self.url, error = %e, "Failed to fetch JWKS");
+                return Err(AuthError::Unavailable("JWKS fetch failed".into()));
+            }
+            Err(_) => {
+                tracing::warn!(url = %self.url, "Timed out fetching JWKS");
+                return Err(AuthError::Unavailable("JWKS fetch timed out".into()));
+            }
+        };
+        tracing::debug!(url = %self.url, keys = keys.len(), "Fetched JWKS");
+        *self.keys.write().unwrap() = Some(Arc::new(KeySet {
+            keys,
+            fetched: Instant::now(),
+        }));
+        Ok(())
+    }
+
+    async fn fetch_keys(&self) -> Result<Vec<Key>, String> {
+        let response = self
+            .client
+            .get(self.url.clone())
+            .await
+            .map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("JWKS endpoint returned {}", response.status()));
+        }
+        let body = Limited::new(response.into_body(), MAX_JWKS_BYTES)
+            .collect()
+            .await
+            .map_err(|e| e.to_string())?
+            .to_bytes();
+        let set: JwkSet = serde_json::from_slice(&body).map_err(|e| e.to_string())?;
+        Ok(set
+            .keys
+            .iter()
+            .filter_map(|jwk| self.key_from_jwk(jwk))
+            .collect())
+    }
+
+    /// A verification key from `jwk`, skipping encryption and symmetric
+    /// keys.
+    fn key_from_jwk(&self, jwk: &Jwk) -> Option<Key> {
+        if jwk.common.public_key_use == Some(PublicKeyUse::Encryption) {
+            return None;
+        }
+        let algorithm = match (&jwk.common.key_algorithm, &jwk.algorithm) {
+            (_, AlgorithmParameters::OctetKey(_)) => return None,
+            (Some(alg), _) => Algorithm::from_str(&alg.to_string()).ok()?,
+            (None, AlgorithmParameters::RSA(_)) => self.default_algorithm,
+            (None, AlgorithmParameters::EllipticCurve(params)) => match params.curve {
+                EllipticCurve::P256 => Algorithm::ES256,
+                EllipticCurve::P384 => Algorithm::ES384,
+                _ => return None,
+            },
+            (None, AlgorithmParameters::OctetKeyPair(_)) => Algorithm::EdDSA,
+        };
+        Some(Key {
+            kid: jwk.common.key_id.clone(),
+            key: DecodingKey::from_jwk(jwk).ok()?,
+            algorithm,
+        })
+    }
+}
+
+fn find(keys: &KeySet, kid: Option<&str>) -> Option<(DecodingKey, Algorithm)> {
+    let key = match kid {
+        Some(kid) => keys.keys.iter().find(|key| key.kid.as_deref() == Some(kid)),
+        None if keys.keys.len() == 1 => keys.keys.first(),
+        None => None,
+    }?;
+    Some((key.key.clone(), key.algorithm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::jwt::tests::{EC_PRIVATE_KEY, EC_PUBLIC_KEY, ED_PRIVATE_KEY, ED_PUBLIC_KEY};
+    use crate::auth::{AsyncTokenValidator, Claims, JwtConfig};
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Stub {
+        jwks: Mutex<serde_json::Value>,
+        hits: AtomicUsize,
+        down: AtomicBool,
+    }
+
+    async fn serve_jwks(State(stub): State<Arc<Stub>>) -> impl IntoResponse {
+        stub.hits.fetch_add(1, Ordering::SeqCst);
+        if stub.down.load(Ordering::SeqCst) {
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+        Json(stub.jwks.lock().unwrap().clone()).into_response()
+    }
+
+    async fn start(stub: Arc<Stub>) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let app = Router::new()
+            .route("/jwks.json", get(serve_jwks))
+            .with_state(stub);
+        tokio::spawn(async move { axum::serve(listener, app).await });
+        format!("http://{addr}/jwks.json")
+    }
+
+    fn signer(algorithm: Algorithm, public_key: &str, private_key: &str, kid: &str) -> JwtConfig {
+        JwtConfig::from_public_key(algorithm, public_key.as_bytes())
+            .unwrap()
+            .private_key(private_key.as_bytes())
+            .unwrap()
+            .key_id(kid)
+    }
+
+    fn ec_jwk() -> serde_json::Value {
+        serde_json::json!({
+            "kty": "EC", "crv": "P-256", "kid": "k1", "use": "sig",
+            "x": "MB5AKDYGcTxKdAjEYrL3inA5LtxS0u33Mb1GKAOBQeQ",
+            "y": "4gZOA9PZAoOe3epan0jwzNA808BgHhqmBjqS9wTX1M8",
+        })
+    }
+
+    fn ed_jwk() -> serde_json::Value {
+        serde_json::json!({
+            "kty": "OKP", "crv": "Ed25519", "kid": "k2",
+            "x": "GamRfh5NiT7axqhmjTCnIQC-ZZqiFAYSO_zDIQsAgTM",
+        })
+    }
+
+    #[tokio::test]
+    async fn caches_and_rotates_keys() {
+        let stub = Arc::new(Stub::default());
+        *stub.jwks.lock().unwrap() = serde_json::json!({ "keys": [ec_jwk()] });
+        let url = start(stub.clone()).await;
+        let jwks = Jwks::new(&url)
+            .unwrap()
+            .ttl(Duration::from_millis(200))
+            .min_refresh_interval(Duration::from_millis(100));
+        let verifier = JwtConfig::from_jwks(jwks);
+        let hits = || stub.hits.load(Ordering::SeqCst);
+
+        let k1 = signer(Algorithm::ES256, EC_PUBLIC_KEY, EC_PRIVATE_KEY, "k1");
+        let k1_token = k1.encode(&Claims::new("user-1", 60)).unwrap();
+        assert_eq!(verifier.validate(&k1_token).await.unwrap().sub, "user-1");
+        assert_eq!(verifier.validate(&k1_token).await.unwrap().sub, "user-1");
+        assert_eq!(hits(), 1);
+
+        // An unknown kid right after a fetch doesn't fetch again.
+        let unknown = k1.clone().key_id("k9");
+        let token = unknown.encode(&Claims::new("user-1", 60)).unwrap();
+        let result = verifier.validate(&token).await;
+        assert!(matches!(result, Err(AuthError::InvalidToken(_))));
+        assert_eq!(hits(), 1);
+
+        // A rotated key is fetched before the TTL runs out.
+        *stub.jwks.lock().unwrap() = serde_json::json!({ "keys": [ec_jwk(), ed_jwk()] });
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let k2 = signer(Algorithm::EdDSA, ED_PUBLIC_KEY, ED_PRIVATE_KEY, "k2");
+        let k2_token = k2.encode(&Claims::new("user-2", 60)).unwrap();
+        assert_eq!(verifier.validate(&k2_token).await.unwrap().sub, "user-2");
+        assert_eq!(hits(), 2);
+
+        // While the endpoint is down the expired keys stay in use.
+        stub.down.store(true, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(verifier.validate(&k1_token).await.unwrap().sub, "user-1");
+        assert_eq!(verifier.validate(&k2_token).await.unwrap().sub, "user-2");
+        assert_eq!(hits(), 3);
+
+        // Without any keys there's nothing to fall back on.
+        let cold = JwtConfig::from_jwks(Jwks::new(&url).unwrap());
+        let result = cold.validate(&k1_token).await;
+        assert!(matches!(result, Err(AuthError::Unavailable(msg)) if msg == "JWKS fetch failed"));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_token_for_another_algorithm() {
+        let stub = Arc::new(Stub::default());
+        *stub.jwks.lock().unwrap() = serde_json::json!({ "keys": [ed_jwk()] });
+        let url = start(stub).await;
+        let verifier: JwtConfig = serde_json::from_value(serde_json::json!({
+            "jwks_url": url,
+            "jwks_ttl_secs": 60,
+        }))
+        .unwrap();
+        assert!(!verifier.can_sign());
+
+        // kid k2 signed with an EC key claiming ES256.
+        let forged = signer(Algorithm::ES256, EC_PUBLIC_KEY, EC_PRIVATE_KEY, "k2");
+        let token = forged.encode(&Claims::new("admin", 60)).unwrap();
+        assert!(verifier.validate(&token).await.is_err());
+
+        let k2 = signer(Algorithm::EdDSA, ED_PUBLIC_KEY, ED_PRIVATE_KEY, "k2");
+        let token = k2.encode(&Claims::new("user-2", 60)).unwrap();
+        assert_eq!(verifier.validate(&token).await.unwrap().sub, "user-2");
+        assert_eq!(verifier.decode::<Claims>(&token).unwrap().sub, "user-2");
+    }
+}
@@ -6,49 +6,177 @@ use std::future::Future;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
+use std::time::SystemTime;
 use tower::{Layer, Service};
 
-use super::AuthError;
+use super::{AuthError, AuthorizeLayer};
+use server_kit::{AuthorizationConfig, Permissions, Subject};
 
+/// Validates bearer tokens for [`AuthLayer`].
+///
+/// Every `TokenValidator` is also an [`AsyncTokenValidator`].
 pub trait TokenValidator: Clone + Send + Sync + 'static {
-    fn validate(&self, token: &str) -> Result<(), AuthError>;
+    /// Who the token belongs to, e.g. decoded claims. [`AuthLayer`] inserts
+    /// it into the request extensions, where handlers read it with
+    /// [`Auth`](super::Auth). Use `()` when there is nothing to keep.
+    type Identity: Clone + Send + Sync + 'static;
+
+    fn validate(&self, token: &str) -> Result<Self::Identity, AuthError>;
+
+    /// The [`Subject`] to insert next to the identity, used to rate limit
+    /// per user. None by default.
+    fn subject(&self, _identity: &Self::Identity) -> Option<String> {
+        None
+    }
+
+    /// The scopes and roles checked by
+    /// [`AuthorizeLayer`](super::AuthorizeLayer). Empty by default, which
+    /// passes only policies requiring nothing beyond authentication.
+    fn permissions(&self, _identity: &Self::Identity) -> Permissions {
+        Permissions::default()
+    }
+
+    /// When the identity stops being valid, e.g. the token's `exp`.
+    /// [`CachedValidator`](super::CachedValidator) keeps it no longer than
+    /// that. None by default, leaving it to the cache TTL.
+    fn expires_at(&self, _identity: &Self::Identity) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// Future returned by [`AsyncTokenValidator::validate`].
+pub type ValidateFuture<'a, I> = Pin<Box<dyn Future<Output = Result<I, AuthError>> + Send + 'a>>;
+
+/// Validates bearer tokens asynchronously, e.g. against a session store or
+/// an introspection endpoint. Wrap it in a
+/// [`CachedValidator`](super::CachedValidator) to avoid a lookup per
+/// request.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// impl AsyncTokenValidator for Sessions {
+///     type Identity = User;
+///
+///     fn validate<'a>(&'a self, token: &'a str) -> ValidateFuture<'a, User> {
+///         Box::pin(async move {
+///             match self.store.get(token).await {
+///                 Ok(Some(user)) => Ok(user),
+///                 Ok(None) => Err(AuthError::InvalidToken("unknown session".into())),
+///                 Err(e) => Err(AuthError::Unavailable(e.to_string())),
+///             }
+///         })
+///     }
+/// }
+/// ```
+pub trait AsyncTokenValidator: Send + Sync + 'static {
+    /// See [`TokenValidator::Identity`].
+    type Identity: Clone + Send + Sync + 'static;
+
+    fn validate<'a>(&'a self, token: &'a str) -> ValidateFuture<'a, Self::Identity>;
+
+    /// See [`TokenValidator::subject`].
+    fn subject(&self, _identity: &Self::Identity) -> Option<String> {
+        None
+    }
+
+    /// See [`TokenValidator::permissions`].
+    fn permissions(&self, _identity: &Self::Identity) -> Permissions {
+        Permissions::default()
+    }
+
+    /// See [`TokenValidator::expires_at`].
+    fn expires_at(&self, _identity: &Self::Identity) -> Option<SystemTime> {
+        None
+    }
+}
+
+impl<V: TokenValidator> AsyncTokenValidator for V {
+    type Identity = V::Identity;
+
+    fn validate<'a>(&'a self, token: &'a str) -> ValidateFuture<'a, Self::Identity> {
+        Box::pin(std::future::ready(TokenValidator::validate(self, token)))
+    }
+
+    fn subject(&self, identity: &Self::Identity) -> Option<String> {
+        TokenValidator::subject(self, identity)
+    }
+
+    fn permissions(&self, identity: &Self::Identity) -> Permissions {
+        TokenValidator::permissions(self, identity)
+    }
+
+    fn expires_at(&self, identity: &Self::Identity) -> Option<SystemTime> {
+        TokenValidator::expires_at(self, identity)
+    }
 }
 
-#[derive(Clone)]
 pub struct AuthLayer<V> {
     validator: Arc<V>,
+    optional: bool,
 }
 
-impl<V: TokenValidator> AuthLayer<V> {
+impl<V> Clone for AuthLayer<V> {
+    fn clone(&self) -> Self {
+        Self {
+            validator: Arc::clone(&self.validator),
+            optional: self.optional,
+        }
+    }
+}
+
+impl<V: AsyncTokenValidator> AuthLayer<V> {
     pub fn new(validator: V) -> Self {
         Self {
             validator: Arc::new(validator),
+            optional: false,
+        }
+    }
+
+    /// Let requests without a token through unauthenticated, for handlers
+    /// using [`OptionalAuth`](super::OptionalAuth). Invalid tokens are still
+    /// rejected.
+    pub fn optional(validator: V) -> Self {
+        Self {
+            optional: true,
+            ..Self::new(validator)
         }
     }
 }
 
-impl<S, V: TokenValidator> Layer<S> for AuthLayer<V> {
+impl<S, V: AsyncTokenValidator> Layer<S> for AuthLayer<V> {
     type Service = AuthService<S, V>;
 
     fn layer(&self, inner: S) -> Self::Service {
         AuthService {
             inner,
             validator: Arc::clone(&self.validator),
+            optional: self.optional,
         }
     }
 }
 
-#[derive(Clone)]
 pub struct AuthService<S, V> {
     inner: S,
     validator: Arc<V>,
+    optional: bool,
+}
+
+impl<S: Clone, V> Clone for AuthService<S, V> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            validator: Arc::clone(&self.validator),
+            optional: self.optional,
+        }
+    }
 }
 
 impl<S, V> Service<Request<Body>> for AuthService<S, V>
 where
     S: Service<Request<Body>, Response = Response> + Clone + Send + 'static,
     S::Future: Send,
-    V: TokenValidator,
+    V: AsyncTokenValidator,
 {
     type Response = S::Response;
     type Error = S::Error;
@@ -58,7 +186,7 @@ where
         self.inner.poll_ready(cx)
     }
 
-    fn call(&mut self, req: Request<Body>) -> Self::Future {
+    fn call(&mut self, mut req: Request<Body>) -> Self::Future {
         let token = req
             .headers()
             .get("Authorization")
@@ -67,17 +195,27 @@ where
             .map(|s| s.to_string());
 
         let validator = Arc::clone(&self.validator);
+        let optional = self.optional;
         let clone = self.inner.clone();
         let mut inner = std::mem::replace(&mut self.inner, clone);
 
         Box::pin(async move {
             let Some(token) = token else {
+                if optional {
+                    return inner.call(req).await;
+                }
                 return Ok(AuthError::MissingToken.into_response());
             };
 
-            if let Err(e) = validator.validate(&token) {
-                return Ok(e.into_response());
+            let identity = match validator.validate(&token).await {
+                Ok(identity) => identity,
+                Err(e) => return Ok(e.into_response()),
+            };
+            if let Some(subject) = validator.subject(&identity) {
+                req.extensions_mut().insert(Subject(subject));
             }
+            req.extensions_mut().insert(validator.permissions(&identity));
+            req.extensions_mut().insert(identity);
 
             inner.call(req).await
         })
@@ -85,17 +223,25 @@ where
 }
 
 pub trait AuthExt {
-    fn with_auth<V: TokenValidator>(self, validator: V) -> Self;
+    fn with_auth<V: AsyncTokenValidator>(self, validator: V) -> Self;
+
+    /// Enforce the route policies of the `authorization` config section.
+    /// Call it before `with_auth`, so the policies see the permissions.
+    fn with_authorization(self, config: &AuthorizationConfig) -> Self;
 
     #[cfg(feature = "jwt")]
     fn with_jwt_auth(self, config: &impl AsRef<super::JwtConfig>) -> Self;
 }
 
 impl AuthExt for Router {
-    fn with_auth<V: TokenValidator>(self, validator: V) -> Self {
+    fn with_auth<V: AsyncTokenValidator>(self, validator: V) -> Self {
         self.layer(AuthLayer::new(validator))
     }
 
+    fn with_authorization(self, config: &AuthorizationConfig) -> Self {
+        self.route_layer(AuthorizeLayer::from_config(config))
+    }
+
     #[cfg(feature = "jwt")]
     fn with_jwt_auth(self, config: &impl AsRef<super::JwtConfig>) -> Self {
         self.with_auth(config.as_ref().clone())
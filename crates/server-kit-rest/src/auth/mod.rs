@@ -1,13 +1,26 @@
-//! Authentication middleware.
+//! Authentication and authorization middleware.
 
+mod authorize;
+mod cache;
 mod error;
+mod extract;
 mod layer;
 
 #[cfg(feature = "jwt")]
 mod jwt;
+#[cfg(feature = "jwks")]
+mod jwks;
 
+pub use authorize::{require_role, require_scopes, AuthorizeLayer, AuthorizeService};
+pub use cache::CachedValidator;
 pub use error::AuthError;
-pub use layer::{AuthExt, AuthLayer, TokenValidator};
+pub use extract::{Auth, OptionalAuth};
+pub use layer::{AsyncTokenValidator, AuthExt, AuthLayer, TokenValidator, ValidateFuture};
+pub use server_kit::{Permissions, Policy, PolicyMap};
 
 #[cfg(feature = "jwt")]
-pub use jwt::{Claims, JwtConfig};
+pub use jwt::{Claims, JwtConfig, JwtKeyError};
+#[cfg(feature = "jwt")]
+pub use jsonwebtoken::Algorithm;
+#[cfg(feature = "jwks")]
+pub use jwks::Jwks;
@@ -1,22 +1,72 @@
 //! Server configuration.
 
 use serde::{Deserialize, Serialize};
+use server_kit::ListenAddr;
 use std::time::Duration;
+use tracing::Level;
 
-pub use server_kit::{ConfigBuilder, ConfigError, Environment};
+pub use server_kit::{
+    AccessLogConfig, AccessLogFormat, AccessLogOutput, AdaptiveConcurrencyConfig,
+    AuthorizationConfig, ConcurrencyConfig, ConfigBuilder, ConfigError, Environment,
+    ForwardedHeader, IpFilterConfig, IpNetwork, ProxyProtocolConfig, RateLimitConfig,
+    RateLimitKey, RateLimitQuota, RouteConcurrency, RoutePolicy, RouteRateLimit, RouteTimeout,
+    TrustedProxies,
+};
 
 /// Server configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct ServerConfig {
     pub environment: Environment,
+    /// Host to bind, or `unix:///path/to/socket` to listen on a Unix
+    /// domain socket (the port is then ignored).
     pub host: String,
     pub port: u16,
+    /// `FileDescriptorName=` of the systemd socket to serve on when started
+    /// by socket activation. Without a name the first inherited socket is
+    /// used; with none inherited, `host` and `port` are bound as usual.
+    pub listen_fd_name: Option<String>,
+    /// Timeout for handling a request; `0` disables it.
     pub request_timeout_secs: u64,
+    /// Timeout overrides for routes starting with a prefix; the first match
+    /// wins.
+    pub route_timeouts: Vec<RouteTimeout>,
+    /// Hand listening sockets to a re-executed process on SIGUSR2, then
+    /// drain and exit once it is ready (Unix only).
+    pub hot_restart: bool,
+    /// How long the re-executed process has to become ready.
+    pub hot_restart_timeout_secs: u64,
+    /// How long in-flight requests may take to finish after shutdown starts.
+    pub shutdown_timeout_secs: u64,
+    /// Pre-stop delay: after the shutdown signal, `/ready` fails but new
+    /// connections are still accepted for this long before draining starts.
+    pub shutdown_delay_secs: u64,
     /// CORS allowed origins. Empty means CORS is disabled.
     /// Only used when `cors` feature is enabled.
     #[serde(default)]
     pub cors_origins: Vec<String>,
+    /// Request tracing settings used by `with_default_layers`.
+    pub trace: TraceConfig,
+    /// Access log settings. Disabled by default.
+    pub access_log: AccessLogConfig,
+    /// PROXY protocol settings for TCP listeners. Disabled by default.
+    pub proxy_protocol: ProxyProtocolConfig,
+    /// Proxies (CIDRs or IPs) whose `X-Forwarded-For` header (or
+    /// `Forwarded`, if configured) is believed when resolving `ClientIp`.
+    /// Empty means the socket peer is always the client.
+    pub trusted_proxies: TrustedProxies,
+    /// Allow/deny rules for routes wrapped in `IpFilterLayer::from_config`.
+    /// Not applied by `with_default_layers`.
+    pub ip_filter: IpFilterConfig,
+    /// Rate limits applied by `with_default_layers` when enabled and the
+    /// `ratelimit` feature is on.
+    pub rate_limit: RateLimitConfig,
+    /// Concurrency limits and load shedding applied by `with_default_layers`
+    /// when enabled.
+    pub concurrency: ConcurrencyConfig,
+    /// Scope and role requirements per route prefix, enforced by
+    /// `AuthExt::with_authorization` when the `auth` feature is on.
+    pub authorization: AuthorizationConfig,
 }
 
 impl Default for ServerConfig {
@@ -25,8 +75,22 @@ impl Default for ServerConfig {
             environment: Environment::default(),
             host: "0.0.0.0".to_string(),
             port: 3000,
+            listen_fd_name: None,
             request_timeout_secs: 30,
+            route_timeouts: Vec::new(),
+            hot_restart: false,
+            hot_restart_timeout_secs: 30,
+            shutdown_timeout_secs: 30,
+            shutdown_delay_secs: 0,
             cors_origins: Vec::new(),
+            trace: TraceConfig::default(),
+            access_log: AccessLogConfig::default(),
+            proxy_protocol: ProxyProtocolConfig::default(),
+            trusted_proxies: TrustedProxies::default(),
+            ip_filter: IpFilterConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            concurrency: ConcurrencyConfig::default(),
+            authorization: AuthorizationConfig::default(),
         }
     }
 }
@@ -50,8 +114,21 @@ impl ServerConfig {
         Duration::from_secs(self.request_timeout_secs)
     }
 
-    pub(crate) fn addr(&self) -> String {
-        format!("{}:{}", self.host, self.port)
+    pub fn hot_restart_timeout(&self) -> Duration {
+        Duration::from_secs(self.hot_restart_timeout_secs)
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout_secs)
+    }
+
+    pub fn shutdown_delay(&self) -> Duration {
+        Duration::from_secs(self.shutdown_delay_secs)
+    }
+
+    /// Address to listen on, TCP or Unix socket.
+    pub fn listen_addr(&self) -> ListenAddr {
+        ListenAddr::from_host_port(&self.host, self.port)
     }
 }
 
@@ -61,6 +138,63 @@ impl AsRef<ServerConfig> for ServerConfig {
     }
 }
 
+/// Request tracing configuration.
+///
+/// Loaded from the `trace` section of the config file, or from
+/// `TRACE__*` environment variables.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TraceConfig {
+    /// Level for 1xx, 2xx and 3xx responses.
+    #[serde(with = "level_serde")]
+    pub success_level: Level,
+    /// Level for 4xx responses.
+    #[serde(with = "level_serde")]
+    pub client_error_level: Level,
+    /// Level for 5xx responses.
+    #[serde(with = "level_serde")]
+    pub server_error_level: Level,
+    /// Request headers recorded on the response event.
+    pub request_headers: Vec<String>,
+    /// Response headers recorded on the response event.
+    pub response_headers: Vec<String>,
+    /// Requests slower than this are logged at WARN. `None` disables the check.
+    pub slow_request_threshold_ms: Option<u64>,
+}
+
+impl Default for TraceConfig {
+    fn default() -> Self {
+        Self {
+            success_level: Level::DEBUG,
+            client_error_level: Level::WARN,
+            server_error_level: Level::ERROR,
+            request_headers: Vec::new(),
+            response_headers: Vec::new(),
+            slow_request_threshold_ms: Some(1000),
+        }
+    }
+}
+
+impl TraceConfig {
+    pub fn slow_request_threshold(&self) -> Option<Duration> {
+        self.slow_request_threshold_ms.map(Duration::from_millis)
+    }
+}
+
+mod level_serde {
+    use serde::{de, Deserialize, Deserializer, Serializer};
+    use tracing::Level;
+
+    pub fn serialize<S: Serializer>(level: &Level, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&level.as_str().to_lowercase())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Level, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -115,6 +249,8 @@ mod tests {
         assert_eq!(config.request_timeout_secs, 30);
         assert!(config.cors_origins.is_empty());
         assert!(config.environment.is_development());
+        assert!(!config.hot_restart);
+        assert_eq!(config.hot_restart_timeout(), Duration::from_secs(30));
     }
 
     #[test]
@@ -124,7 +260,22 @@ mod tests {
             port: 8080,
             ..Default::default()
         };
-        assert_eq!(config.addr(), "127.0.0.1:8080");
+        assert_eq!(
+            config.listen_addr(),
+            ListenAddr::Tcp("127.0.0.1:8080".to_string())
+        );
+    }
+
+    #[test]
+    fn server_config_unix_socket() {
+        let config = ServerConfig {
+            host: "unix:///run/app.sock".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            config.listen_addr(),
+            ListenAddr::Unix(PathBuf::from("/run/app.sock"))
+        );
     }
 
     #[test]
@@ -136,6 +287,278 @@ mod tests {
         assert_eq!(config.request_timeout(), Duration::from_secs(60));
     }
 
+    #[test]
+    fn server_config_shutdown_settings() {
+        let config = ServerConfig::default();
+        assert_eq!(config.shutdown_timeout(), Duration::from_secs(30));
+        assert_eq!(config.shutdown_delay(), Duration::ZERO);
+
+        let config = ServerConfig {
+            shutdown_timeout_secs: 10,
+            shutdown_delay_secs: 5,
+            ..Default::default()
+        };
+        assert_eq!(config.shutdown_timeout(), Duration::from_secs(10));
+        assert_eq!(config.shutdown_delay(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn trace_config_defaults() {
+        let config = TraceConfig::default();
+        assert_eq!(config.success_level, Level::DEBUG);
+        assert_eq!(config.client_error_level, Level::WARN);
+        assert_eq!(config.server_error_level, Level::ERROR);
+        assert_eq!(config.slow_request_threshold(), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn config_builder_loads_trace_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+
+        std::fs::write(
+            &config_path,
+            r#"
+            [trace]
+            success_level = "info"
+            server_error_level = "warn"
+            request_headers = ["x-tenant-id"]
+            slow_request_threshold_ms = 250
+            "#,
+        )
+        .unwrap();
+
+        let config: ServerConfig = ServerConfig::builder()
+            .with_config_file(&config_path)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.trace.success_level, Level::INFO);
+        assert_eq!(config.trace.client_error_level, Level::WARN);
+        assert_eq!(config.trace.server_error_level, Level::WARN);
+        assert_eq!(config.trace.request_headers, vec!["x-tenant-id"]);
+        assert_eq!(
+            config.trace.slow_request_threshold(),
+            Some(Duration::from_millis(250))
+        );
+    }
+
+    #[test]
+    fn config_builder_loads_access_log_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+
+        std::fs::write(
+            &config_path,
+            r#"
+access_log:
+  enabled: true
+  format: common
+  output: file
+  path: /var/log/app/access.log
+"#,
+        )
+        .unwrap();
+
+        let config: ServerConfig = ServerConfig::builder()
+            .with_config_file(&config_path)
+            .build()
+            .unwrap();
+
+        assert!(config.access_log.enabled);
+        assert_eq!(config.access_log.format, AccessLogFormat::Common);
+        assert_eq!(config.access_log.output, AccessLogOutput::File);
+        assert_eq!(
+            config.access_log.path,
+            Some(PathBuf::from("/var/log/app/access.log"))
+        );
+    }
+
+    #[test]
+    fn config_builder_loads_proxy_protocol_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+
+        std::fs::write(
+            &config_path,
+            r#"
+            [proxy_protocol]
+            enabled = true
+            trusted_sources = ["10.0.0.0/8", "192.168.1.10"]
+            "#,
+        )
+        .unwrap();
+
+        let config: ServerConfig = ServerConfig::builder()
+            .with_config_file(&config_path)
+            .build()
+            .unwrap();
+
+        assert!(config.proxy_protocol.enabled);
+        assert!(config.proxy_protocol.is_trusted("10.1.2.3".parse().unwrap()));
+        assert!(config.proxy_protocol.is_trusted("192.168.1.10".parse().unwrap()));
+        assert!(!config.proxy_protocol.is_trusted("192.168.1.11".parse().unwrap()));
+        assert_eq!(config.proxy_protocol.header_timeout(), Duration::from_secs(5));
+    }
+
+    #[test]
+    fn config_builder_loads_trusted_proxies() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(&config_path, r#"trusted_proxies = ["10.0.0.0/8", "::1"]"#).unwrap();
+
+        let config: ServerConfig = ServerConfig::builder()
+            .with_config_file(&config_path)
+            .build()
+            .unwrap();
+
+        assert!(config.trusted_proxies.is_trusted("10.0.0.7".parse().unwrap()));
+        assert!(config.trusted_proxies.is_trusted("::1".parse().unwrap()));
+        assert!(!config.trusted_proxies.is_trusted("203.0.113.7".parse().unwrap()));
+        assert!(ServerConfig::default().trusted_proxies.is_empty());
+
+        let table = "[trusted_proxies]\nnetworks = [\"10.0.0.0/8\"]\nheader = \"forwarded\"\n";
+        std::fs::write(&config_path, table).unwrap();
+        let config: ServerConfig = ServerConfig::builder()
+            .with_config_file(&config_path)
+            .build()
+            .unwrap();
+        let expected = TrustedProxies::new(["10.0.0.0/8".parse().unwrap()])
+            .header(ForwardedHeader::Forwarded);
+        assert_eq!(config.trusted_proxies, expected);
+    }
+
+    #[test]
+    fn config_builder_loads_ip_filter_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        std::fs::write(
+            &config_path,
+            r#"
+ip_filter:
+  allow: ["203.0.113.0/24", "10.8.0.0/16"]
+  deny: ["10.8.99.0/24"]
+"#,
+        )
+        .unwrap();
+
+        let config: ServerConfig = ServerConfig::builder()
+            .with_config_file(&config_path)
+            .build()
+            .unwrap();
+
+        assert_eq!(config.ip_filter.allow.len(), 2);
+        assert!(config.ip_filter.is_allowed("10.8.1.1".parse().unwrap()));
+        assert!(!config.ip_filter.is_allowed("10.8.99.1".parse().unwrap()));
+    }
+
+    #[test]
+    fn config_builder_loads_rate_limit_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            r#"
+[rate_limit]
+enabled = true
+key = { header = "x-api-key" }
+requests = 100
+exempt_routes = ["/health"]
+
+[[rate_limit.routes]]
+prefix = "/auth/login"
+requests = 5
+period_secs = 60
+"#,
+        )
+        .unwrap();
+
+        let config: ServerConfig = ServerConfig::builder()
+            .with_config_file(&config_path)
+            .build()
+            .unwrap();
+
+        let rate_limit = &config.rate_limit;
+        assert!(rate_limit.enabled);
+        assert_eq!(rate_limit.key, RateLimitKey::Header("x-api-key".to_string()));
+        assert_eq!(rate_limit.quota(), Some(RateLimitQuota::per_second(100)));
+        assert_eq!(
+            rate_limit.routes[0].quota(),
+            Some(RateLimitQuota::per_minute(5))
+        );
+        assert!(rate_limit.is_exempt("/health", None));
+        assert!(!ServerConfig::default().rate_limit.enabled);
+    }
+
+    #[test]
+    fn config_builder_loads_concurrency_section() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.yaml");
+        std::fs::write(
+            &config_path,
+            r#"
+concurrency:
+  enabled: true
+  max_in_flight: 200
+  queue_timeout_ms: 250
+  routes:
+    - prefix: /reports
+      max_in_flight: 4
+  adaptive:
+    enabled: true
+    latency_target_ms: 100
+"#,
+        )
+        .unwrap();
+
+        let config: ServerConfig = ServerConfig::builder()
+            .with_config_file(&config_path)
+            .build()
+            .unwrap();
+
+        let concurrency = &config.concurrency;
+        assert!(concurrency.enabled);
+        assert_eq!(concurrency.max_in_flight, 200);
+        assert_eq!(concurrency.max_queue, 100);
+        assert_eq!(concurrency.queue_timeout(), Duration::from_millis(250));
+        assert_eq!(concurrency.routes[0].prefix, "/reports");
+        assert!(concurrency.adaptive.enabled);
+        assert_eq!(concurrency.adaptive.decrease_factor, 0.9);
+        assert!(!ServerConfig::default().concurrency.enabled);
+    }
+
+    #[test]
+    fn config_builder_loads_route_timeouts() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        std::fs::write(
+            &config_path,
+            r#"
+request_timeout_secs = 10
+
+[[route_timeouts]]
+prefix = "/reports"
+timeout_ms = 120000
+"#,
+        )
+        .unwrap();
+
+        let config: ServerConfig = ServerConfig::builder()
+            .with_config_file(&config_path)
+            .build()
+            .unwrap();
+
+        let timeouts = server_kit::RequestTimeouts::from_config(
+            config.request_timeout(),
+            &config.route_timeouts,
+        );
+        assert_eq!(timeouts.for_route("/users"), Some(Duration::from_secs(10)));
+        assert_eq!(
+            timeouts.for_route("/reports/daily"),
+            Some(Duration::from_secs(120))
+        );
+    }
+
     #[test]
     fn config_builder_loads_toml() {
         let dir = tempfile::tempdir().unwrap();
@@ -51,10 +51,7 @@ pub trait HttpError: std::fmt::Debug {
     where
         Self: Sized,
     {
-        let body = ErrorResponse {
-            code: self.error_code(),
-            message: self.message().to_string(),
-        };
+        let body = ErrorResponse::new(self.error_code(), self.message());
         (self.status_code(), axum::Json(body)).into_response()
     }
 }
@@ -64,6 +61,8 @@ pub trait HttpError: std::fmt::Debug {
 pub struct ErrorResponse {
     pub code: String,
     pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
 }
 
 impl ErrorResponse {
@@ -71,15 +70,19 @@ impl ErrorResponse {
         Self {
             code: code.into(),
             message: message.into(),
+            request_id: None,
         }
     }
 
     /// Create an error response from a status code.
     pub fn from_status(status: StatusCode, message: impl Into<String>) -> Self {
-        Self {
-            code: status_to_error_code(status),
-            message: message.into(),
-        }
+        Self::new(status_to_error_code(status), message)
+    }
+
+    /// Attach the request ID so clients can quote it in bug reports.
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
     }
 }
 
@@ -112,6 +115,17 @@ mod tests {
         assert_eq!(resp.message, "Test message");
     }
 
+    #[test]
+    fn error_response_request_id_is_optional() {
+        let resp = ErrorResponse::new("TEST_CODE", "Test message");
+        let json = serde_json::to_value(&resp).unwrap();
+        assert!(json.get("request_id").is_none());
+
+        let resp = resp.with_request_id("req-1");
+        let json = serde_json::to_value(&resp).unwrap();
+        assert_eq!(json["request_id"], "req-1");
+    }
+
     #[test]
     fn error_response_from_status() {
         let resp = ErrorResponse::from_status(StatusCode::NOT_FOUND, "Resource not found");
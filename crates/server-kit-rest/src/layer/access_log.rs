@@ -0,0 +1,132 @@
+use super::ClientIp;
+use axum::http::{Request, Response};
+use server_kit::{AccessLog, AccessLogBody, AccessLogConfig, AccessLogRecord};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+use tower::{Layer, Service};
+
+/// Layer that writes one access log line per request.
+///
+/// The line is written when the response body has been sent, so
+/// `$body_bytes_sent` reflects what actually went over the wire.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use server_kit_rest::{AccessLog, AccessLogFormat, AccessLogLayer};
+///
+/// let app = Router::new()
+///     .route("/", get(handler))
+///     .layer(AccessLogLayer::new(AccessLog::stdout(AccessLogFormat::Combined)));
+/// ```
+#[derive(Clone)]
+pub struct AccessLogLayer {
+    log: Arc<AccessLog>,
+}
+
+impl AccessLogLayer {
+    pub fn new(log: AccessLog) -> Self {
+        Self { log: Arc::new(log) }
+    }
+
+    /// Create the layer from the `access_log` config section.
+    pub fn from_config(config: &AccessLogConfig) -> std::io::Result<Self> {
+        AccessLog::from_config(config).map(Self::new)
+    }
+}
+
+impl<S> Layer<S> for AccessLogLayer {
+    type Service = AccessLogService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AccessLogService {
+            inner,
+            log: Arc::clone(&self.log),
+        }
+    }
+}
+
+/// Service created by [`AccessLogLayer`].
+#[derive(Clone)]
+pub struct AccessLogService<S> {
+    inner: S,
+    log: Arc<AccessLog>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for AccessLogService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+{
+    type Response = Response<AccessLogBody<ResBody>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let start = Instant::now();
+        let mut record = AccessLogRecord::from_request(&req);
+        record.remote_addr = ClientIp::from_extensions(req.extensions());
+
+        let log = Arc::clone(&self.log);
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let response = inner.call(req).await?;
+            record.set_response(&response);
+
+            Ok(response.map(|body| AccessLogBody::new(body, log, record, start)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use server_kit::AccessLogFormat;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn writes_line_after_body_is_sent() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let log = AccessLog::file(
+            AccessLogFormat::Custom("$request $status $body_bytes_sent $request_id".into()),
+            &path,
+        )
+        .unwrap();
+
+        let app = Router::new()
+            .route("/hello", get(|| async { "hello world" }))
+            .layer(AccessLogLayer::new(log));
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/hello?name=x")
+                    .header("x-request-id", "abc")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        response.into_body().collect().await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, "GET /hello?name=x HTTP/1.1 200 11 abc\n");
+    }
+}
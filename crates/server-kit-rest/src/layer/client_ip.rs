@@ -0,0 +1,187 @@
+use crate::ErrorResponse;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use axum::http::{Extensions, Request, StatusCode};
+use axum::response::{IntoResponse, Response};
+use server_kit::TrustedProxies;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// The client's IP address.
+///
+/// Set by [`ClientIpLayer`] (part of `with_default_layers`) from
+/// `X-Forwarded-For`, or `Forwarded` if configured, when the request came
+/// through one of the configured `trusted_proxies`. Without the layer, the socket peer is used.
+/// Rate limiting, IP filtering and the access log read the same value.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use server_kit_rest::ClientIp;
+///
+/// async fn handler(ClientIp(ip): ClientIp) -> String {
+///     format!("Hello, {ip}")
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+impl ClientIp {
+    /// The client IP of a request, from [`ClientIpLayer`] or the socket peer.
+    ///
+    /// Returns `None` for requests without connection info, such as those
+    /// served over a Unix socket.
+    pub fn from_extensions(extensions: &Extensions) -> Option<IpAddr> {
+        extensions
+            .get::<ClientIp>()
+            .map(|ClientIp(ip)| *ip)
+            .or_else(|| {
+                extensions
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|ConnectInfo(addr)| addr.ip().to_canonical())
+            })
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Self::from_extensions(&parts.extensions)
+            .map(ClientIp)
+            .ok_or_else(|| {
+                let status = StatusCode::INTERNAL_SERVER_ERROR;
+                let body = ErrorResponse::from_status(status, "Client IP unavailable");
+                (status, axum::Json(body)).into_response()
+            })
+    }
+}
+
+/// Layer that resolves [`ClientIp`] once per request.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// let app = Router::new()
+///     .route("/", get(handler))
+///     .layer(ClientIpLayer::new(config.trusted_proxies.clone()));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ClientIpLayer {
+    trusted: Arc<TrustedProxies>,
+}
+
+impl ClientIpLayer {
+    pub fn new(trusted: TrustedProxies) -> Self {
+        Self {
+            trusted: Arc::new(trusted),
+        }
+    }
+}
+
+impl<S> Layer<S> for ClientIpLayer {
+    type Service = ClientIpService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ClientIpService {
+            inner,
+            trusted: Arc::clone(&self.trusted),
+        }
+    }
+}
+
+/// Service created by [`ClientIpLayer`].
+#[derive(Debug, Clone)]
+pub struct ClientIpService<S> {
+    inner: S,
+    trusted: Arc<TrustedProxies>,
+}
+
+impl<S, B> Service<Request<B>> for ClientIpService<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        if let Some(ConnectInfo(peer)) = req.extensions().get::<ConnectInfo<SocketAddr>>() {
+            let ip = self.trusted.resolve(peer.ip(), req.headers());
+            req.extensions_mut().insert(ClientIp(ip));
+        }
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn request(peer: &str, forwarded_for: &str) -> Request<Body> {
+        let mut req = Request::builder()
+            .uri("/")
+            .header("x-forwarded-for", forwarded_for)
+            .body(Body::empty())
+            .unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+        req
+    }
+
+    async fn body(app: Router, req: Request<Body>) -> String {
+        let response = app.oneshot(req).await.unwrap();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn resolves_through_trusted_proxies() {
+        let trusted = ["10.0.0.0/8".parse().unwrap()].into_iter().collect();
+        let app = Router::new()
+            .route(
+                "/",
+                get(|ClientIp(ip): ClientIp| async move { ip.to_string() }),
+            )
+            .layer(ClientIpLayer::new(trusted));
+
+        let forwarded = body(
+            app.clone(),
+            request("10.0.0.1:5000", "6.6.6.6, 203.0.113.7"),
+        )
+        .await;
+        assert_eq!(forwarded, "203.0.113.7");
+
+        let direct = body(app, request("198.51.100.1:5000", "203.0.113.7")).await;
+        assert_eq!(direct, "198.51.100.1");
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_peer_without_layer() {
+        let app = Router::new().route(
+            "/",
+            get(|ClientIp(ip): ClientIp| async move { ip.to_string() }),
+        );
+        assert_eq!(
+            body(app.clone(), request("10.0.0.1:5000", "203.0.113.7")).await,
+            "10.0.0.1"
+        );
+
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -0,0 +1,213 @@
+use crate::ErrorResponse;
+use axum::body::Body;
+use axum::extract::MatchedPath;
+use axum::http::{header, Request, Response, StatusCode};
+use axum::response::IntoResponse;
+use server_kit::{ConcurrencyConfig, ConcurrencyLimits};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// Layer capping the number of requests processed at once.
+///
+/// Requests over the limit wait in a bounded queue. When the queue is full
+/// or the wait exceeds the queue timeout, they get a JSON 503 with
+/// `Retry-After`. The slot is held until the handler returns its response,
+/// so streamed bodies aren't counted.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use server_kit_rest::{ConcurrencyLimitLayer, ConcurrencyLimiter, ConcurrencyLimits};
+///
+/// let limits = ConcurrencyLimits::new()
+///     .global(ConcurrencyLimiter::new("*", 200))
+///     .route("/reports", ConcurrencyLimiter::new("/reports", 4).max_queue(10));
+///
+/// let app = Router::new()
+///     .route("/reports/daily", get(daily_report))
+///     .layer(ConcurrencyLimitLayer::new(limits))
+///     .with_default_layers(&config);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimitLayer {
+    limits: Arc<ConcurrencyLimits>,
+}
+
+impl ConcurrencyLimitLayer {
+    pub fn new(limits: ConcurrencyLimits) -> Self {
+        #[cfg(feature = "metrics")]
+        let limits = limits.observe(|name, stats| {
+            metrics::gauge!("http_concurrency_queue_depth", "limit" => name.to_string())
+                .set(stats.queued as f64);
+            metrics::gauge!("http_concurrency_limit", "limit" => name.to_string())
+                .set(stats.limit as f64);
+        });
+        Self {
+            limits: Arc::new(limits),
+        }
+    }
+
+    /// Build the limits from the `concurrency` config section.
+    /// `with_default_layers` adds this layer when the section is enabled.
+    pub fn from_config(config: &ConcurrencyConfig) -> Self {
+        Self::new(ConcurrencyLimits::from_config(config))
+    }
+
+    /// The limits, e.g. to report their current state.
+    pub fn limits(&self) -> &Arc<ConcurrencyLimits> {
+        &self.limits
+    }
+}
+
+impl<S> Layer<S> for ConcurrencyLimitLayer {
+    type Service = ConcurrencyLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConcurrencyLimitService {
+            inner,
+            limits: Arc::clone(&self.limits),
+        }
+    }
+}
+
+/// Service created by [`ConcurrencyLimitLayer`].
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimitService<S> {
+    inner: S,
+    limits: Arc<ConcurrencyLimits>,
+}
+
+impl<S, B> Service<Request<B>> for ConcurrencyLimitService<S>
+where
+    S: Service<Request<B>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let limits = Arc::clone(&self.limits);
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let route = req
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str())
+            .unwrap_or_else(|| req.uri().path())
+            .to_string();
+
+        Box::pin(async move {
+            let _permit = match limits.acquire(&route).await {
+                Ok(permit) => permit,
+                Err(e) => {
+                    tracing::debug!(route = %route, reason = e.as_str(), "Request shed");
+                    #[cfg(feature = "metrics")]
+                    metrics::counter!("http_concurrency_rejections_total", "reason" => e.as_str())
+                        .increment(1);
+
+                    let status = StatusCode::SERVICE_UNAVAILABLE;
+                    let body = ErrorResponse::from_status(status, "Server is overloaded");
+                    let mut response = (status, axum::Json(body)).into_response();
+                    let retry_after = limits.retry_after_delay().as_secs().max(1);
+                    response
+                        .headers_mut()
+                        .insert(header::RETRY_AFTER, retry_after.into());
+                    return Ok(response);
+                }
+            };
+            inner.call(req).await
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum::Router;
+    use server_kit::{ConcurrencyLimiter, RouteConcurrency};
+    use std::time::Duration;
+    use tokio::sync::oneshot;
+    use tower::ServiceExt;
+
+    async fn status(app: &Router, uri: &str) -> Response<Body> {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        app.clone().oneshot(req).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn sheds_requests_over_the_route_limit() {
+        let (release, released) = oneshot::channel::<()>();
+        let released = Arc::new(tokio::sync::Mutex::new(Some(released)));
+        let layer = ConcurrencyLimitLayer::from_config(&ConcurrencyConfig {
+            enabled: true,
+            max_queue: 0,
+            retry_after_secs: 5,
+            routes: vec![RouteConcurrency {
+                prefix: "/slow".to_string(),
+                max_in_flight: 1,
+            }],
+            ..Default::default()
+        });
+        let app = Router::new()
+            .route(
+                "/slow",
+                get(move || {
+                    let released = Arc::clone(&released);
+                    async move {
+                        if let Some(released) = released.lock().await.take() {
+                            let _ = released.await;
+                        }
+                        "OK"
+                    }
+                }),
+            )
+            .route("/fast", get(|| async { "OK" }))
+            .layer(layer.clone());
+
+        let slow = tokio::spawn({
+            let app = app.clone();
+            async move { status(&app, "/slow").await.status() }
+        });
+        while layer.limits().limiters().all(|l| l.stats().in_flight == 0) {
+            tokio::task::yield_now().await;
+        }
+
+        let response = status(&app, "/slow").await;
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()["retry-after"], "5");
+        assert_eq!(status(&app, "/fast").await.status(), StatusCode::OK);
+
+        release.send(()).unwrap();
+        assert_eq!(slow.await.unwrap(), StatusCode::OK);
+        assert_eq!(status(&app, "/slow").await.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn queued_requests_wait_for_a_slot() {
+        let limits = ConcurrencyLimits::new()
+            .global(ConcurrencyLimiter::new("*", 1).queue_timeout(Duration::from_secs(5)));
+        let app = Router::new()
+            .route(
+                "/",
+                get(|| async {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    "OK"
+                }),
+            )
+            .layer(ConcurrencyLimitLayer::new(limits));
+
+        let (a, b) = tokio::join!(status(&app, "/"), status(&app, "/"));
+        assert_eq!(a.status(), StatusCode::OK);
+        assert_eq!(b.status(), StatusCode::OK);
+    }
+}
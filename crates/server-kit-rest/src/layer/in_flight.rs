@@ -0,0 +1,105 @@
+use axum::http::{Request, Response};
+use server_kit::{InFlight, InFlightBody};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// Layer that counts in-flight requests for graceful shutdown.
+///
+/// A request is counted from arrival until its response body has been sent.
+/// `serve_router` adds this layer with the process-wide [`InFlight`] counter
+/// and logs the count while draining.
+#[derive(Clone)]
+pub struct InFlightLayer {
+    in_flight: InFlight,
+}
+
+impl Default for InFlightLayer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl InFlightLayer {
+    /// Count requests in the process-wide [`InFlight::global`] counter.
+    pub fn new() -> Self {
+        Self::with_counter(InFlight::global().clone())
+    }
+
+    /// Count requests in a custom counter.
+    pub fn with_counter(in_flight: InFlight) -> Self {
+        Self { in_flight }
+    }
+}
+
+impl<S> Layer<S> for InFlightLayer {
+    type Service = InFlightService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        InFlightService {
+            inner,
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
+
+/// Service created by [`InFlightLayer`].
+#[derive(Clone)]
+pub struct InFlightService<S> {
+    inner: S,
+    in_flight: InFlight,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for InFlightService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+{
+    type Response = Response<InFlightBody<ResBody>>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let guard = self.in_flight.track();
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            let response = inner.call(req).await?;
+            Ok(response.map(|body| InFlightBody::new(body, guard)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn counts_until_body_is_sent() {
+        let in_flight = InFlight::new();
+        let app = Router::new()
+            .route("/", get(|| async { "OK" }))
+            .layer(InFlightLayer::with_counter(in_flight.clone()));
+
+        let response = app
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(in_flight.count(), 1);
+
+        response.into_body().collect().await.unwrap();
+        assert_eq!(in_flight.count(), 0);
+    }
+}
@@ -0,0 +1,158 @@
+use super::ClientIp;
+use crate::ErrorResponse;
+use axum::body::Body;
+use axum::http::{Request, Response, StatusCode};
+use axum::response::IntoResponse;
+use server_kit::{IpFilter, IpFilterConfig};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+/// Layer that rejects requests whose [`ClientIp`] fails the filter rules
+/// with a JSON 403.
+///
+/// Apply it to the routes that need it, e.g. admin endpoints. Keep the
+/// [`IpFilter`] handle to reload the rules at runtime.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use server_kit_rest::{IpFilter, IpFilterLayer};
+///
+/// let filter = IpFilter::new(config.ip_filter.clone());
+/// let admin = Router::new()
+///     .route("/admin/stats", get(stats))
+///     .layer(IpFilterLayer::new(filter.clone()));
+///
+/// let app = Router::new().merge(admin).with_default_layers(&config);
+/// ```
+#[derive(Debug, Clone)]
+pub struct IpFilterLayer {
+    filter: IpFilter,
+}
+
+impl IpFilterLayer {
+    pub fn new(filter: IpFilter) -> Self {
+        Self { filter }
+    }
+
+    /// Create the layer from the `ip_filter` config section.
+    pub fn from_config(config: &IpFilterConfig) -> Self {
+        Self::new(IpFilter::new(config.clone()))
+    }
+
+    /// The filter, for reloading its rules.
+    pub fn filter(&self) -> &IpFilter {
+        &self.filter
+    }
+}
+
+impl<S> Layer<S> for IpFilterLayer {
+    type Service = IpFilterService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IpFilterService {
+            inner,
+            filter: self.filter.clone(),
+        }
+    }
+}
+
+/// Service created by [`IpFilterLayer`].
+#[derive(Debug, Clone)]
+pub struct IpFilterService<S> {
+    inner: S,
+    filter: IpFilter,
+}
+
+impl<S, B> Service<Request<B>> for IpFilterService<S>
+where
+    S: Service<Request<B>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let ip = ClientIp::from_extensions(req.extensions());
+        if !self.filter.check(ip) {
+            tracing::warn!(
+                client_ip = ip.map(|ip| ip.to_string()).as_deref().unwrap_or("-"),
+                path = %req.uri().path(),
+                "Request rejected by IP filter"
+            );
+            #[cfg(feature = "metrics")]
+            metrics::counter!("http_ip_filter_rejections_total").increment(1);
+
+            let status = StatusCode::FORBIDDEN;
+            let body = ErrorResponse::from_status(status, "Access denied");
+            let response = (status, axum::Json(body)).into_response();
+            return Box::pin(async move { Ok(response) });
+        }
+
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move { inner.call(req).await })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::ConnectInfo;
+    use axum::routing::get;
+    use axum::Router;
+    use std::net::SocketAddr;
+    use tower::ServiceExt;
+
+    fn request(peer: &str) -> Request<Body> {
+        let mut req = Request::builder()
+            .uri("/admin")
+            .body(Body::empty())
+            .unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+        req
+    }
+
+    #[tokio::test]
+    async fn rejects_and_reloads() {
+        let layer = IpFilterLayer::from_config(&IpFilterConfig {
+            allow: vec!["10.0.0.0/8".parse().unwrap()],
+            deny: Vec::new(),
+        });
+        let filter = layer.filter().clone();
+        let app = Router::new()
+            .route("/admin", get(|| async { "OK" }))
+            .layer(layer);
+
+        let response = app.clone().oneshot(request("10.1.2.3:4000")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .clone()
+            .oneshot(request("192.168.1.5:4000"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "FORBIDDEN");
+
+        filter.reload(IpFilterConfig {
+            allow: vec!["192.168.0.0/16".parse().unwrap()],
+            deny: Vec::new(),
+        });
+        let response = app.oneshot(request("192.168.1.5:4000")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
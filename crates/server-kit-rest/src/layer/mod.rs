@@ -1,13 +1,17 @@
+mod access_log;
+mod client_ip;
+mod concurrency;
+mod in_flight;
+mod ip_filter;
 mod json_error;
+mod panic;
 #[cfg(feature = "ratelimit")]
 mod ratelimit;
+mod timeout;
 mod trace;
 
-use axum::http::StatusCode;
 use axum::Router;
-use tower_http::catch_panic::CatchPanicLayer;
 use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
-use tower_http::timeout::TimeoutLayer;
 
 #[cfg(feature = "compression")]
 use tower_http::compression::CompressionLayer;
@@ -16,22 +20,40 @@ use tower_http::compression::CompressionLayer;
 use tower_http::cors::{AllowOrigin, CorsLayer};
 
 use crate::ServerConfig;
-use trace::DefaultTraceLayer;
 
+pub use access_log::AccessLogLayer;
+pub use client_ip::{ClientIp, ClientIpLayer};
+pub use concurrency::ConcurrencyLimitLayer;
+pub use in_flight::InFlightLayer;
+pub use ip_filter::IpFilterLayer;
 pub use json_error::JsonErrorLayer;
+pub use panic::PanicLayer;
 #[cfg(feature = "ratelimit")]
-pub use ratelimit::RateLimitLayer;
+pub use ratelimit::{reload_rate_limits, KeyedRateLimitLayer, RateLimitLayer};
+pub use timeout::RequestTimeoutLayer;
+pub use trace::DefaultTraceLayer;
 
 pub(crate) fn default_layers(router: Router, config: &ServerConfig) -> Router {
+    let router = router.layer(PanicLayer::new());
+
+    let router = if config.concurrency.enabled {
+        router.layer(ConcurrencyLimitLayer::from_config(&config.concurrency))
+    } else {
+        router
+    };
+
+    #[cfg(feature = "ratelimit")]
+    let router = if config.rate_limit.enabled {
+        router.layer(KeyedRateLimitLayer::from_config(&config.rate_limit))
+    } else {
+        router
+    };
+
     let router = router
-        .layer(CatchPanicLayer::new())
-        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(DefaultTraceLayer::from_config(&config.trace))
         .layer(PropagateRequestIdLayer::x_request_id())
-        .layer(DefaultTraceLayer::new())
-        .layer(TimeoutLayer::with_status_code(
-            StatusCode::REQUEST_TIMEOUT,
-            config.request_timeout(),
-        ));
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .layer(RequestTimeoutLayer::from_config(config));
 
     #[cfg(feature = "compression")]
     let router = router.layer(CompressionLayer::new());
@@ -50,5 +72,19 @@ pub(crate) fn default_layers(router: Router, config: &ServerConfig) -> Router {
         }
     };
 
-    router.layer(JsonErrorLayer::new(config.environment))
+    let router = router.layer(JsonErrorLayer::new(config.environment));
+
+    let router = if !config.access_log.enabled {
+        router
+    } else {
+        match AccessLogLayer::from_config(&config.access_log) {
+            Ok(layer) => router.layer(layer),
+            Err(e) => {
+                tracing::error!(error = %e, "Failed to open access log, access logging disabled");
+                router
+            }
+        }
+    };
+
+    router.layer(ClientIpLayer::new(config.trusted_proxies.clone()))
 }
@@ -0,0 +1,165 @@
+use axum::body::Body;
+use axum::http::{header, Request, Response, StatusCode};
+use axum::response::IntoResponse;
+use server_kit::{CatchPanic, PanicReport};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+use crate::error::ErrorResponse;
+
+/// Layer that turns handler panics into JSON 500 responses.
+///
+/// The panic message, location and backtrace are logged at ERROR inside the
+/// current request span together with the request ID, and the response body
+/// is an [`ErrorResponse`] carrying the same request ID. With the `metrics`
+/// feature, each panic increments `panics_total`.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use server_kit_rest::PanicLayer;
+///
+/// let app = Router::new()
+///     .route("/", get(handler))
+///     .layer(PanicLayer::new());
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct PanicLayer;
+
+impl PanicLayer {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl<S> Layer<S> for PanicLayer {
+    type Service = PanicService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        PanicService { inner }
+    }
+}
+
+/// Service created by [`PanicLayer`].
+#[derive(Clone)]
+pub struct PanicService<S> {
+    inner: S,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for PanicService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    ReqBody: Send + 'static,
+    ResBody: axum::body::HttpBody<Data = axum::body::Bytes> + Send + 'static,
+    ResBody::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let request_id = req
+            .headers()
+            .get("x-request-id")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(async move {
+            match CatchPanic::new(async move { inner.call(req).await }).await {
+                Ok(result) => result.map(|response| response.map(Body::new)),
+                Err(report) => Ok(panic_response(&report, request_id)),
+            }
+        })
+    }
+}
+
+fn panic_response(report: &PanicReport, request_id: Option<String>) -> Response<Body> {
+    tracing::error!(
+        panic.message = %report.message,
+        panic.location = report.location.as_deref().unwrap_or("-"),
+        panic.thread = report.thread.as_deref().unwrap_or("-"),
+        panic.backtrace = report.backtrace.as_deref().unwrap_or("-"),
+        request_id = request_id.as_deref().unwrap_or("-"),
+        "handler panicked"
+    );
+
+    #[cfg(feature = "metrics")]
+    metrics::counter!("panics_total").increment(1);
+
+    let mut error = ErrorResponse::from_status(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal Server Error",
+    );
+    if let Some(request_id) = request_id {
+        error = error.with_request_id(request_id);
+    }
+
+    let mut response = (StatusCode::INTERNAL_SERVER_ERROR, axum::Json(error)).into_response();
+    response
+        .headers_mut()
+        .insert(header::CONTENT_TYPE, "application/json".parse().unwrap());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn converts_panic_to_json_with_request_id() {
+        let app = Router::new()
+            .route("/", get(|| async { "OK" }))
+            .route(
+                "/panic",
+                get(|| async {
+                    panic!("handler exploded");
+                    #[allow(unreachable_code)]
+                    "unreachable"
+                }),
+            )
+            .layer(PanicLayer::new());
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/panic")
+                    .header("x-request-id", "req-7")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            response.headers().get(header::CONTENT_TYPE).unwrap(),
+            "application/json"
+        );
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "INTERNAL_SERVER_ERROR");
+        assert_eq!(json["message"], "Internal Server Error");
+        assert_eq!(json["request_id"], "req-7");
+    }
+}
@@ -1,19 +1,40 @@
+use super::ClientIp;
+use crate::ErrorResponse;
 use axum::body::Body;
+use axum::extract::MatchedPath;
 use axum::http::{Request, Response, StatusCode};
 use axum::response::IntoResponse;
-use governor::{DefaultDirectRateLimiter, Quota, RateLimiter};
+use server_kit::{
+    KeyedRateLimiter, RateLimitConfig, RateLimitKey, RateLimitQuota, RateLimitRequest,
+};
 use std::future::Future;
-use std::num::NonZeroU32;
 use std::pin::Pin;
 use std::sync::Arc;
 use std::task::{Context, Poll};
 use std::time::Duration;
 use tower::{Layer, Service};
 
+/// Section the limiters built from `ServerConfig::rate_limit` register under.
+const SECTION: &str = "rest.rate_limit";
+
+/// Reload the limiters built from the `rate_limit` config section by
+/// [`KeyedRateLimitLayer::from_config`], and so by `with_default_layers`.
+/// gRPC limiters in the same process keep their rules. Returns the number of
+/// limiters reloaded.
+///
+/// Nothing calls this on its own: call it after re-reading the config file,
+/// e.g. on SIGHUP.
+pub fn reload_rate_limits(config: &RateLimitConfig) -> usize {
+    server_kit::reload_rate_limits(SECTION, config)
+}
+
 /// Rate limiter layer using the governor crate.
-#[derive(Clone)]
+///
+/// All requests share one quota; see [`KeyedRateLimitLayer`] to limit each
+/// client separately.
+#[derive(Debug, Clone)]
 pub struct RateLimitLayer {
-    limiter: Arc<DefaultDirectRateLimiter>,
+    limiter: Arc<KeyedRateLimiter>,
 }
 
 impl RateLimitLayer {
@@ -21,24 +42,31 @@ impl RateLimitLayer {
     ///
     /// # Arguments
     ///
-    /// * `num_requests` - Maximum number of requests allowed in the period
-    /// * `per_duration` - The time period for the rate limit
+    /// * `num_requests` - Maximum number of requests allowed at once
+    /// * `per_duration` - How long it takes for one request to be allowed again
+    ///
+    /// Up to `num_requests` requests pass in a burst, after which one more
+    /// is allowed every `per_duration`. Use [`KeyedRateLimitLayer`] with a
+    /// [`RateLimitQuota`] for a quota refilling `n` requests per period.
     pub fn new(num_requests: u32, per_duration: Duration) -> Self {
-        let quota = Quota::with_period(per_duration)
-            .expect("invalid duration")
-            .allow_burst(NonZeroU32::new(num_requests).expect("num_requests must be > 0"));
-
+        let refill = per_duration
+            .checked_mul(num_requests)
+            .unwrap_or(Duration::MAX);
+        let limiter = KeyedRateLimiter::new(RateLimitKey::Global)
+            .quota(RateLimitQuota::new(num_requests, refill));
         Self {
-            limiter: Arc::new(RateLimiter::direct(quota)),
+            limiter: Arc::new(limiter),
         }
     }
 
-    /// Create a rate limiter allowing `n` requests per second.
+    /// Create a rate limiter allowing a burst of `n` requests, then one per
+    /// second.
     pub fn per_second(n: u32) -> Self {
         Self::new(n, Duration::from_secs(1))
     }
 
-    /// Create a rate limiter allowing `n` requests per minute.
+    /// Create a rate limiter allowing a burst of `n` requests, then one per
+    /// minute.
     pub fn per_minute(n: u32) -> Self {
         Self::new(n, Duration::from_secs(60))
     }
@@ -55,17 +83,81 @@ impl<S> Layer<S> for RateLimitLayer {
     }
 }
 
-/// Rate limiter service.
-#[derive(Clone)]
+/// Rate limiter layer with a separate quota per key, e.g. per client IP.
+///
+/// Requests over their key's quota get a JSON 429. Add it inside
+/// `with_default_layers` (i.e. before it) so [`ClientIp`] is resolved, and
+/// inside your auth layer when keying by [`Subject`](server_kit::Subject).
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use server_kit_rest::{KeyedRateLimitLayer, KeyedRateLimiter, RateLimitKey, RateLimitQuota};
+///
+/// let limiter = KeyedRateLimiter::new(RateLimitKey::ClientIp)
+///     .quota(RateLimitQuota::per_second(20))
+///     .route_group("/auth", RateLimitQuota::per_minute(5));
+///
+/// let app = Router::new()
+///     .route("/api/items", get(list_items))
+///     .route("/auth/login", post(login))
+///     .layer(KeyedRateLimitLayer::new(limiter))
+///     .with_default_layers(&config);
+/// ```
+#[derive(Debug, Clone)]
+pub struct KeyedRateLimitLayer {
+    limiter: Arc<KeyedRateLimiter>,
+}
+
+impl KeyedRateLimitLayer {
+    pub fn new(limiter: KeyedRateLimiter) -> Self {
+        Self {
+            limiter: Arc::new(limiter),
+        }
+    }
+
+    /// Build the limiter from the `rate_limit` config section and register
+    /// it with [`reload_rate_limits`]. `with_default_layers` adds this layer
+    /// when the section is enabled.
+    pub fn from_config(config: &RateLimitConfig) -> Self {
+        Self {
+            limiter: KeyedRateLimiter::from_config(config).register(SECTION),
+        }
+    }
+
+    /// The limiter, e.g. to report how many keys it tracks or to
+    /// [`reload`](KeyedRateLimiter::reload) it.
+    pub fn limiter(&self) -> &Arc<KeyedRateLimiter> {
+        &self.limiter
+    }
+}
+
+impl<S> Layer<S> for KeyedRateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: Arc::clone(&self.limiter),
+        }
+    }
+}
+
+/// Service created by [`RateLimitLayer`] and [`KeyedRateLimitLayer`].
+///
+/// Limited responses carry `RateLimit-Limit`, `RateLimit-Remaining` and
+/// `RateLimit-Reset` headers; 429s also carry `Retry-After`.
+#[derive(Debug, Clone)]
 pub struct RateLimitService<S> {
     inner: S,
-    limiter: Arc<DefaultDirectRateLimiter>,
+    limiter: Arc<KeyedRateLimiter>,
 }
 
-impl<S> Service<Request<Body>> for RateLimitService<S>
+impl<S, B> Service<Request<B>> for RateLimitService<S>
 where
-    S: Service<Request<Body>, Response = Response<Body>> + Clone + Send + 'static,
+    S: Service<Request<B>, Response = Response<Body>> + Clone + Send + 'static,
     S::Future: Send,
+    B: Send + 'static,
 {
     type Response = Response<Body>;
     type Error = S::Error;
@@ -75,21 +167,215 @@ where
         self.inner.poll_ready(cx)
     }
 
-    fn call(&mut self, req: Request<Body>) -> Self::Future {
+    fn call(&mut self, req: Request<B>) -> Self::Future {
         let limiter = Arc::clone(&self.limiter);
         let clone = self.inner.clone();
         let mut inner = std::mem::replace(&mut self.inner, clone);
 
         Box::pin(async move {
-            if limiter.check().is_err() {
-                let body = serde_json::json!({
-                    "code": "TOO_MANY_REQUESTS",
-                    "message": "Rate limit exceeded"
-                });
-                return Ok((StatusCode::TOO_MANY_REQUESTS, axum::Json(body)).into_response());
+            // Check on the parts: the body isn't `Sync`, so the request can't
+            // be borrowed across the store lookup.
+            let (parts, body) = req.into_parts();
+            let route = parts
+                .extensions
+                .get::<MatchedPath>()
+                .map(|path| path.as_str())
+                .unwrap_or_else(|| parts.uri.path());
+            let limit = limiter
+                .check(&RateLimitRequest {
+                    route,
+                    headers: &parts.headers,
+                    extensions: &parts.extensions,
+                    client_ip: ClientIp::from_extensions(&parts.extensions),
+                })
+                .await;
+
+            if let Some(limit) = limit.filter(|limit| !limit.is_allowed()) {
+                #[cfg(feature = "metrics")]
+                metrics::counter!("http_rate_limit_rejections_total").increment(1);
+
+                let status = StatusCode::TOO_MANY_REQUESTS;
+                let body = ErrorResponse::from_status(status, "Rate limit exceeded");
+                let mut response = (status, axum::Json(body)).into_response();
+                limit.insert_headers(response.headers_mut());
+                return Ok(response);
             }
 
-            inner.call(req).await
+            let mut response = inner.call(Request::from_parts(parts, body)).await?;
+            if let Some(limit) = limit {
+                limit.insert_headers(response.headers_mut());
+            }
+            Ok(response)
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::ConnectInfo;
+    use axum::routing::get;
+    use axum::Router;
+    use std::net::SocketAddr;
+    use tower::ServiceExt;
+
+    fn request(uri: &str, peer: &str) -> Request<Body> {
+        let mut req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        req.extensions_mut()
+            .insert(ConnectInfo(peer.parse::<SocketAddr>().unwrap()));
+        req
+    }
+
+    async fn status(app: &Router, uri: &str, peer: &str) -> StatusCode {
+        app.clone()
+            .oneshot(request(uri, peer))
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn limits_each_client_separately() {
+        let limiter =
+            KeyedRateLimiter::new(RateLimitKey::ClientIp).quota(RateLimitQuota::per_minute(2));
+        let app = Router::new()
+            .route("/api", get(|| async { "OK" }))
+            .layer(KeyedRateLimitLayer::new(limiter));
+
+        let response = app
+            .clone()
+            .oneshot(request("/api", "10.0.0.1:4000"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["ratelimit-limit"], "2");
+        assert_eq!(response.headers()["ratelimit-remaining"], "1");
+        assert_eq!(response.headers()["ratelimit-reset"], "30");
+        assert!(response.headers().get("retry-after").is_none());
+
+        assert_eq!(status(&app, "/api", "10.0.0.1:4001").await, StatusCode::OK);
+        let response = app
+            .clone()
+            .oneshot(request("/api", "10.0.0.1:4002"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(response.headers()["ratelimit-remaining"], "0");
+        assert_eq!(response.headers()["retry-after"], "30");
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["code"], "TOO_MANY_REQUESTS");
+
+        assert_eq!(status(&app, "/api", "10.0.0.2:4000").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn route_groups_use_their_own_quota() {
+        let limiter = KeyedRateLimiter::new(RateLimitKey::ClientIp)
+            .quota(RateLimitQuota::per_minute(100))
+            .route_group("/auth", RateLimitQuota::per_minute(1));
+        let app = Router::new()
+            .route("/auth/login", get(|| async { "OK" }))
+            .route("/api", get(|| async { "OK" }))
+            .layer(KeyedRateLimitLayer::new(limiter));
+
+        let peer = "10.0.0.1:4000";
+        assert_eq!(status(&app, "/auth/login", peer).await, StatusCode::OK);
+        assert_eq!(
+            status(&app, "/auth/login", peer).await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(status(&app, "/api", peer).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn unkeyed_layer_shares_one_quota() {
+        let app = Router::new()
+            .route("/api", get(|| async { "OK" }))
+            .layer(RateLimitLayer::per_minute(1));
+
+        assert_eq!(status(&app, "/api", "10.0.0.1:4000").await, StatusCode::OK);
+        assert_eq!(
+            status(&app, "/api", "10.0.0.2:4000").await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+    }
+
+    #[tokio::test]
+    async fn unkeyed_layer_refills_one_request_per_period() {
+        let app = Router::new()
+            .route("/api", get(|| async { "OK" }))
+            .layer(RateLimitLayer::per_minute(2));
+
+        for _ in 0..2 {
+            assert_eq!(status(&app, "/api", "10.0.0.1:4000").await, StatusCode::OK);
+        }
+        let response = app
+            .clone()
+            .oneshot(request("/api", "10.0.0.1:4000"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let retry_after: u64 = response.headers()["retry-after"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(retry_after > 30 && retry_after <= 60, "{retry_after}");
+    }
+
+    #[tokio::test]
+    async fn default_layers_apply_config() {
+        use crate::{RouterExt, ServerConfig};
+
+        let mut config = ServerConfig::default();
+        config.rate_limit.enabled = true;
+        config.rate_limit.requests = 1;
+        config.rate_limit.period_secs = 60;
+        config.rate_limit.exempt_routes = vec!["/health".to_string()];
+        let app = Router::new()
+            .route("/api", get(|| async { "OK" }))
+            .route("/health", get(|| async { "OK" }))
+            .with_default_layers(&config);
+
+        assert_eq!(status(&app, "/api", "10.0.0.1:4000").await, StatusCode::OK);
+        assert_eq!(
+            status(&app, "/api", "10.0.0.1:4000").await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+        assert_eq!(
+            status(&app, "/health", "10.0.0.1:4000").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(&app, "/health", "10.0.0.1:4000").await,
+            StatusCode::OK
+        );
+    }
+
+    #[tokio::test]
+    async fn config_layer_reloads() {
+        let mut config = RateLimitConfig {
+            enabled: true,
+            requests: 1,
+            period_secs: 60,
+            ..Default::default()
+        };
+        let layer = KeyedRateLimitLayer::from_config(&config);
+        let app = Router::new()
+            .route("/api", get(|| async { "OK" }))
+            .layer(layer.clone());
+
+        assert_eq!(status(&app, "/api", "10.0.0.1:4000").await, StatusCode::OK);
+        assert_eq!(
+            status(&app, "/api", "10.0.0.1:4000").await,
+            StatusCode::TOO_MANY_REQUESTS
+        );
+
+        config.requests = 2;
+        layer.limiter().reload(&config);
+        assert_eq!(status(&app, "/api", "10.0.0.1:4000").await, StatusCode::OK);
+    }
+}
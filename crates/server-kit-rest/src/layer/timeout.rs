@@ -0,0 +1,160 @@
+use axum::body::Body;
+use axum::extract::MatchedPath;
+use axum::http::{Request, Response, StatusCode};
+use axum::response::IntoResponse;
+use server_kit::{Deadline, RequestTimeouts};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower::{Layer, Service};
+
+use crate::ServerConfig;
+
+/// Layer enforcing the request timeout, with per-route overrides.
+///
+/// Each request gets a [`Deadline`] in its extensions (read it with
+/// `Extension<Deadline>`) and the handler runs inside it, so gRPC calls made
+/// through `server-kit-grpc` channels forward the remaining time. Requests
+/// still running at the deadline get a 408. Routes are matched on the route
+/// pattern, or the path for unmatched requests.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use server_kit_rest::{RequestTimeoutLayer, RequestTimeouts};
+///
+/// let timeouts = RequestTimeouts::new(Duration::from_secs(10))
+///     .route("/reports", Duration::from_secs(120));
+///
+/// let app = Router::new()
+///     .route("/reports/daily", get(daily_report))
+///     .layer(RequestTimeoutLayer::new(timeouts));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RequestTimeoutLayer {
+    timeouts: Arc<RequestTimeouts>,
+}
+
+impl RequestTimeoutLayer {
+    pub fn new(timeouts: RequestTimeouts) -> Self {
+        Self {
+            timeouts: Arc::new(timeouts),
+        }
+    }
+
+    /// Use `request_timeout_secs` and the `route_timeouts` overrides.
+    /// `with_default_layers` adds this layer.
+    pub fn from_config(config: &ServerConfig) -> Self {
+        Self::new(RequestTimeouts::from_config(
+            config.request_timeout(),
+            &config.route_timeouts,
+        ))
+    }
+}
+
+impl<S> Layer<S> for RequestTimeoutLayer {
+    type Service = RequestTimeoutService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestTimeoutService {
+            inner,
+            timeouts: Arc::clone(&self.timeouts),
+        }
+    }
+}
+
+/// Service created by [`RequestTimeoutLayer`].
+#[derive(Debug, Clone)]
+pub struct RequestTimeoutService<S> {
+    inner: S,
+    timeouts: Arc<RequestTimeouts>,
+}
+
+impl<S, B> Service<Request<B>> for RequestTimeoutService<S>
+where
+    S: Service<Request<B>, Response = Response<Body>> + Clone + Send + 'static,
+    S::Future: Send,
+    B: Send + 'static,
+{
+    type Response = Response<Body>;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let route = req
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|path| path.as_str())
+            .unwrap_or_else(|| req.uri().path());
+        let Some(timeout) = self.timeouts.for_route(route) else {
+            return Box::pin(inner.call(req));
+        };
+
+        let deadline = Deadline::after(timeout);
+        req.extensions_mut().insert(deadline);
+        Box::pin(async move {
+            match deadline.timeout(inner.call(req)).await {
+                Ok(response) => response,
+                Err(_) => Ok(StatusCode::REQUEST_TIMEOUT.into_response()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use axum::{Extension, Router};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    async fn status(app: &Router, uri: &str) -> StatusCode {
+        let req = Request::builder().uri(uri).body(Body::empty()).unwrap();
+        app.clone().oneshot(req).await.unwrap().status()
+    }
+
+    async fn slow() -> &'static str {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        "OK"
+    }
+
+    #[tokio::test]
+    async fn route_timeouts_override_the_default() {
+        let timeouts = RequestTimeouts::new(Duration::from_millis(20))
+            .route("/reports", Duration::from_secs(5))
+            .route("/events", Duration::ZERO);
+        let app = Router::new()
+            .route("/users", get(slow))
+            .route("/reports/daily", get(slow))
+            .route("/events", get(slow))
+            .layer(RequestTimeoutLayer::new(timeouts));
+
+        assert_eq!(status(&app, "/users").await, StatusCode::REQUEST_TIMEOUT);
+        assert_eq!(status(&app, "/reports/daily").await, StatusCode::OK);
+        assert_eq!(status(&app, "/events").await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn handlers_see_the_deadline() {
+        let app = Router::new()
+            .route(
+                "/",
+                get(|Extension(deadline): Extension<Deadline>| async move {
+                    assert_eq!(Deadline::current(), Some(deadline));
+                    assert!(deadline.remaining() <= Duration::from_secs(30));
+                    "OK"
+                }),
+            )
+            .layer(RequestTimeoutLayer::from_config(&ServerConfig::default()));
+
+        assert_eq!(status(&app, "/").await, StatusCode::OK);
+    }
+}
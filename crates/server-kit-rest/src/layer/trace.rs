@@ -1,68 +1,355 @@
-use axum::http::Request;
-use tower::Layer;
-use tower_http::{
-    classify::{ServerErrorsAsFailures, SharedClassifier},
-    trace::{DefaultOnResponse, MakeSpan, TraceLayer},
-    LatencyUnit,
-};
-use tracing::{Level, Span};
-
-/// Custom span maker that includes request ID and useful request info.
-#[derive(Clone, Copy)]
-pub struct RequestSpan;
-
-impl<B> MakeSpan<B> for RequestSpan {
-    fn make_span(&mut self, request: &Request<B>) -> Span {
-        let request_id = request
-            .headers()
-            .get("x-request-id")
-            .and_then(|v| v.to_str().ok())
+use super::ClientIp;
+use axum::extract::MatchedPath;
+use axum::http::{header, HeaderMap, Request, Response, StatusCode};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use tower::{Layer, Service};
+use tracing::{Instrument, Level};
+
+use crate::config::TraceConfig;
+
+/// Headers whose values are never written to logs.
+const REDACTED_HEADERS: [header::HeaderName; 4] = [
+    header::AUTHORIZATION,
+    header::PROXY_AUTHORIZATION,
+    header::COOKIE,
+    header::SET_COOKIE,
+];
+
+macro_rules! event_at {
+    ($level:expr, $($args:tt)+) => {
+        match $level {
+            Level::ERROR => tracing::error!($($args)+),
+            Level::WARN => tracing::warn!($($args)+),
+            Level::INFO => tracing::info!($($args)+),
+            Level::DEBUG => tracing::debug!($($args)+),
+            _ => tracing::trace!($($args)+),
+        }
+    };
+}
+
+/// Request tracing layer.
+///
+/// Each request runs inside an `http` span with method, path, matched route,
+/// request ID, user agent and client IP. The response is logged at a level
+/// chosen by status class, and requests slower than the configured threshold
+/// are logged at WARN instead.
+///
+/// # Example
+///
+/// ```rust,ignore
+/// use server_kit_rest::DefaultTraceLayer;
+/// use std::time::Duration;
+/// use tracing::Level;
+///
+/// let layer = DefaultTraceLayer::new()
+///     .success_level(Level::INFO)
+///     .request_header("x-tenant-id")
+///     .response_header("content-length")
+///     .slow_request_threshold(Duration::from_millis(500));
+///
+/// let app = Router::new().route("/", get(handler)).layer(layer);
+/// ```
+#[derive(Clone, Default)]
+pub struct DefaultTraceLayer {
+    config: Arc<TraceConfig>,
+}
+
+impl DefaultTraceLayer {
+    /// Create a trace layer with default settings.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a trace layer from the `trace` config section.
+    pub fn from_config(config: &TraceConfig) -> Self {
+        Self {
+            config: Arc::new(config.clone()),
+        }
+    }
+
+    /// Set the level for 1xx, 2xx and 3xx responses.
+    pub fn success_level(mut self, level: Level) -> Self {
+        Arc::make_mut(&mut self.config).success_level = level;
+        self
+    }
+
+    /// Set the level for 4xx responses.
+    pub fn client_error_level(mut self, level: Level) -> Self {
+        Arc::make_mut(&mut self.config).client_error_level = level;
+        self
+    }
+
+    /// Set the level for 5xx responses.
+    pub fn server_error_level(mut self, level: Level) -> Self {
+        Arc::make_mut(&mut self.config).server_error_level = level;
+        self
+    }
+
+    /// Record a request header on the response event.
+    pub fn request_header(mut self, name: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.config)
+            .request_headers
+            .push(name.into());
+        self
+    }
+
+    /// Record a response header on the response event.
+    pub fn response_header(mut self, name: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.config)
+            .response_headers
+            .push(name.into());
+        self
+    }
+
+    /// Log requests slower than `threshold` at WARN.
+    pub fn slow_request_threshold(mut self, threshold: Duration) -> Self {
+        Arc::make_mut(&mut self.config).slow_request_threshold_ms =
+            Some(threshold.as_millis() as u64);
+        self
+    }
+
+    /// Disable slow request warnings.
+    pub fn without_slow_request_threshold(mut self) -> Self {
+        Arc::make_mut(&mut self.config).slow_request_threshold_ms = None;
+        self
+    }
+}
+
+impl<S> Layer<S> for DefaultTraceLayer {
+    type Service = TraceService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TraceService {
+            inner,
+            config: Arc::clone(&self.config),
+        }
+    }
+}
+
+/// Service created by [`DefaultTraceLayer`].
+#[derive(Clone)]
+pub struct TraceService<S> {
+    inner: S,
+    config: Arc<TraceConfig>,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for TraceService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: std::fmt::Display,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<ReqBody>) -> Self::Future {
+        let config = Arc::clone(&self.config);
+        let headers = req.headers();
+
+        let request_id = header_str(headers, "x-request-id").unwrap_or("-");
+        let user_agent = header_str(headers, header::USER_AGENT.as_str()).unwrap_or("-");
+        let route = req
+            .extensions()
+            .get::<MatchedPath>()
+            .map(|m| m.as_str())
             .unwrap_or("-");
+        let client_ip = ClientIp::from_extensions(req.extensions())
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| "-".to_string());
 
-        tracing::info_span!(
+        let span = tracing::info_span!(
             "http",
-            method = %request.method(),
-            path = %request.uri().path(),
+            method = %req.method(),
+            path = %req.uri().path(),
+            route = %route,
             request_id = %request_id,
+            user_agent = %user_agent,
+            client_ip = %client_ip,
+        );
+        let request_headers = capture_headers(headers, &config.request_headers);
+
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+
+        Box::pin(
+            async move {
+                let start = Instant::now();
+                let result = inner.call(req).await;
+                let latency = start.elapsed();
+                let latency_ms = latency.as_secs_f64() * 1000.0;
+
+                match &result {
+                    Ok(response) => {
+                        let status = response.status().as_u16();
+                        let response_headers =
+                            capture_headers(response.headers(), &config.response_headers);
+
+                        if is_slow(&config, latency) {
+                            tracing::warn!(
+                                status,
+                                latency_ms,
+                                threshold_ms = config.slow_request_threshold_ms,
+                                request_headers = ?request_headers,
+                                response_headers = ?response_headers,
+                                "slow request"
+                            );
+                        } else {
+                            event_at!(
+                                level_for_status(&config, response.status()),
+                                status,
+                                latency_ms,
+                                request_headers = ?request_headers,
+                                response_headers = ?response_headers,
+                                "response"
+                            );
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!(latency_ms, error = %e, "request failed");
+                    }
+                }
+
+                result
+            }
+            .instrument(span),
         )
     }
 }
 
-/// Inner type alias for the configured TraceLayer.
-pub type InnerTraceLayer =
-    TraceLayer<SharedClassifier<ServerErrorsAsFailures>, RequestSpan, (), DefaultOnResponse>;
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
 
-/// Pre-configured TraceLayer with request ID and latency logging.
-///
-/// - Uses `RequestSpan` for span creation (includes request ID)
-/// - Logs responses at INFO level with latency in microseconds
-#[derive(Clone, Copy)]
-pub struct DefaultTraceLayer;
+fn capture_headers(headers: &HeaderMap, names: &[String]) -> Vec<(String, String)> {
+    names
+        .iter()
+        .filter_map(|name| {
+            let value = headers.get(name.as_str())?;
+            let value = if REDACTED_HEADERS.iter().any(|h| h.as_str().eq_ignore_ascii_case(name)) {
+                "[redacted]".to_string()
+            } else {
+                String::from_utf8_lossy(value.as_bytes()).into_owned()
+            };
+            Some((name.to_lowercase(), value))
+        })
+        .collect()
+}
 
-impl DefaultTraceLayer {
-    pub fn new() -> Self {
-        Self
+fn level_for_status(config: &TraceConfig, status: StatusCode) -> Level {
+    if status.is_server_error() {
+        config.server_error_level
+    } else if status.is_client_error() {
+        config.client_error_level
+    } else {
+        config.success_level
     }
 }
 
-impl Default for DefaultTraceLayer {
-    fn default() -> Self {
-        Self::new()
-    }
+fn is_slow(config: &TraceConfig, latency: Duration) -> bool {
+    config
+        .slow_request_threshold()
+        .is_some_and(|threshold| latency > threshold)
 }
 
-impl<S> Layer<S> for DefaultTraceLayer {
-    type Service = <InnerTraceLayer as Layer<S>>::Service;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
 
-    fn layer(&self, inner: S) -> Self::Service {
-        TraceLayer::new_for_http()
-            .make_span_with(RequestSpan)
-            .on_request(())
-            .on_response(
-                DefaultOnResponse::new()
-                    .level(Level::INFO)
-                    .latency_unit(LatencyUnit::Micros),
-            )
-            .layer(inner)
+    #[test]
+    fn level_for_status_uses_status_class() {
+        let config = TraceConfig::default();
+        assert_eq!(level_for_status(&config, StatusCode::OK), Level::DEBUG);
+        assert_eq!(
+            level_for_status(&config, StatusCode::MOVED_PERMANENTLY),
+            Level::DEBUG
+        );
+        assert_eq!(level_for_status(&config, StatusCode::NOT_FOUND), Level::WARN);
+        assert_eq!(
+            level_for_status(&config, StatusCode::BAD_GATEWAY),
+            Level::ERROR
+        );
+    }
+
+    #[test]
+    fn is_slow_respects_threshold() {
+        let layer = DefaultTraceLayer::new().slow_request_threshold(Duration::from_millis(100));
+        assert!(!is_slow(&layer.config, Duration::from_millis(50)));
+        assert!(is_slow(&layer.config, Duration::from_millis(150)));
+
+        let layer = layer.without_slow_request_threshold();
+        assert!(!is_slow(&layer.config, Duration::from_secs(60)));
+    }
+
+    #[test]
+    fn capture_headers_records_and_redacts() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-tenant-id", "acme".parse().unwrap());
+        headers.insert(header::AUTHORIZATION, "Bearer secret".parse().unwrap());
+
+        let names = vec![
+            "X-Tenant-Id".to_string(),
+            "authorization".to_string(),
+            "x-missing".to_string(),
+        ];
+        let captured = capture_headers(&headers, &names);
+
+        assert_eq!(
+            captured,
+            vec![
+                ("x-tenant-id".to_string(), "acme".to_string()),
+                ("authorization".to_string(), "[redacted]".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn builder_updates_config() {
+        let layer = DefaultTraceLayer::new()
+            .success_level(Level::INFO)
+            .client_error_level(Level::INFO)
+            .server_error_level(Level::WARN)
+            .request_header("x-a")
+            .response_header("x-b");
+
+        assert_eq!(layer.config.success_level, Level::INFO);
+        assert_eq!(layer.config.client_error_level, Level::INFO);
+        assert_eq!(layer.config.server_error_level, Level::WARN);
+        assert_eq!(layer.config.request_headers, vec!["x-a"]);
+        assert_eq!(layer.config.response_headers, vec!["x-b"]);
+    }
+
+    #[tokio::test]
+    async fn passes_through_responses() {
+        let app = Router::new()
+            .route("/", get(|| async { "OK" }))
+            .route("/missing", get(|| async { StatusCode::NOT_FOUND }))
+            .layer(DefaultTraceLayer::new().slow_request_threshold(Duration::ZERO));
+
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = app
+            .oneshot(Request::builder().uri("/missing").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 }
@@ -61,15 +61,43 @@ mod router;
 mod routes;
 mod server;
 
-pub use config::{ConfigBuilder, ConfigError, Environment, ServerConfig};
+pub use config::{
+    AccessLogConfig, AccessLogFormat, AccessLogOutput, AdaptiveConcurrencyConfig,
+    AuthorizationConfig, ConcurrencyConfig, ConfigBuilder, ConfigError, Environment,
+    ForwardedHeader, IpFilterConfig, IpNetwork, ProxyProtocolConfig, RateLimitConfig, RateLimitKey,
+    RateLimitQuota, RouteConcurrency, RoutePolicy, RouteRateLimit, RouteTimeout, ServerConfig,
+    TraceConfig, TrustedProxies,
+};
 pub use error::{ErrorResponse, HttpError};
+pub use layer::{
+    AccessLogLayer, ClientIp, ClientIpLayer, ConcurrencyLimitLayer, DefaultTraceLayer,
+    InFlightLayer, IpFilterLayer, PanicLayer, RequestTimeoutLayer,
+};
+pub use server_kit::{
+    build_info, install_panic_hook, AccessLog, BuildInfo, ConcurrencyError, ConcurrencyLimiter,
+    ConcurrencyLimits, ConcurrencyPermit, ConcurrencyStats, Deadline, DeadlineExceeded, IpFilter,
+    ListenAddr, Listener, LocalAddr, PanicHook, ProxiedAddr, RequestTimeouts, Secret, ServerHandle,
+    ShutdownController, ShutdownSignal, ShutdownToken,
+};
 pub use router::RouterExt;
-pub use routes::{fallback_handler, health_routes};
-pub use server::ServerError;
+pub use routes::{fallback_handler, health_routes, version_routes};
+pub use server::{
+    serve_router, serve_router_on, serve_router_with_shutdown, spawn_router, ServerError,
+};
 
 #[cfg(feature = "metrics")]
 pub use metrics::Metrics;
 
+#[cfg(feature = "ratelimit")]
+pub use layer::{reload_rate_limits, KeyedRateLimitLayer, RateLimitLayer};
+#[cfg(feature = "ratelimit")]
+pub use server_kit::{
+    BackendFuture, KeyExtractor, KeyedRateLimiter, MemoryBackend, RateLimitBackend,
+    RateLimitBackendError, RateLimitRequest, RateLimitStatus, Subject,
+};
+#[cfg(feature = "ratelimit-redis")]
+pub use server_kit::RedisBackend;
+
 #[cfg(feature = "tracing")]
 pub use logging::init_logging_from_env;
 
@@ -6,7 +6,8 @@ use axum::http::{header, Request, Response};
 use axum::response::IntoResponse;
 use axum::routing::get;
 use axum::Router;
-use metrics::{counter, histogram};
+use metrics::{counter, gauge, histogram};
+use server_kit::BuildInfo;
 use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 use std::future::Future;
 use std::pin::Pin;
@@ -82,6 +83,14 @@ impl Metrics {
     }
 }
 
+/// Publish the registered build info as a `build_info` gauge.
+pub(crate) fn record_build_info() {
+    if let Some(info) = BuildInfo::get() {
+        let labels = info.labels();
+        gauge!("build_info", &labels[..]).set(1.0);
+    }
+}
+
 async fn metrics_handler() -> impl IntoResponse {
     let handle = PROMETHEUS_HANDLE.get().expect("metrics not initialized");
 
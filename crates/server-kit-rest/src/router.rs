@@ -1,8 +1,9 @@
 //! Router extension traits for axum-like API.
 
 use axum::Router;
+use server_kit::{BuildInfo, Listener, ServerHandle, ShutdownController};
 
-use crate::routes::{fallback_handler, health_routes};
+use crate::routes::{fallback_handler, health_routes, version_routes};
 use crate::ServerConfig;
 
 /// Extension trait for Router that provides server-kit functionality.
@@ -35,16 +36,25 @@ pub trait RouterExt: Sized {
     /// Equivalent to `.fallback(fallback_handler)`.
     fn with_fallback(self) -> Self;
 
+    /// Adds `GET /version` and registers `info` as the process build info.
+    ///
+    /// Use with `build_info!()` so the values describe the calling crate.
+    fn with_version(self, info: BuildInfo) -> Self;
+
     /// Applies the default middleware stack.
     ///
     /// Layers applied (innermost to outermost):
-    /// - `CatchPanicLayer` - Converts panics to 500 responses
-    /// - `SetRequestIdLayer` / `PropagateRequestIdLayer` - X-Request-Id handling
-    /// - `TraceLayer` - Request/response logging with latency
-    /// - `TimeoutLayer` - Request timeout from config
+    /// - `PanicLayer` - Logs panics and converts them to JSON 500 responses
+    /// - `ConcurrencyLimitLayer` - Load shedding (when `config.concurrency.enabled`)
+    /// - `KeyedRateLimitLayer` - Rate limits (feature: `ratelimit`, when enabled)
+    /// - `DefaultTraceLayer` - Request/response logging, configured by `config.trace`
+    /// - `PropagateRequestIdLayer` / `SetRequestIdLayer` - X-Request-Id handling
+    /// - `RequestTimeoutLayer` - Request timeout and `Deadline`, with `config.route_timeouts`
     /// - `CompressionLayer` - Response compression (feature: `compression`)
     /// - `CorsLayer` - CORS support (feature: `cors`, when origins configured)
-    /// - `JsonErrorLayer` - Converts error responses to JSON (outermost)
+    /// - `JsonErrorLayer` - Converts error responses to JSON
+    /// - `AccessLogLayer` - Access log lines (when `config.access_log.enabled`)
+    /// - `ClientIpLayer` - Resolves `ClientIp` using `config.trusted_proxies` (outermost)
     fn with_default_layers(self, config: &impl AsRef<ServerConfig>) -> Self;
 
     /// Adds Prometheus metrics collection and endpoint.
@@ -71,6 +81,44 @@ pub trait RouterExt: Sized {
         self,
         config: &(impl AsRef<ServerConfig> + Sync),
     ) -> impl std::future::Future<Output = Result<(), crate::ServerError>> + Send;
+
+    /// Serve the router, shutting down when `shutdown` is triggered.
+    ///
+    /// The controller's cleanup hooks run after in-flight requests have
+    /// drained. Use `ShutdownController::without_signals` in tests to stop
+    /// the server deterministically.
+    fn serve_with_controller(
+        self,
+        config: &(impl AsRef<ServerConfig> + Sync),
+        shutdown: ShutdownController,
+    ) -> impl std::future::Future<Output = Result<(), crate::ServerError>> + Send;
+
+    /// Serve the router on an already bound TCP or Unix listener.
+    ///
+    /// `config.host` and `config.port` are ignored.
+    fn serve_on(
+        self,
+        listener: impl Into<Listener> + Send,
+        config: &(impl AsRef<ServerConfig> + Sync),
+    ) -> impl std::future::Future<Output = Result<(), crate::ServerError>> + Send;
+
+    /// Bind and serve the router in a background task.
+    ///
+    /// The returned handle reports the bound address and stops the server:
+    ///
+    /// ```rust,ignore
+    /// let config = ServerConfig { port: 0, ..Default::default() };
+    /// let server = app.spawn(&config).await?;
+    /// let addr = server.local_addr().as_tcp().unwrap();
+    /// // ...
+    /// server.stop().await?;
+    /// ```
+    fn spawn(
+        self,
+        config: &(impl AsRef<ServerConfig> + Sync),
+    ) -> impl std::future::Future<
+        Output = Result<ServerHandle<crate::ServerError>, crate::ServerError>,
+    > + Send;
 }
 
 impl RouterExt for Router {
@@ -82,6 +130,11 @@ impl RouterExt for Router {
         self.fallback(fallback_handler)
     }
 
+    fn with_version(self, info: BuildInfo) -> Self {
+        let info = info.register().clone();
+        self.merge(version_routes(info))
+    }
+
     fn with_default_layers(self, config: &impl AsRef<ServerConfig>) -> Self {
         crate::layer::default_layers(self, config.as_ref())
     }
@@ -102,4 +155,27 @@ impl RouterExt for Router {
     ) -> Result<(), crate::ServerError> {
         crate::server::serve_router(self, config).await
     }
+
+    async fn serve_with_controller(
+        self,
+        config: &(impl AsRef<ServerConfig> + Sync),
+        shutdown: ShutdownController,
+    ) -> Result<(), crate::ServerError> {
+        crate::server::serve_router_with_shutdown(self, config, shutdown).await
+    }
+
+    async fn serve_on(
+        self,
+        listener: impl Into<Listener> + Send,
+        config: &(impl AsRef<ServerConfig> + Sync),
+    ) -> Result<(), crate::ServerError> {
+        crate::server::serve_router_on(self, listener, config, ShutdownController::new()).await
+    }
+
+    async fn spawn(
+        self,
+        config: &(impl AsRef<ServerConfig> + Sync),
+    ) -> Result<ServerHandle<crate::ServerError>, crate::ServerError> {
+        crate::server::spawn_router(self, config).await
+    }
 }
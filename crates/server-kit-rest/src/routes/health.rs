@@ -1,10 +1,27 @@
 use axum::http::StatusCode;
 use axum::routing::get;
-use axum::Router;
+use axum::{Extension, Router};
+use server_kit::Drain;
 
-/// Returns a router with `GET /health` endpoint.
+/// Returns a router with `GET /health` and `GET /ready` endpoints.
+///
+/// `/ready` returns 503 once the server serving it has started shutting
+/// down, so load balancers stop sending new traffic during the pre-stop
+/// delay.
 pub fn health_routes() -> Router {
-    Router::new().route("/health", get(|| async { StatusCode::OK }))
+    Router::new()
+        .route("/health", get(|| async { StatusCode::OK }))
+        .route("/ready", get(ready))
+}
+
+/// The [`Drain`] is added by `serve_router_on`; without it the router isn't
+/// being served by this crate and is always ready.
+async fn ready(drain: Option<Extension<Drain>>) -> StatusCode {
+    if drain.is_some_and(|Extension(drain)| drain.is_draining()) {
+        StatusCode::SERVICE_UNAVAILABLE
+    } else {
+        StatusCode::OK
+    }
 }
 
 #[cfg(test)]
@@ -23,4 +40,26 @@ mod tests {
             .unwrap();
         assert_eq!(response.status(), StatusCode::OK);
     }
+
+    #[tokio::test]
+    async fn ready_endpoint_returns_ok() {
+        let app = health_routes();
+        let response = app
+            .oneshot(Request::builder().uri("/ready").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn ready_endpoint_fails_while_draining() {
+        let drain = Drain::new(server_kit::InFlight::new());
+        drain.shutdown_after(async {}, std::time::Duration::ZERO).await;
+        let app = health_routes().layer(Extension(drain));
+        let response = app
+            .oneshot(Request::builder().uri("/ready").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    }
 }
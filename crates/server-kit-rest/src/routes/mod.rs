@@ -1,5 +1,7 @@
 mod fallback;
 mod health;
+mod version;
 
 pub use fallback::fallback_handler;
 pub use health::health_routes;
+pub use version::version_routes;
@@ -0,0 +1,35 @@
+use axum::routing::get;
+use axum::{Json, Router};
+use server_kit::BuildInfo;
+
+/// Returns a router with `GET /version` serving the given build info as JSON.
+pub fn version_routes(info: BuildInfo) -> Router {
+    Router::new().route("/version", get(move || async move { Json(info) }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::{Request, StatusCode};
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn version_endpoint_returns_build_info() {
+        let info = BuildInfo::new("app", "1.2.3").with_build_env(Some("abc123"), None, None, None);
+        let app = version_routes(info);
+
+        let response = app
+            .oneshot(Request::builder().uri("/version").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(json["name"], "app");
+        assert_eq!(json["version"], "1.2.3");
+        assert_eq!(json["git_sha"], "abc123");
+    }
+}
@@ -1,9 +1,19 @@
 //! Server utilities.
 
+use crate::layer::InFlightLayer;
 use crate::ServerConfig;
-use axum::Router;
+use axum::extract::{ConnectInfo, Request};
+use axum::serve::ListenerExt;
+use axum::{Extension, Router};
+use server_kit::{
+    Drain, InFlight, Listener, ProxiedAddr, ProxyProtocolAcceptor, RestartRegistration, SdNotify,
+    ServerHandle, ShutdownController,
+};
+use std::future::{Future, IntoFuture};
+use std::net::SocketAddr;
+use std::pin::Pin;
 use std::{fmt, io};
-use tokio::net::TcpListener;
+use tokio::net::TcpStream;
 
 /// Error type for server operations.
 #[derive(Debug)]
@@ -32,52 +42,345 @@ impl std::error::Error for ServerError {
 }
 
 /// Serve a router with graceful shutdown support.
+///
+/// Serves on the socket handed over by a hot restart or the systemd socket
+/// named by `config.listen_fd_name` if there is one, otherwise binds
+/// `config.listen_addr()` (TCP or `unix://` socket), then continues with
+/// [`serve_router_on`].
+///
+/// With `config.hot_restart`, SIGUSR2 re-executes the process, hands it the
+/// listening socket and, once it is serving, drains this process through
+/// the normal shutdown path.
+///
+/// Shuts down on SIGINT and SIGTERM; use [`serve_router_with_shutdown`] to
+/// control shutdown.
 pub async fn serve_router(
     router: Router,
     config: &(impl AsRef<ServerConfig> + Sync),
+) -> Result<(), ServerError> {
+    serve_router_with_shutdown(router, config, ShutdownController::new()).await
+}
+
+/// [`serve_router`] with shutdown started by `shutdown`.
+pub async fn serve_router_with_shutdown(
+    router: Router,
+    config: &(impl AsRef<ServerConfig> + Sync),
+    shutdown: ShutdownController,
 ) -> Result<(), ServerError> {
     let config = config.as_ref();
-    let addr = config.addr();
-    let listener = TcpListener::bind(&addr)
+    let (listener, _restart) = listen(config).await?;
+    serve_router_on(router, listener, config, shutdown).await
+}
+
+/// Bind or inherit the configured listener and offer it for hot restart if
+/// enabled.
+async fn listen(
+    config: &ServerConfig,
+) -> Result<(Listener, Option<RestartRegistration>), ServerError> {
+    let addr = config.listen_addr();
+    let listener = Listener::bind_or_inherit(&addr, config.listen_fd_name.as_deref())
         .await
         .map_err(ServerError::Bind)?;
+    let restart = config
+        .hot_restart
+        .then(|| {
+            server_kit::register_for_restart(
+                &addr.to_string(),
+                &listener,
+                config.hot_restart_timeout(),
+            )
+        })
+        .transpose()
+        .map_err(ServerError::Bind)?;
+    Ok((listener, restart))
+}
+
+/// Serve a router on an already bound listener.
+///
+/// Shutdown starts when `shutdown` is triggered (by a signal, from code or
+/// via `server_kit::request_shutdown`). `/ready` then starts failing, the
+/// server keeps accepting connections for `shutdown_delay_secs`, waits up to
+/// `shutdown_timeout_secs` for in-flight requests, and finally runs the
+/// controller's cleanup hooks.
+///
+/// Under systemd, `READY=1` is sent once listening, `STOPPING=1` when
+/// shutdown starts, and watchdog pings while running if `WatchdogSec=` is set.
+///
+/// `ConnectInfo<SocketAddr>` is only available on TCP listeners. With
+/// `config.proxy_protocol.enabled` every connection must come from a trusted
+/// proxy and start with a PROXY header; `ConnectInfo<SocketAddr>` is then the
+/// original client address and `ConnectInfo<ProxiedAddr>` has the details.
+pub async fn serve_router_on(
+    router: Router,
+    listener: impl Into<Listener>,
+    config: &(impl AsRef<ServerConfig> + Sync),
+    shutdown: ShutdownController,
+) -> Result<(), ServerError> {
+    let config = config.as_ref();
+    let listener = listener.into();
+    let local_addr = listener.local_addr().map_err(ServerError::Bind)?;
 
-    tracing::info!("Server listening on {}", addr);
+    tracing::info!("Server listening on {}", local_addr);
+    server_kit::log_build_info();
+    #[cfg(feature = "metrics")]
+    crate::metrics::record_build_info();
 
-    axum::serve(listener, router)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .map_err(ServerError::Runtime)?;
+    let notify = SdNotify::global();
+    notify.ready(&format!("Listening on {}", local_addr));
+    let _watchdog = notify.spawn_watchdog();
+    server_kit::restart_ready();
+
+    let drain = Drain::new(InFlight::global().clone());
+    let signal = {
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown.wait().await;
+            notify.stopping();
+        }
+    };
+    let graceful = drain.shutdown_after(signal, config.shutdown_delay());
+    let router = router
+        .layer(InFlightLayer::new())
+        .layer(Extension(drain.clone()));
+    let server: Pin<Box<dyn Future<Output = io::Result<()>> + Send>> = match listener {
+        Listener::Tcp(listener) if config.proxy_protocol.enabled => {
+            tracing::info!("PROXY protocol enabled");
+            let acceptor = ProxyProtocolAcceptor::new(listener, &config.proxy_protocol)
+                .map_err(ServerError::Bind)?;
+            let router = router.layer(axum::middleware::map_request(client_connect_info));
+            // Wrapping the listener with `tap_io` also gets axum's
+            // `ConnectInfo<ProxiedAddr>` impl, which the orphan rule keeps
+            // us from writing for `ProxyProtocolListener` itself.
+            let listener = ProxyProtocolListener(acceptor).tap_io(|stream: &mut TcpStream| {
+                if let Err(e) = stream.set_nodelay(true) {
+                    tracing::trace!("Failed to set TCP_NODELAY: {}", e);
+                }
+            });
+            Box::pin(
+                axum::serve(
+                    listener,
+                    router.into_make_service_with_connect_info::<ProxiedAddr>(),
+                )
+                .with_graceful_shutdown(graceful)
+                .into_future(),
+            )
+        }
+        Listener::Tcp(listener) => Box::pin(
+            axum::serve(
+                listener,
+                router.into_make_service_with_connect_info::<SocketAddr>(),
+            )
+            .with_graceful_shutdown(graceful)
+            .into_future(),
+        ),
+        #[cfg(unix)]
+        Listener::Unix(_) if config.proxy_protocol.enabled => {
+            return Err(ServerError::Bind(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "PROXY protocol requires a TCP listener",
+            )));
+        }
+        #[cfg(unix)]
+        Listener::Unix(listener) => Box::pin(
+            axum::serve(listener, router.into_make_service())
+                .with_graceful_shutdown(graceful)
+                .into_future(),
+        ),
+    };
+
+    let result = drain.run(server, config.shutdown_timeout()).await;
+    shutdown.run_hooks().await;
+    if let Some(result) = result {
+        result.map_err(ServerError::Runtime)?;
+    }
 
     tracing::info!("Server shutdown complete");
     Ok(())
 }
 
-/// Waits for shutdown signals (SIGINT or SIGTERM).
-async fn shutdown_signal() {
-    let ctrl_c = async {
-        tokio::signal::ctrl_c()
+/// Accepts connections through a [`ProxyProtocolAcceptor`].
+struct ProxyProtocolListener(ProxyProtocolAcceptor);
+
+impl axum::serve::Listener for ProxyProtocolListener {
+    type Io = TcpStream;
+    type Addr = ProxiedAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        self.0.accept().await
+    }
+
+    /// The bound address, reported as the `peer` of an unproxied address.
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(ProxiedAddr {
+            peer: self.0.local_addr(),
+            source: None,
+            destination: None,
+        })
+    }
+}
+
+/// Expose the original client as `ConnectInfo<SocketAddr>` so extractors,
+/// tracing and access logs see it instead of the proxy.
+async fn client_connect_info(mut request: Request) -> Request {
+    if let Some(ConnectInfo(addr)) = request.extensions().get::<ConnectInfo<ProxiedAddr>>() {
+        let client = addr.client();
+        request.extensions_mut().insert(ConnectInfo(client));
+    }
+    request
+}
+
+/// Bind (or inherit, see [`serve_router`]) and serve the router in a
+/// background task.
+///
+/// The returned handle reports the bound address (useful with port `0`)
+/// and stops the server. The server also stops on SIGINT and SIGTERM.
+pub async fn spawn_router(
+    router: Router,
+    config: &(impl AsRef<ServerConfig> + Sync),
+) -> Result<ServerHandle<ServerError>, ServerError> {
+    let config = config.as_ref().clone();
+    let (listener, restart) = listen(&config).await?;
+    let local_addr = listener.local_addr().map_err(ServerError::Bind)?;
+
+    let shutdown = ShutdownController::new();
+    let task = tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            let _restart = restart;
+            serve_router_on(router, listener, &config, shutdown).await
+        }
+    });
+    Ok(ServerHandle::new(local_addr, shutdown, task))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn shuts_down_when_triggered_from_code() {
+        let config = ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            ..Default::default()
+        };
+        let shutdown = ShutdownController::new().without_signals();
+        let cleaned_up = Arc::new(AtomicBool::new(false));
+        {
+            let cleaned_up = Arc::clone(&cleaned_up);
+            shutdown.on_shutdown("cleanup", Duration::from_secs(1), move || async move {
+                cleaned_up.store(true, Ordering::SeqCst);
+            });
+        }
+
+        let router = Router::new().route("/", get(|| async { "OK" }));
+        let server = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { serve_router_with_shutdown(router, &config, shutdown).await }
+        });
+
+        shutdown.shutdown();
+        server.await.unwrap().unwrap();
+        assert!(cleaned_up.load(Ordering::SeqCst));
+    }
+
+    async fn get_over(stream: impl tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let mut stream = stream;
+        stream
+            .write_all(b"GET / HTTP/1.1\r\nhost: test\r\nconnection: close\r\n\r\n")
             .await
-            .expect("Failed to install Ctrl+C handler");
-    };
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn spawn_reports_bound_port() {
+        let config = ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            ..Default::default()
+        };
+        let router = Router::new().route("/", get(|| async { "OK" }));
+
+        let server = spawn_router(router, &config).await.unwrap();
+        let addr = server.local_addr().as_tcp().unwrap();
+        assert_ne!(addr.port(), 0);
+
+        let response = get_over(tokio::net::TcpStream::connect(addr).await.unwrap()).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("OK"));
+
+        server.stop().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn proxy_protocol_exposes_client_address() {
+        let mut config = ServerConfig {
+            host: "127.0.0.1".to_string(),
+            port: 0,
+            ..Default::default()
+        };
+        config.proxy_protocol.enabled = true;
+        config.proxy_protocol.trusted_sources = vec!["127.0.0.1".parse().unwrap()];
+        let router = Router::new().route(
+            "/",
+            get(
+                |ConnectInfo(client): ConnectInfo<SocketAddr>,
+                 ConnectInfo(proxied): ConnectInfo<ProxiedAddr>| async move {
+                    format!("{} via {}", client, proxied.peer.ip())
+                },
+            ),
+        );
+
+        let server = spawn_router(router, &config).await.unwrap();
+        let addr = server.local_addr().as_tcp().unwrap();
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        tokio::io::AsyncWriteExt::write_all(
+            &mut stream,
+            b"PROXY TCP4 203.0.113.7 127.0.0.1 51234 443\r\n",
+        )
+        .await
+        .unwrap();
+        let response = get_over(stream).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
+        assert!(response.ends_with("203.0.113.7:51234 via 127.0.0.1"));
+
+        // Plain HTTP without the header is closed.
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let _ = tokio::io::AsyncWriteExt::write_all(&mut stream, b"GET / HTTP/1.1\r\n\r\n").await;
+        let mut buf = [0u8; 1];
+        let read = tokio::io::AsyncReadExt::read(&mut stream, &mut buf).await;
+        assert!(matches!(read, Ok(0) | Err(_)));
+
+        server.stop().await.unwrap();
+    }
 
     #[cfg(unix)]
-    let terminate = async {
-        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
-            .expect("Failed to install SIGTERM handler")
-            .recv()
-            .await;
-    };
+    #[tokio::test]
+    async fn serves_on_unix_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.sock");
+        let config = ServerConfig {
+            host: format!("unix://{}", path.display()),
+            ..Default::default()
+        };
+        let router = Router::new().route("/", get(|| async { "OK" }));
+
+        let server = spawn_router(router, &config).await.unwrap();
+        assert_eq!(server.local_addr().as_unix(), Some(path.as_path()));
 
-    #[cfg(not(unix))]
-    let terminate = std::future::pending::<()>();
+        let response = get_over(tokio::net::UnixStream::connect(&path).await.unwrap()).await;
+        assert!(response.starts_with("HTTP/1.1 200"));
 
-    tokio::select! {
-        _ = ctrl_c => {
-            tracing::info!("Received SIGINT, starting graceful shutdown...");
-        },
-        _ = terminate => {
-            tracing::info!("Received SIGTERM, starting graceful shutdown...");
-        },
+        server.stop().await.unwrap();
     }
 }
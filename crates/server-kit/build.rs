@@ -0,0 +1,12 @@
+use std::process::Command;
+
+fn main() {
+    // Fallback rustc version for `build_info!()` in crates without their own
+    // build script; everything in one build uses the same compiler.
+    let rustc = std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    if let Ok(output) = Command::new(rustc).arg("--version").output() {
+        let version = String::from_utf8_lossy(&output.stdout);
+        println!("cargo:rustc-env=SERVER_KIT_RUSTC_VERSION={}", version.trim());
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,591 @@
+//! Access logging in Common/Combined Log Format.
+//!
+//! The HTTP and gRPC crates provide layers that build an [`AccessLogRecord`]
+//! per request and wrap the response body in an [`AccessLogBody`], which
+//! writes the log line once the body has been sent (or dropped).
+
+use bytes::Buf;
+use http_body::{Body, Frame, SizeHint};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Tracing target used by [`AccessLogOutput::Tracing`].
+pub const ACCESS_LOG_TARGET: &str = "access_log";
+
+const COMMON_TEMPLATE: &str =
+    "$remote_addr - $remote_user [$time_local] \"$request\" $status $body_bytes_sent";
+const COMBINED_TEMPLATE: &str = "$remote_addr - $remote_user [$time_local] \"$request\" $status $body_bytes_sent \"$http_referer\" \"$http_user_agent\"";
+
+/// Access log line format.
+///
+/// `Common` and `Combined` follow the nginx/Apache layouts, followed by the
+/// request ID, latency in milliseconds and, for gRPC, the `grpc-status`.
+/// `Custom` templates use nginx-style variables:
+///
+/// | Variable           | Value                                    |
+/// | ------------------ | ---------------------------------------- |
+/// | `$remote_addr`     | Client IP                                |
+/// | `$remote_user`     | Authenticated user (`-` if unknown)      |
+/// | `$time_local`      | `10/Oct/2000:13:55:36 +0000`             |
+/// | `$time_iso8601`    | `2000-10-10T13:55:36Z`                   |
+/// | `$request`         | `GET /path?query HTTP/1.1`               |
+/// | `$request_method`  | Request method                           |
+/// | `$request_uri`     | Path and query                           |
+/// | `$server_protocol` | `HTTP/1.1`, `HTTP/2.0`, ...              |
+/// | `$status`          | Response status code                     |
+/// | `$body_bytes_sent` | Response body size in bytes              |
+/// | `$http_referer`    | `Referer` header                         |
+/// | `$http_user_agent` | `User-Agent` header                      |
+/// | `$request_id`      | `X-Request-Id` header                    |
+/// | `$request_time`    | Latency in seconds, millisecond precision |
+/// | `$latency_ms`      | Latency in milliseconds                  |
+/// | `$grpc_status`     | `grpc-status` code (`-` for plain HTTP)  |
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum AccessLogFormat {
+    Common,
+    #[default]
+    Combined,
+    Custom(String),
+}
+
+impl AccessLogFormat {
+    fn template(&self) -> String {
+        match self {
+            Self::Common => format!("{COMMON_TEMPLATE} $request_id $latency_ms"),
+            Self::Combined => format!("{COMBINED_TEMPLATE} $request_id $latency_ms"),
+            Self::Custom(template) => template.clone(),
+        }
+    }
+}
+
+impl Serialize for AccessLogFormat {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Common => serializer.serialize_str("common"),
+            Self::Combined => serializer.serialize_str("combined"),
+            Self::Custom(template) => serializer.serialize_str(template),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for AccessLogFormat {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(match s.to_lowercase().as_str() {
+            "common" | "clf" => Self::Common,
+            "combined" => Self::Combined,
+            _ => Self::Custom(s),
+        })
+    }
+}
+
+/// Where access log lines are written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AccessLogOutput {
+    #[default]
+    Stdout,
+    /// Append to the file at `AccessLogConfig::path`.
+    File,
+    /// Emit an INFO event with target [`ACCESS_LOG_TARGET`].
+    Tracing,
+}
+
+/// Access log configuration.
+///
+/// Loaded from the `access_log` section of the server config.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AccessLogConfig {
+    pub enabled: bool,
+    pub format: AccessLogFormat,
+    pub output: AccessLogOutput,
+    /// Log file path, required when `output` is `file`.
+    pub path: Option<PathBuf>,
+}
+
+/// Data collected for a single access log line.
+#[derive(Debug, Clone)]
+pub struct AccessLogRecord {
+    /// Client IP, as resolved by the server's client IP layer.
+    pub remote_addr: Option<IpAddr>,
+    pub remote_user: Option<String>,
+    pub time: SystemTime,
+    pub method: String,
+    pub uri: String,
+    pub protocol: String,
+    pub status: u16,
+    pub bytes_sent: u64,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+    pub request_id: Option<String>,
+    pub latency: Duration,
+    pub grpc_status: Option<String>,
+}
+
+impl AccessLogRecord {
+    /// Start a record from the request line and headers.
+    pub fn from_request<B>(req: &http::Request<B>) -> Self {
+        let header = |name: &str| {
+            req.headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+
+        Self {
+            remote_addr: None,
+            remote_user: None,
+            time: SystemTime::now(),
+            method: req.method().to_string(),
+            uri: req
+                .uri()
+                .path_and_query()
+                .map(|pq| pq.as_str().to_string())
+                .unwrap_or_else(|| req.uri().path().to_string()),
+            protocol: format!("{:?}", req.version()),
+            status: 0,
+            bytes_sent: 0,
+            referer: header("referer"),
+            user_agent: header("user-agent"),
+            request_id: header("x-request-id"),
+            latency: Duration::ZERO,
+            grpc_status: None,
+        }
+    }
+
+    /// Fill in status, request ID fallback and `grpc-status` from the response head.
+    pub fn set_response<B>(&mut self, res: &http::Response<B>) {
+        self.status = res.status().as_u16();
+        if self.request_id.is_none() {
+            self.request_id = res
+                .headers()
+                .get("x-request-id")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string);
+        }
+        if let Some(status) = res.headers().get("grpc-status").and_then(|v| v.to_str().ok()) {
+            self.grpc_status = Some(status.to_string());
+        }
+    }
+
+    fn var(&self, name: &str) -> Option<String> {
+        let or_dash = |v: &Option<String>| v.clone().unwrap_or_else(|| "-".to_string());
+
+        Some(match name {
+            "remote_addr" => self
+                .remote_addr
+                .map(|ip| ip.to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            "remote_user" => or_dash(&self.remote_user),
+            "time_local" => format_clf_time(self.time),
+            "time_iso8601" => format_iso8601(self.time),
+            "request" => format!("{} {} {}", self.method, self.uri, self.protocol),
+            "request_method" => self.method.clone(),
+            "request_uri" => self.uri.clone(),
+            "server_protocol" => self.protocol.clone(),
+            "status" => self.status.to_string(),
+            "body_bytes_sent" => self.bytes_sent.to_string(),
+            "http_referer" => or_dash(&self.referer),
+            "http_user_agent" => or_dash(&self.user_agent),
+            "request_id" => or_dash(&self.request_id),
+            "request_time" => format!("{:.3}", self.latency.as_secs_f64()),
+            "latency_ms" => format!("{:.3}", self.latency.as_secs_f64() * 1000.0),
+            "grpc_status" => or_dash(&self.grpc_status),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Literal(String),
+    Var(String),
+}
+
+fn parse_template(template: &str) -> Vec<Segment> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            literal.push(c);
+            continue;
+        }
+
+        let mut name = String::new();
+        while let Some(&next) = chars.peek() {
+            if next.is_ascii_alphanumeric() || next == '_' {
+                name.push(next);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+
+        if name.is_empty() {
+            literal.push('$');
+        } else {
+            if !literal.is_empty() {
+                segments.push(Segment::Literal(std::mem::take(&mut literal)));
+            }
+            segments.push(Segment::Var(name));
+        }
+    }
+
+    if !literal.is_empty() {
+        segments.push(Segment::Literal(literal));
+    }
+    segments
+}
+
+enum Writer {
+    Stdout,
+    File(Mutex<File>),
+    Tracing,
+}
+
+/// Access log formatter and writer, shared by all requests.
+pub struct AccessLog {
+    segments: Vec<Segment>,
+    append_grpc_status: bool,
+    writer: Writer,
+}
+
+impl std::fmt::Debug for AccessLog {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AccessLog")
+            .field("segments", &self.segments)
+            .finish_non_exhaustive()
+    }
+}
+
+impl AccessLog {
+    /// Write access logs to stdout.
+    pub fn stdout(format: AccessLogFormat) -> Self {
+        Self::with_writer(format, Writer::Stdout)
+    }
+
+    /// Append access logs to a file, creating it if needed.
+    pub fn file(format: AccessLogFormat, path: impl AsRef<Path>) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::with_writer(format, Writer::File(Mutex::new(file))))
+    }
+
+    /// Emit access logs as tracing events with target [`ACCESS_LOG_TARGET`].
+    pub fn tracing(format: AccessLogFormat) -> Self {
+        Self::with_writer(format, Writer::Tracing)
+    }
+
+    /// Build an access log from configuration.
+    pub fn from_config(config: &AccessLogConfig) -> io::Result<Self> {
+        let format = config.format.clone();
+        match config.output {
+            AccessLogOutput::Stdout => Ok(Self::stdout(format)),
+            AccessLogOutput::Tracing => Ok(Self::tracing(format)),
+            AccessLogOutput::File => {
+                let path = config.path.as_ref().ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "access_log.path is required when output is \"file\"",
+                    )
+                })?;
+                Self::file(format, path)
+            }
+        }
+    }
+
+    fn with_writer(format: AccessLogFormat, writer: Writer) -> Self {
+        Self {
+            append_grpc_status: !matches!(format, AccessLogFormat::Custom(_)),
+            segments: parse_template(&format.template()),
+            writer,
+        }
+    }
+
+    /// Render a record as a single log line (without trailing newline).
+    pub fn format(&self, record: &AccessLogRecord) -> String {
+        let mut line = String::with_capacity(128);
+        for segment in &self.segments {
+            match segment {
+                Segment::Literal(s) => line.push_str(s),
+                Segment::Var(name) => match record.var(name) {
+                    Some(value) => line.push_str(&value),
+                    None => {
+                        line.push('$');
+                        line.push_str(name);
+                    }
+                },
+            }
+        }
+        if self.append_grpc_status {
+            if let Some(status) = &record.grpc_status {
+                line.push(' ');
+                line.push_str(status);
+            }
+        }
+        line
+    }
+
+    /// Format and write a record.
+    pub fn log(&self, record: &AccessLogRecord) {
+        let line = self.format(record);
+        match &self.writer {
+            Writer::Stdout => {
+                let mut out = io::stdout().lock();
+                let _ = writeln!(out, "{line}");
+            }
+            Writer::File(file) => {
+                if let Ok(mut file) = file.lock() {
+                    let _ = writeln!(file, "{line}");
+                }
+            }
+            Writer::Tracing => {
+                tracing::info!(
+                    target: ACCESS_LOG_TARGET,
+                    method = %record.method,
+                    uri = %record.uri,
+                    status = record.status,
+                    bytes_sent = record.bytes_sent,
+                    latency_ms = record.latency.as_secs_f64() * 1000.0,
+                    request_id = record.request_id.as_deref().unwrap_or("-"),
+                    grpc_status = record.grpc_status.as_deref().unwrap_or("-"),
+                    "{line}"
+                );
+            }
+        }
+    }
+}
+
+/// Writes the record when the response body is finished or dropped.
+struct Pending {
+    log: Arc<AccessLog>,
+    record: AccessLogRecord,
+    start: Instant,
+}
+
+impl Drop for Pending {
+    fn drop(&mut self) {
+        self.record.latency = self.start.elapsed();
+        self.log.log(&self.record);
+    }
+}
+
+pin_project_lite::pin_project! {
+    /// Response body wrapper that counts bytes sent and captures `grpc-status`
+    /// from trailers, writing the access log line when dropped.
+    pub struct AccessLogBody<B> {
+        #[pin]
+        inner: B,
+        pending: Pending,
+    }
+}
+
+impl<B> AccessLogBody<B> {
+    /// Wrap a response body. `start` is when the request was received.
+    pub fn new(inner: B, log: Arc<AccessLog>, record: AccessLogRecord, start: Instant) -> Self {
+        Self {
+            inner,
+            pending: Pending { log, record, start },
+        }
+    }
+}
+
+impl<B> Body for AccessLogBody<B>
+where
+    B: Body,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let result = this.inner.poll_frame(cx);
+
+        if let Poll::Ready(Some(Ok(frame))) = &result {
+            if let Some(data) = frame.data_ref() {
+                this.pending.record.bytes_sent += data.remaining() as u64;
+            } else if let Some(status) = frame
+                .trailers_ref()
+                .and_then(|t| t.get("grpc-status"))
+                .and_then(|v| v.to_str().ok())
+            {
+                this.pending.record.grpc_status = Some(status.to_string());
+            }
+        }
+
+        result
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Split a timestamp into UTC (year, month, day, hour, minute, second).
+pub(crate) fn utc_parts(time: SystemTime) -> (i64, u32, u32, u64, u64, u64) {
+    let secs = time
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Howard Hinnant's civil_from_days.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day, rem / 3600, (rem % 3600) / 60, rem % 60)
+}
+
+fn format_clf_time(time: SystemTime) -> String {
+    let (year, month, day, h, m, s) = utc_parts(time);
+    format!(
+        "{:02}/{}/{}:{:02}:{:02}:{:02} +0000",
+        day,
+        MONTHS[(month - 1) as usize],
+        year,
+        h,
+        m,
+        s
+    )
+}
+
+pub(crate) fn format_iso8601(time: SystemTime) -> String {
+    let (year, month, day, h, m, s) = utc_parts(time);
+    format!("{year:04}-{month:02}-{day:02}T{h:02}:{m:02}:{s:02}Z")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    fn record() -> AccessLogRecord {
+        AccessLogRecord {
+            remote_addr: Some("10.0.0.1".parse().unwrap()),
+            remote_user: None,
+            time: UNIX_EPOCH + Duration::from_secs(971_186_136),
+            method: "GET".to_string(),
+            uri: "/apache_pb.gif?x=1".to_string(),
+            protocol: "HTTP/1.0".to_string(),
+            status: 200,
+            bytes_sent: 2326,
+            referer: Some("http://example.com/start.html".to_string()),
+            user_agent: Some("Mozilla/4.08".to_string()),
+            request_id: Some("req-1".to_string()),
+            latency: Duration::from_micros(12_500),
+            grpc_status: None,
+        }
+    }
+
+    #[test]
+    fn formats_time() {
+        let time = UNIX_EPOCH + Duration::from_secs(971_186_136);
+        assert_eq!(format_clf_time(time), "10/Oct/2000:13:55:36 +0000");
+        assert_eq!(format_iso8601(time), "2000-10-10T13:55:36Z");
+    }
+
+    #[test]
+    fn formats_common() {
+        let log = AccessLog::stdout(AccessLogFormat::Common);
+        assert_eq!(
+            log.format(&record()),
+            r#"10.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /apache_pb.gif?x=1 HTTP/1.0" 200 2326 req-1 12.500"#
+        );
+    }
+
+    #[test]
+    fn formats_combined_with_grpc_status() {
+        let log = AccessLog::stdout(AccessLogFormat::Combined);
+        let mut record = record();
+        record.grpc_status = Some("5".to_string());
+
+        assert_eq!(
+            log.format(&record),
+            r#"10.0.0.1 - - [10/Oct/2000:13:55:36 +0000] "GET /apache_pb.gif?x=1 HTTP/1.0" 200 2326 "http://example.com/start.html" "Mozilla/4.08" req-1 12.500 5"#
+        );
+    }
+
+    #[test]
+    fn formats_custom_template() {
+        let log = AccessLog::stdout(AccessLogFormat::Custom(
+            "$request_method $request_uri $status $request_time $grpc_status $unknown $".to_string(),
+        ));
+        assert_eq!(
+            log.format(&record()),
+            "GET /apache_pb.gif?x=1 200 0.013 - $unknown $"
+        );
+    }
+
+    #[test]
+    fn format_deserializes_keywords_and_templates() {
+        let format: AccessLogFormat = serde_json::from_str(r#""Combined""#).unwrap();
+        assert_eq!(format, AccessLogFormat::Combined);
+        let format: AccessLogFormat = serde_json::from_str(r#""common""#).unwrap();
+        assert_eq!(format, AccessLogFormat::Common);
+        let format: AccessLogFormat = serde_json::from_str(r#""$status""#).unwrap();
+        assert_eq!(format, AccessLogFormat::Custom("$status".to_string()));
+    }
+
+    #[test]
+    fn from_config_requires_path_for_file() {
+        let config = AccessLogConfig {
+            output: AccessLogOutput::File,
+            ..Default::default()
+        };
+        assert!(AccessLog::from_config(&config).is_err());
+    }
+
+    #[tokio::test]
+    async fn body_counts_bytes_and_writes_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("access.log");
+        let log = Arc::new(
+            AccessLog::file(AccessLogFormat::Custom("$status $body_bytes_sent".into()), &path)
+                .unwrap(),
+        );
+
+        let mut record = record();
+        record.status = 201;
+        record.bytes_sent = 0;
+        let body = AccessLogBody::new(
+            http_body_util::Full::new(bytes::Bytes::from_static(b"hello")),
+            log,
+            record,
+            Instant::now(),
+        );
+
+        let collected = body.collect().await.unwrap().to_bytes();
+        assert_eq!(&collected[..], b"hello");
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(contents, "201 5\n");
+    }
+}
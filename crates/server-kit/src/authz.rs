@@ -0,0 +1,216 @@
+//! Scope and role based authorization.
+//!
+//! Auth layers insert the caller's [`Permissions`] next to its identity.
+//! A [`Policy`] lists what a route needs; [`PolicyMap`] assigns policies to
+//! route prefixes (gRPC paths like `/pkg.Service/Method` for tonic), and the
+//! HTTP and gRPC crates enforce it in an authorization layer.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// Scopes and roles granted to an authenticated caller.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Permissions {
+    scopes: HashSet<String>,
+    roles: HashSet<String>,
+}
+
+impl Permissions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn scopes<I, S>(mut self, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes.extend(scopes.into_iter().map(Into::into));
+        self
+    }
+
+    /// Add the scopes of an OAuth2 `scope` value, separated by spaces.
+    pub fn scope_str(self, scope: &str) -> Self {
+        self.scopes(scope.split_whitespace())
+    }
+
+    pub fn roles<I, S>(mut self, roles: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.roles.extend(roles.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.contains(scope)
+    }
+
+    pub fn has_role(&self, role: &str) -> bool {
+        self.roles.contains(role)
+    }
+}
+
+/// What a caller needs to access a route: every listed scope, and one of
+/// the listed roles if there are any. An empty policy only requires
+/// authentication.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Policy {
+    scopes: Vec<String>,
+    roles: Vec<String>,
+}
+
+impl Policy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require all of `scopes`.
+    pub fn scopes<I, S>(mut self, scopes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.scopes.extend(scopes.into_iter().map(Into::into));
+        self
+    }
+
+    /// Require one of `roles`.
+    pub fn roles<I, S>(mut self, roles: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.roles.extend(roles.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn allows(&self, permissions: &Permissions) -> bool {
+        self.scopes.iter().all(|scope| permissions.has_scope(scope))
+            && (self.roles.is_empty() || self.roles.iter().any(|role| permissions.has_role(role)))
+    }
+}
+
+/// Policies for route prefixes; the first matching prefix wins and routes
+/// without a match aren't checked.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PolicyMap {
+    routes: Vec<(String, Policy)>,
+}
+
+impl PolicyMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_config(config: &AuthorizationConfig) -> Self {
+        config.routes.iter().fold(Self::new(), |map, route| {
+            map.route(&route.prefix, route.policy())
+        })
+    }
+
+    /// Apply `policy` to routes starting with `prefix`.
+    pub fn route(mut self, prefix: impl Into<String>, policy: Policy) -> Self {
+        self.routes.push((prefix.into(), policy));
+        self
+    }
+
+    pub fn policy_for(&self, route: &str) -> Option<&Policy> {
+        self.routes
+            .iter()
+            .find(|(prefix, _)| route.starts_with(prefix.as_str()))
+            .map(|(_, policy)| policy)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.routes.is_empty()
+    }
+}
+
+/// Route policies loaded from the `authorization` section of the config
+/// file:
+///
+/// ```toml
+/// [[authorization.routes]]
+/// prefix = "/admin"
+/// roles = ["admin"]
+///
+/// [[authorization.routes]]
+/// prefix = "/orders"
+/// scopes = ["orders:write"]
+/// ```
+///
+/// `methods` is accepted as an alias of `routes`, and `method` of `prefix`,
+/// for gRPC paths such as `/orders.Orders/CreateOrder`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthorizationConfig {
+    #[serde(alias = "methods")]
+    pub routes: Vec<RoutePolicy>,
+}
+
+/// Policy for routes starting with a prefix.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoutePolicy {
+    #[serde(alias = "method")]
+    pub prefix: String,
+    /// Scopes the caller needs, all of them.
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// Roles of which the caller needs one.
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl RoutePolicy {
+    pub fn policy(&self) -> Policy {
+        Policy::new()
+            .scopes(self.scopes.iter().cloned())
+            .roles(self.roles.iter().cloned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn policy_needs_all_scopes_and_one_role() {
+        let writer = Permissions::new().scope_str("orders:read orders:write");
+        let admin = Permissions::new().roles(["admin"]);
+
+        let write = Policy::new().scopes(["orders:read", "orders:write"]);
+        assert!(write.allows(&writer));
+        assert!(!write.allows(&Permissions::new().scopes(["orders:read"])));
+        assert!(!write.allows(&admin));
+
+        let staff = Policy::new().roles(["admin", "support"]);
+        assert!(staff.allows(&admin));
+        assert!(!staff.allows(&writer));
+
+        assert!(Policy::new().allows(&Permissions::new()));
+    }
+
+    #[test]
+    fn first_matching_prefix_wins() {
+        let config: AuthorizationConfig = serde_json::from_value(serde_json::json!({
+            "methods": [
+                { "method": "/orders.Orders/CreateOrder", "scopes": ["orders:write"] },
+                { "method": "/orders.Orders/", "scopes": ["orders:read"] },
+            ]
+        }))
+        .unwrap();
+        let policies = PolicyMap::from_config(&config);
+
+        assert_eq!(
+            policies.policy_for("/orders.Orders/CreateOrder"),
+            Some(&Policy::new().scopes(["orders:write"]))
+        );
+        assert_eq!(
+            policies.policy_for("/orders.Orders/GetOrder"),
+            Some(&Policy::new().scopes(["orders:read"]))
+        );
+        assert_eq!(policies.policy_for("/greeter.Greeter/SayHello"), None);
+    }
+}
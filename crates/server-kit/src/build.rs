@@ -0,0 +1,116 @@
+//! Build script helpers.
+//!
+//! Call [`emit`] from your crate's `build.rs` so that
+//! [`build_info!`](crate::build_info) can pick up the git SHA, build
+//! timestamp, rustc version and enabled features.
+//!
+//! ```ignore
+//! // build.rs (with `server-kit` in [build-dependencies])
+//! fn main() {
+//!     server_kit::build::emit();
+//! }
+//! ```
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::access_log::format_iso8601;
+
+/// Export build information as `rustc-env` variables for the current crate.
+///
+/// - `GIT_SHA` overrides the SHA read from git (useful in Docker builds
+///   without a `.git` directory).
+/// - `SOURCE_DATE_EPOCH` overrides the build time for reproducible builds.
+pub fn emit() {
+    if let Some(sha) = git_sha() {
+        println!("cargo:rustc-env=SERVER_KIT_GIT_SHA={}", sha);
+    }
+    println!(
+        "cargo:rustc-env=SERVER_KIT_BUILD_TIMESTAMP={}",
+        format_iso8601(build_time())
+    );
+    if let Some(version) = rustc_version() {
+        println!("cargo:rustc-env=SERVER_KIT_RUSTC_VERSION={}", version);
+    }
+    println!("cargo:rustc-env=SERVER_KIT_FEATURES={}", features().join(","));
+
+    println!("cargo:rerun-if-env-changed=GIT_SHA");
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    if let Some(git_dir) = git_dir() {
+        // Both change on checkout and commit.
+        println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+        println!("cargo:rerun-if-changed={}", git_dir.join("logs/HEAD").display());
+    }
+}
+
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let out = String::from_utf8(output.stdout).ok()?;
+    Some(out.trim().to_string()).filter(|s| !s.is_empty())
+}
+
+fn git_sha() -> Option<String> {
+    env::var("GIT_SHA")
+        .ok()
+        .filter(|s| !s.is_empty())
+        .or_else(|| git(&["rev-parse", "HEAD"]))
+}
+
+fn git_dir() -> Option<PathBuf> {
+    git(&["rev-parse", "--absolute-git-dir"]).map(PathBuf::from)
+}
+
+fn build_time() -> SystemTime {
+    env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+        .unwrap_or_else(SystemTime::now)
+}
+
+fn rustc_version() -> Option<String> {
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let output = Command::new(rustc).arg("--version").output().ok()?;
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string()).filter(|s| !s.is_empty())
+}
+
+/// Enabled features from `CARGO_FEATURE_*`, excluding `default`.
+fn features() -> Vec<String> {
+    feature_names(env::vars().map(|(k, _)| k))
+}
+
+fn feature_names(vars: impl Iterator<Item = String>) -> Vec<String> {
+    let mut features: Vec<String> = vars
+        .filter_map(|key| {
+            key.strip_prefix("CARGO_FEATURE_")
+                .map(|name| name.to_lowercase().replace('_', "-"))
+        })
+        .filter(|name| name != "default")
+        .collect();
+    features.sort();
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn feature_names_from_env_keys() {
+        let vars = [
+            "CARGO_FEATURE_TLS",
+            "CARGO_FEATURE_DEFAULT",
+            "CARGO_FEATURE_RATE_LIMIT",
+            "CARGO_PKG_NAME",
+        ]
+        .into_iter()
+        .map(String::from);
+
+        assert_eq!(feature_names(vars), vec!["rate-limit", "tls"]);
+    }
+}
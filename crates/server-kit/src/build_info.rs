@@ -0,0 +1,171 @@
+//! Compile-time build information.
+//!
+//! [`build_info!`](crate::build_info) captures the calling crate's name and
+//! version together with the values exported by [`build::emit`](crate::build::emit)
+//! from its build script. Register the result once with
+//! [`BuildInfo::register`] so servers can log it at startup and expose it.
+
+use serde::{Deserialize, Serialize};
+use std::sync::{Once, OnceLock};
+
+static BUILD_INFO: OnceLock<BuildInfo> = OnceLock::new();
+static LOG_ONCE: Once = Once::new();
+
+/// Version and build details of the running binary.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildInfo {
+    /// Crate name.
+    pub name: String,
+    /// Crate version.
+    pub version: String,
+    /// Git commit the binary was built from.
+    pub git_sha: Option<String>,
+    /// Build time as an RFC 3339 UTC timestamp.
+    pub build_timestamp: Option<String>,
+    /// Output of `rustc --version`.
+    pub rustc_version: Option<String>,
+    /// Enabled cargo features.
+    pub features: Vec<String>,
+}
+
+impl BuildInfo {
+    pub fn new(name: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            version: version.into(),
+            git_sha: None,
+            build_timestamp: None,
+            rustc_version: option_env!("SERVER_KIT_RUSTC_VERSION").map(str::to_string),
+            features: Vec::new(),
+        }
+    }
+
+    /// Fill in the values exported by [`build::emit`](crate::build::emit).
+    #[doc(hidden)]
+    pub fn with_build_env(
+        mut self,
+        git_sha: Option<&str>,
+        build_timestamp: Option<&str>,
+        rustc_version: Option<&str>,
+        features: Option<&str>,
+    ) -> Self {
+        let non_empty = |s: Option<&str>| s.filter(|s| !s.is_empty()).map(str::to_string);
+
+        self.git_sha = non_empty(git_sha);
+        self.build_timestamp = non_empty(build_timestamp);
+        if let Some(rustc_version) = non_empty(rustc_version) {
+            self.rustc_version = Some(rustc_version);
+        }
+        self.features = features
+            .unwrap_or_default()
+            .split(',')
+            .filter(|f| !f.is_empty())
+            .map(str::to_string)
+            .collect();
+        self
+    }
+
+    /// Register this as the process-wide build info.
+    ///
+    /// The first registration wins; later calls return the registered value.
+    pub fn register(self) -> &'static BuildInfo {
+        BUILD_INFO.get_or_init(|| self)
+    }
+
+    /// The registered build info, if any.
+    pub fn get() -> Option<&'static BuildInfo> {
+        BUILD_INFO.get()
+    }
+
+    /// Label pairs for a `build_info` gauge.
+    pub fn labels(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("name", self.name.clone()),
+            ("version", self.version.clone()),
+            ("git_sha", self.git_sha.clone().unwrap_or_default()),
+            ("rustc_version", self.rustc_version.clone().unwrap_or_default()),
+            ("features", self.features.join(",")),
+        ]
+    }
+}
+
+/// Log the registered build info at INFO, at most once per process.
+pub fn log_build_info() {
+    let Some(info) = BuildInfo::get() else {
+        return;
+    };
+    LOG_ONCE.call_once(|| {
+        tracing::info!(
+            name = %info.name,
+            version = %info.version,
+            git_sha = info.git_sha.as_deref().unwrap_or("-"),
+            build_timestamp = info.build_timestamp.as_deref().unwrap_or("-"),
+            rustc_version = info.rustc_version.as_deref().unwrap_or("-"),
+            features = %info.features.join(","),
+            "build info"
+        );
+    });
+}
+
+/// Capture [`BuildInfo`] for the calling crate.
+///
+/// Name and version come from cargo. Git SHA, build timestamp, rustc version
+/// and features are read from the variables set by
+/// [`build::emit`](crate::build::emit) in the crate's build script; the
+/// rustc version falls back to the compiler that built `server-kit`.
+///
+/// # Example
+///
+/// ```ignore
+/// server_kit::build_info!().register();
+/// ```
+#[macro_export]
+macro_rules! build_info {
+    () => {
+        $crate::BuildInfo::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")).with_build_env(
+            option_env!("SERVER_KIT_GIT_SHA"),
+            option_env!("SERVER_KIT_BUILD_TIMESTAMP"),
+            option_env!("SERVER_KIT_RUSTC_VERSION"),
+            option_env!("SERVER_KIT_FEATURES"),
+        )
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn macro_captures_crate_metadata() {
+        let info = crate::build_info!();
+        assert_eq!(info.name, "server-kit");
+        assert_eq!(info.version, env!("CARGO_PKG_VERSION"));
+        assert!(info.rustc_version.unwrap().starts_with("rustc "));
+    }
+
+    #[test]
+    fn with_build_env_parses_values() {
+        let info = BuildInfo::new("app", "1.2.3").with_build_env(
+            Some("abc123"),
+            Some("2024-01-02T03:04:05Z"),
+            None,
+            Some("metrics,tls"),
+        );
+
+        assert_eq!(info.git_sha.as_deref(), Some("abc123"));
+        assert_eq!(info.build_timestamp.as_deref(), Some("2024-01-02T03:04:05Z"));
+        assert!(info.rustc_version.is_some());
+        assert_eq!(info.features, vec!["metrics", "tls"]);
+
+        let labels = info.labels();
+        assert!(labels.contains(&("version", "1.2.3".to_string())));
+        assert!(labels.contains(&("features", "metrics,tls".to_string())));
+    }
+
+    #[test]
+    fn empty_values_are_none() {
+        let info = BuildInfo::new("app", "1.0.0").with_build_env(Some(""), None, None, Some(""));
+        assert!(info.git_sha.is_none());
+        assert!(info.features.is_empty());
+    }
+}
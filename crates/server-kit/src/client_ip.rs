@@ -0,0 +1,290 @@
+//! Client IP resolution behind reverse proxies.
+//!
+//! Forwarding headers can be set by anyone, so they are only believed when
+//! the connection comes from a trusted proxy, and only the header those
+//! proxies set is read: a proxy that appends `X-Forwarded-For` passes a
+//! client's own `Forwarded` header through untouched. [`TrustedProxies::resolve`]
+//! walks that header from the right, skipping hops that are themselves
+//! trusted proxies, and returns the first address that isn't: the closest
+//! hop we can't vouch for.
+
+use crate::IpNetwork;
+use http::HeaderMap;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, SocketAddr};
+
+/// Proxies whose forwarding headers are believed, and the header they set.
+///
+/// Empty by default, which ignores forwarding headers and uses the socket
+/// peer. In config files it is either a list of networks, read from
+/// `X-Forwarded-For`, or a table naming the header:
+///
+/// ```toml
+/// trusted_proxies = ["10.0.0.0/8"]
+///
+/// [trusted_proxies]
+/// networks = ["10.0.0.0/8"]
+/// header = "forwarded"
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "RawTrustedProxies")]
+pub struct TrustedProxies {
+    networks: Vec<IpNetwork>,
+    header: ForwardedHeader,
+}
+
+/// The header trusted proxies record the client in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ForwardedHeader {
+    /// `X-Forwarded-For`, appended by nginx, HAProxy, AWS ALB and most
+    /// other proxies.
+    #[default]
+    XForwardedFor,
+    /// RFC 7239 `Forwarded`, using the `for=` parameters.
+    Forwarded,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawTrustedProxies {
+    Networks(Vec<IpNetwork>),
+    Table {
+        networks: Vec<IpNetwork>,
+        #[serde(default)]
+        header: ForwardedHeader,
+    },
+}
+
+impl From<RawTrustedProxies> for TrustedProxies {
+    fn from(raw: RawTrustedProxies) -> Self {
+        match raw {
+            RawTrustedProxies::Networks(networks) => Self::new(networks),
+            RawTrustedProxies::Table { networks, header } => Self::new(networks).header(header),
+        }
+    }
+}
+
+impl TrustedProxies {
+    /// Trust `networks`, reading `X-Forwarded-For`.
+    pub fn new(networks: impl IntoIterator<Item = IpNetwork>) -> Self {
+        Self {
+            networks: networks.into_iter().collect(),
+            header: ForwardedHeader::default(),
+        }
+    }
+
+    /// Read the client from `header` instead of `X-Forwarded-For`.
+    pub fn header(mut self, header: ForwardedHeader) -> Self {
+        self.header = header;
+        self
+    }
+
+    pub fn networks(&self) -> &[IpNetwork] {
+        &self.networks
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+    }
+
+    /// Returns `true` if `addr` is one of the trusted proxies.
+    pub fn is_trusted(&self, addr: IpAddr) -> bool {
+        IpNetwork::any_contains(&self.networks, addr)
+    }
+
+    /// Resolve the client address of a request received from `peer`.
+    pub fn resolve(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let mut client = peer.to_canonical();
+        if !self.is_trusted(client) {
+            return client;
+        }
+
+        for hop in forwarded_hops(headers, self.header).into_iter().rev() {
+            match hop {
+                Some(addr) if self.is_trusted(addr) => client = addr,
+                Some(addr) => return addr,
+                // `unknown` or an obfuscated identifier: the chain can't be
+                // followed further, so the last trusted proxy is the client.
+                None => return client,
+            }
+        }
+        client
+    }
+}
+
+impl FromIterator<IpNetwork> for TrustedProxies {
+    fn from_iter<I: IntoIterator<Item = IpNetwork>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+/// Hops from `header`, left to right.
+fn forwarded_hops(headers: &HeaderMap, header: ForwardedHeader) -> Vec<Option<IpAddr>> {
+    let values = |name: &str| {
+        headers
+            .get_all(name)
+            .iter()
+            // A header we can't read breaks the chain like an unknown hop.
+            .flat_map(|value| match value.to_str() {
+                Ok(value) => value.split(',').map(str::trim).collect::<Vec<_>>(),
+                Err(_) => vec![""],
+            })
+            .collect::<Vec<_>>()
+    };
+
+    match header {
+        ForwardedHeader::Forwarded => values(http::header::FORWARDED.as_str())
+            .into_iter()
+            .map(|element| {
+                element
+                    .split(';')
+                    .filter_map(|pair| pair.trim().split_once('='))
+                    .find(|(key, _)| key.eq_ignore_ascii_case("for"))
+                    .and_then(|(_, value)| parse_node(value.trim_matches('"')))
+            })
+            .collect(),
+        ForwardedHeader::XForwardedFor => values("x-forwarded-for")
+            .into_iter()
+            .map(parse_node)
+            .collect(),
+    }
+}
+
+/// Parse `1.2.3.4`, `1.2.3.4:80`, `2001:db8::1` or `[2001:db8::1]:80`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim();
+    let addr = node
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| node.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+        .or_else(|| {
+            node.strip_prefix('[')
+                .and_then(|rest| rest.strip_suffix(']'))
+                .and_then(|ip| ip.parse().ok())
+        })?;
+    Some(addr.to_canonical())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxies(nets: &[&str]) -> TrustedProxies {
+        nets.iter().map(|net| net.parse().unwrap()).collect()
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn untrusted_peer_ignores_headers() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        let headers = headers(&[("x-forwarded-for", "1.1.1.1")]);
+        assert_eq!(
+            trusted.resolve(ip("203.0.113.7"), &headers),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            TrustedProxies::default().resolve(ip("10.0.0.1"), &headers),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn walks_x_forwarded_for_from_the_right() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        // The leftmost entry is client-supplied and must not win.
+        let headers = headers(&[
+            ("x-forwarded-for", "6.6.6.6, 203.0.113.7"),
+            ("x-forwarded-for", "10.0.0.2"),
+        ]);
+        assert_eq!(trusted.resolve(ip("10.0.0.1"), &headers), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn all_trusted_returns_leftmost() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        let headers = headers(&[("x-forwarded-for", "10.0.0.3, 10.0.0.2")]);
+        assert_eq!(trusted.resolve(ip("10.0.0.1"), &headers), ip("10.0.0.3"));
+        assert_eq!(
+            trusted.resolve(ip("10.0.0.1"), &HeaderMap::new()),
+            ip("10.0.0.1")
+        );
+    }
+
+    #[test]
+    fn garbage_stops_at_last_trusted_hop() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        let headers = headers(&[("x-forwarded-for", "203.0.113.7, nonsense, 10.0.0.2")]);
+        assert_eq!(trusted.resolve(ip("10.0.0.1"), &headers), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn ignores_client_supplied_forwarded_header() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        // The proxy appended X-Forwarded-For and passed the client's own
+        // Forwarded header through.
+        let headers = headers(&[
+            ("forwarded", "for=1.2.3.4"),
+            ("x-forwarded-for", "203.0.113.7"),
+        ]);
+        assert_eq!(trusted.resolve(ip("10.0.0.1"), &headers), ip("203.0.113.7"));
+
+        let headers = self::headers(&[("forwarded", "for=1.2.3.4")]);
+        assert_eq!(trusted.resolve(ip("10.0.0.1"), &headers), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn reads_forwarded_header_when_configured() {
+        let trusted = proxies(&["10.0.0.0/8", "2001:db8:cafe::/48"]);
+        let trusted = trusted.header(ForwardedHeader::Forwarded);
+        let headers = headers(&[
+            ("x-forwarded-for", "6.6.6.6"),
+            (
+                "forwarded",
+                r#"for=198.51.100.17;proto=https, For="[2001:db8:cafe::17]:4711";by=10.0.0.1"#,
+            ),
+        ]);
+        assert_eq!(
+            trusted.resolve(ip("::ffff:10.0.0.1"), &headers),
+            ip("198.51.100.17")
+        );
+
+        let headers = self::headers(&[("forwarded", "for=unknown, for=10.0.0.2")]);
+        assert_eq!(trusted.resolve(ip("10.0.0.1"), &headers), ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn deserializes_list_or_table() {
+        let list: TrustedProxies = serde_json::from_str(r#"["10.0.0.0/8"]"#).unwrap();
+        assert_eq!(list, proxies(&["10.0.0.0/8"]));
+
+        let table: TrustedProxies = serde_json::from_value(serde_json::json!({
+            "networks": ["10.0.0.0/8"],
+            "header": "forwarded",
+        }))
+        .unwrap();
+        assert_eq!(
+            table,
+            proxies(&["10.0.0.0/8"]).header(ForwardedHeader::Forwarded)
+        );
+    }
+
+    #[test]
+    fn parses_nodes_with_ports() {
+        assert_eq!(parse_node("1.2.3.4:80"), Some(ip("1.2.3.4")));
+        assert_eq!(parse_node("[2001:db8::1]:80"), Some(ip("2001:db8::1")));
+        assert_eq!(parse_node("[2001:db8::1]"), Some(ip("2001:db8::1")));
+        assert_eq!(parse_node("_hidden"), None);
+    }
+}
@@ -0,0 +1,678 @@
+//! Concurrency limiting and load shedding.
+//!
+//! A [`ConcurrencyLimiter`] caps how many requests run at once. Requests over
+//! the limit wait in a bounded queue; when the queue is full, or a request
+//! has waited longer than the queue timeout, it's rejected so the server
+//! sheds load instead of letting work pile up until everything times out.
+//! With [`AdaptiveConcurrencyConfig`] the limit follows observed latency:
+//! it grows by one per limit's worth of fast requests and is cut by a factor
+//! when requests get slower than the target (AIMD).
+//!
+//! [`ConcurrencyLimits`] combines a global limiter with per-route ones; the
+//! HTTP and gRPC crates wrap it in a tower layer.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::pin::pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+
+/// Concurrency limits loaded from the `concurrency` section of the config
+/// file:
+///
+/// ```toml
+/// [concurrency]
+/// enabled = true
+/// max_in_flight = 200
+/// max_queue = 100
+/// queue_timeout_ms = 500
+///
+/// [[concurrency.routes]]
+/// prefix = "/reports"
+/// max_in_flight = 4
+///
+/// [concurrency.adaptive]
+/// enabled = true
+/// latency_target_ms = 250
+/// ```
+///
+/// `methods` is accepted as an alias of `routes` for gRPC paths.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConcurrencyConfig {
+    /// Apply the limits in the default layers. Off by default.
+    pub enabled: bool,
+    /// Requests processed at once across all routes. `0` means no global
+    /// limit.
+    pub max_in_flight: usize,
+    /// Requests waiting for a slot, per limit. Further requests are
+    /// rejected right away.
+    pub max_queue: usize,
+    /// How long a request may wait for a slot.
+    pub queue_timeout_ms: u64,
+    /// `Retry-After` sent with rejections, in seconds.
+    pub retry_after_secs: u64,
+    /// Separate limits for routes starting with a prefix; the first match
+    /// wins. They apply in addition to the global limit.
+    #[serde(alias = "methods")]
+    pub routes: Vec<RouteConcurrency>,
+    /// Adjust the limits from observed latency.
+    pub adaptive: AdaptiveConcurrencyConfig,
+}
+
+impl Default for ConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_in_flight: 0,
+            max_queue: 100,
+            queue_timeout_ms: 1000,
+            retry_after_secs: 1,
+            routes: Vec::new(),
+            adaptive: AdaptiveConcurrencyConfig::default(),
+        }
+    }
+}
+
+impl ConcurrencyConfig {
+    pub fn queue_timeout(&self) -> Duration {
+        Duration::from_millis(self.queue_timeout_ms)
+    }
+
+    pub fn retry_after(&self) -> Duration {
+        Duration::from_secs(self.retry_after_secs)
+    }
+}
+
+/// Concurrency limit for routes (gRPC: methods) starting with `prefix`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteConcurrency {
+    #[serde(alias = "method")]
+    pub prefix: String,
+    pub max_in_flight: usize,
+}
+
+/// Latency-driven adjustment of a limit.
+///
+/// The configured limit is the ceiling. Each request finishing under
+/// `latency_target_ms` raises the limit by `1 / limit`, so it grows by one
+/// per limit's worth of requests; a slower one multiplies it by
+/// `decrease_factor`, at most once per target latency, down to `min_limit`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AdaptiveConcurrencyConfig {
+    pub enabled: bool,
+    pub min_limit: usize,
+    pub latency_target_ms: u64,
+    pub decrease_factor: f64,
+}
+
+impl Default for AdaptiveConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_limit: 1,
+            latency_target_ms: 500,
+            decrease_factor: 0.9,
+        }
+    }
+}
+
+impl AdaptiveConcurrencyConfig {
+    pub fn latency_target(&self) -> Duration {
+        Duration::from_millis(self.latency_target_ms)
+    }
+}
+
+/// Why a request was shed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConcurrencyError {
+    /// The queue was full when the request arrived.
+    QueueFull,
+    /// The request waited longer than the queue timeout.
+    QueueTimeout,
+}
+
+impl ConcurrencyError {
+    /// Short label for logs and metrics.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConcurrencyError::QueueFull => "queue_full",
+            ConcurrencyError::QueueTimeout => "queue_timeout",
+        }
+    }
+}
+
+impl fmt::Display for ConcurrencyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConcurrencyError::QueueFull => f.write_str("concurrency limit queue is full"),
+            ConcurrencyError::QueueTimeout => f.write_str("timed out waiting for a free slot"),
+        }
+    }
+}
+
+impl std::error::Error for ConcurrencyError {}
+
+/// Snapshot of a limiter, passed to the [observer](ConcurrencyLimits::observe).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConcurrencyStats {
+    /// Current limit; below the configured one while adaptive mode backs off.
+    pub limit: usize,
+    pub in_flight: usize,
+    /// Requests waiting for a slot.
+    pub queued: usize,
+}
+
+type Observer = Arc<dyn Fn(&str, ConcurrencyStats) + Send + Sync>;
+
+struct State {
+    limit: f64,
+    in_flight: usize,
+    queued: usize,
+    last_decrease: Option<Instant>,
+}
+
+/// Caps the number of requests processed at once, with a bounded queue.
+///
+/// # Example
+///
+/// ```ignore
+/// let limiter = Arc::new(
+///     ConcurrencyLimiter::new("reports", 4)
+///         .max_queue(10)
+///         .queue_timeout(Duration::from_millis(200)),
+/// );
+/// let _permit = limiter.acquire().await?;
+/// ```
+pub struct ConcurrencyLimiter {
+    name: String,
+    max_in_flight: usize,
+    max_queue: usize,
+    queue_timeout: Duration,
+    adaptive: Option<AdaptiveConcurrencyConfig>,
+    state: Mutex<State>,
+    notify: Notify,
+    observer: Option<Observer>,
+}
+
+impl ConcurrencyLimiter {
+    /// Allow `max_in_flight` requests at once. `name` identifies the limiter
+    /// in logs and metrics.
+    pub fn new(name: impl Into<String>, max_in_flight: usize) -> Self {
+        let max_in_flight = max_in_flight.max(1);
+        Self {
+            name: name.into(),
+            max_in_flight,
+            max_queue: 100,
+            queue_timeout: Duration::from_secs(1),
+            adaptive: None,
+            state: Mutex::new(State {
+                limit: max_in_flight as f64,
+                in_flight: 0,
+                queued: 0,
+                last_decrease: None,
+            }),
+            notify: Notify::new(),
+            observer: None,
+        }
+    }
+
+    /// Set how many requests may wait for a slot (100 by default).
+    pub fn max_queue(mut self, max_queue: usize) -> Self {
+        self.max_queue = max_queue;
+        self
+    }
+
+    /// Set how long a request may wait for a slot (1 second by default).
+    pub fn queue_timeout(mut self, timeout: Duration) -> Self {
+        self.queue_timeout = timeout;
+        self
+    }
+
+    /// Adjust the limit from observed latency, up to `max_in_flight`.
+    pub fn adaptive(mut self, config: AdaptiveConcurrencyConfig) -> Self {
+        self.adaptive = config.enabled.then_some(config);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Current state of the limiter.
+    pub fn stats(&self) -> ConcurrencyStats {
+        Self::snapshot(&self.lock())
+    }
+
+    /// Wait for a slot. The slot is held until the permit is dropped.
+    pub async fn acquire(self: &Arc<Self>) -> Result<ConcurrencyPermit, ConcurrencyError> {
+        self.acquire_until(Instant::now() + self.queue_timeout)
+            .await
+            .map(|permit| ConcurrencyPermit {
+                permits: vec![permit],
+            })
+    }
+
+    async fn acquire_until(
+        self: &Arc<Self>,
+        deadline: Instant,
+    ) -> Result<Permit, ConcurrencyError> {
+        let mut queued = false;
+        loop {
+            let mut notified = pin!(self.notify.notified());
+            {
+                let mut state = self.lock();
+                if state.in_flight < Self::slots(&state) {
+                    state.in_flight += 1;
+                    if queued {
+                        state.queued -= 1;
+                    }
+                    self.observe(&state);
+                    break Ok(Permit {
+                        limiter: Arc::clone(self),
+                        started: Instant::now(),
+                    });
+                }
+                if !queued {
+                    if state.queued >= self.max_queue {
+                        break Err(ConcurrencyError::QueueFull);
+                    }
+                    state.queued += 1;
+                    queued = true;
+                    self.observe(&state);
+                }
+                // Register before unlocking so a release in between isn't missed.
+                notified.as_mut().enable();
+            }
+
+            if tokio::time::timeout_at(deadline.into(), notified)
+                .await
+                .is_err()
+            {
+                let mut state = self.lock();
+                state.queued -= 1;
+                self.observe(&state);
+                break Err(ConcurrencyError::QueueTimeout);
+            }
+        }
+    }
+
+    fn release(&self, latency: Duration) {
+        let mut state = self.lock();
+        state.in_flight -= 1;
+        self.adjust(&mut state, latency);
+        let free = Self::slots(&state).saturating_sub(state.in_flight);
+        let waiting = state.queued;
+        self.observe(&state);
+        drop(state);
+        for _ in 0..free.min(waiting) {
+            self.notify.notify_one();
+        }
+    }
+
+    /// Additive increase on fast requests, multiplicative decrease on slow
+    /// ones.
+    fn adjust(&self, state: &mut State, latency: Duration) {
+        if let Some(adaptive) = &self.adaptive {
+            let max = self.max_in_flight as f64;
+            if latency <= adaptive.latency_target() {
+                state.limit = (state.limit + 1.0 / state.limit).min(max);
+            } else if state
+                .last_decrease
+                .is_none_or(|at| at.elapsed() >= adaptive.latency_target())
+            {
+                let min = adaptive.min_limit.clamp(1, self.max_in_flight) as f64;
+                state.limit = (state.limit * adaptive.decrease_factor).max(min);
+                state.last_decrease = Some(Instant::now());
+                tracing::debug!(
+                    limiter = %self.name,
+                    limit = state.limit as usize,
+                    latency_ms = latency.as_millis() as u64,
+                    "Lowered concurrency limit"
+                );
+            }
+        }
+    }
+
+    fn slots(state: &State) -> usize {
+        state.limit as usize
+    }
+
+    fn snapshot(state: &State) -> ConcurrencyStats {
+        ConcurrencyStats {
+            limit: Self::slots(state),
+            in_flight: state.in_flight,
+            queued: state.queued,
+        }
+    }
+
+    fn observe(&self, state: &State) {
+        if let Some(observer) = &self.observer {
+            observer(&self.name, Self::snapshot(state));
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for ConcurrencyLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ConcurrencyLimiter")
+            .field("name", &self.name)
+            .field("max_in_flight", &self.max_in_flight)
+            .field("max_queue", &self.max_queue)
+            .field("queue_timeout", &self.queue_timeout)
+            .field("adaptive", &self.adaptive.is_some())
+            .field("stats", &self.stats())
+            .finish()
+    }
+}
+
+struct Permit {
+    limiter: Arc<ConcurrencyLimiter>,
+    started: Instant,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.limiter.release(self.started.elapsed());
+    }
+}
+
+/// Slots held by a request; released, and the latency recorded, on drop.
+pub struct ConcurrencyPermit {
+    permits: Vec<Permit>,
+}
+
+impl fmt::Debug for ConcurrencyPermit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.permits.iter().map(|permit| permit.limiter.name()))
+            .finish()
+    }
+}
+
+/// A global concurrency limit plus per-route ones.
+///
+/// A request to a route with its own limit needs a slot in both; waiting
+/// for the two shares one queue timeout. Routes without a limit of their
+/// own only take a global slot.
+///
+/// # Example
+///
+/// ```ignore
+/// let limits = ConcurrencyLimits::from_config(&config.concurrency);
+/// match limits.acquire("/reports/daily").await {
+///     Ok(permit) => { /* handle the request, then drop the permit */ }
+///     Err(e) => { /* 503 with limits.retry_after() */ }
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct ConcurrencyLimits {
+    global: Option<Arc<ConcurrencyLimiter>>,
+    routes: Vec<(String, Arc<ConcurrencyLimiter>)>,
+    retry_after: Duration,
+}
+
+impl ConcurrencyLimits {
+    pub fn new() -> Self {
+        Self {
+            retry_after: Duration::from_secs(1),
+            ..Default::default()
+        }
+    }
+
+    /// Build the limits from the `concurrency` config section. Without
+    /// `enabled` nothing is limited.
+    pub fn from_config(config: &ConcurrencyConfig) -> Self {
+        let mut limits = Self::new().retry_after(config.retry_after());
+        if !config.enabled {
+            return limits;
+        }
+        let limiter = |name: &str, max_in_flight| {
+            ConcurrencyLimiter::new(name, max_in_flight)
+                .max_queue(config.max_queue)
+                .queue_timeout(config.queue_timeout())
+                .adaptive(config.adaptive.clone())
+        };
+        if config.max_in_flight > 0 {
+            limits = limits.global(limiter("*", config.max_in_flight));
+        }
+        for route in &config.routes {
+            if route.max_in_flight > 0 {
+                limits = limits.route(&route.prefix, limiter(&route.prefix, route.max_in_flight));
+            }
+        }
+        limits
+    }
+
+    /// Set the limit shared by all routes.
+    pub fn global(mut self, limiter: ConcurrencyLimiter) -> Self {
+        self.global = Some(Arc::new(limiter));
+        self
+    }
+
+    /// Add a limit for routes starting with `prefix`.
+    pub fn route(mut self, prefix: impl Into<String>, limiter: ConcurrencyLimiter) -> Self {
+        self.routes.push((prefix.into(), Arc::new(limiter)));
+        self
+    }
+
+    /// Set the delay suggested to shed clients (1 second by default).
+    pub fn retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = retry_after;
+        self
+    }
+
+    /// Call `observer` with the limiter's name and state whenever a request
+    /// is queued, admitted, shed or finished, e.g. to export queue depth.
+    pub fn observe(
+        mut self,
+        observer: impl Fn(&str, ConcurrencyStats) + Send + Sync + 'static,
+    ) -> Self {
+        let observer: Observer = Arc::new(observer);
+        for limiter in self.limiters_mut() {
+            match Arc::get_mut(limiter) {
+                Some(limiter) => limiter.observer = Some(Arc::clone(&observer)),
+                None => tracing::warn!("Concurrency limiter already shared, observer not set"),
+            }
+        }
+        self
+    }
+
+    /// The suggested delay before retrying a shed request.
+    pub fn retry_after_delay(&self) -> Duration {
+        self.retry_after
+    }
+
+    /// Whether any limit is configured.
+    pub fn is_empty(&self) -> bool {
+        self.global.is_none() && self.routes.is_empty()
+    }
+
+    /// The limiters, global one first.
+    pub fn limiters(&self) -> impl Iterator<Item = &Arc<ConcurrencyLimiter>> {
+        self.global
+            .iter()
+            .chain(self.routes.iter().map(|(_, limiter)| limiter))
+    }
+
+    /// Wait for slots for a request to `route`.
+    pub async fn acquire(&self, route: &str) -> Result<ConcurrencyPermit, ConcurrencyError> {
+        let route_limiter = self
+            .routes
+            .iter()
+            .find(|(prefix, _)| route.starts_with(prefix.as_str()))
+            .map(|(_, limiter)| limiter);
+
+        let mut permits = Vec::with_capacity(2);
+        let started = Instant::now();
+        // The route's slot first: a slow route queues without holding a
+        // global slot other routes could use.
+        for limiter in route_limiter.into_iter().chain(&self.global) {
+            permits.push(
+                limiter
+                    .acquire_until(started + limiter.queue_timeout)
+                    .await?,
+            );
+        }
+        Ok(ConcurrencyPermit { permits })
+    }
+
+    fn limiters_mut(&mut self) -> impl Iterator<Item = &mut Arc<ConcurrencyLimiter>> {
+        self.global
+            .iter_mut()
+            .chain(self.routes.iter_mut().map(|(_, limiter)| limiter))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(max_in_flight: usize, max_queue: usize, timeout_ms: u64) -> Arc<ConcurrencyLimiter> {
+        Arc::new(
+            ConcurrencyLimiter::new("test", max_in_flight)
+                .max_queue(max_queue)
+                .queue_timeout(Duration::from_millis(timeout_ms)),
+        )
+    }
+
+    #[tokio::test]
+    async fn queues_until_a_slot_is_free() {
+        let limiter = limiter(1, 1, 1000);
+        let first = limiter.acquire().await.unwrap();
+
+        let waiting = tokio::spawn({
+            let limiter = Arc::clone(&limiter);
+            async move { limiter.acquire().await.map(|_| ()) }
+        });
+        while limiter.stats().queued == 0 {
+            tokio::task::yield_now().await;
+        }
+        // The queue holds one request; the next is shed right away.
+        assert_eq!(
+            limiter.acquire().await.unwrap_err(),
+            ConcurrencyError::QueueFull
+        );
+
+        drop(first);
+        waiting.await.unwrap().unwrap();
+        assert_eq!(
+            limiter.stats(),
+            ConcurrencyStats {
+                limit: 1,
+                in_flight: 0,
+                queued: 0
+            }
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sheds_requests_after_queue_timeout() {
+        let limiter = limiter(1, 10, 100);
+        let _held = limiter.acquire().await.unwrap();
+        let err = limiter.acquire().await.unwrap_err();
+        assert_eq!(err, ConcurrencyError::QueueTimeout);
+        assert_eq!(limiter.stats().queued, 0);
+    }
+
+    #[tokio::test]
+    async fn route_limits_apply_with_the_global_one() {
+        let limits = ConcurrencyLimits::from_config(&ConcurrencyConfig {
+            enabled: true,
+            max_in_flight: 3,
+            max_queue: 0,
+            routes: vec![RouteConcurrency {
+                prefix: "/reports".to_string(),
+                max_in_flight: 1,
+            }],
+            ..Default::default()
+        });
+
+        let report = limits.acquire("/reports/daily").await.unwrap();
+        assert_eq!(
+            limits.acquire("/reports/weekly").await.unwrap_err(),
+            ConcurrencyError::QueueFull
+        );
+        let a = limits.acquire("/api").await.unwrap();
+        let b = limits.acquire("/api").await.unwrap();
+        // The report holds a global slot too.
+        assert!(limits.acquire("/api").await.is_err());
+
+        drop((report, a, b));
+        assert!(limits.limiters().all(|l| l.stats().in_flight == 0));
+        assert!(ConcurrencyLimits::from_config(&ConcurrencyConfig::default()).is_empty());
+    }
+
+    #[test]
+    fn adaptive_limit_backs_off_and_recovers() {
+        let limiter = Arc::new(ConcurrencyLimiter::new("test", 10).adaptive(
+            AdaptiveConcurrencyConfig {
+                enabled: true,
+                min_limit: 2,
+                latency_target_ms: 0,
+                decrease_factor: 0.5,
+            },
+        ));
+        let adjust = |latency| limiter.adjust(&mut limiter.lock(), latency);
+        adjust(Duration::from_millis(5));
+        assert_eq!(limiter.stats().limit, 5);
+        adjust(Duration::from_millis(5));
+        adjust(Duration::from_millis(5));
+        assert_eq!(limiter.stats().limit, 2);
+
+        // +1/2, +1/2.5, +1/2.9: one more slot after about a limit's worth.
+        for _ in 0..3 {
+            adjust(Duration::ZERO);
+        }
+        assert_eq!(limiter.stats().limit, 3);
+    }
+
+    #[tokio::test]
+    async fn observer_sees_queue_depth() {
+        let depths = Arc::new(Mutex::new(Vec::new()));
+        let limits = ConcurrencyLimits::new()
+            .global(ConcurrencyLimiter::new("*", 1).queue_timeout(Duration::from_millis(10)))
+            .observe({
+                let depths = Arc::clone(&depths);
+                move |name, stats| {
+                    depths
+                        .lock()
+                        .unwrap()
+                        .push((name.to_string(), stats.queued))
+                }
+            });
+
+        let held = limits.acquire("/").await.unwrap();
+        assert!(limits.acquire("/").await.is_err());
+        drop(held);
+
+        let depths = depths.lock().unwrap();
+        assert!(depths.contains(&("*".to_string(), 1)));
+        assert_eq!(depths.last(), Some(&("*".to_string(), 0)));
+    }
+
+    #[test]
+    fn config_deserializes_with_defaults() {
+        let config: ConcurrencyConfig = serde_json::from_str(
+            r#"{
+                "enabled": true,
+                "max_in_flight": 200,
+                "methods": [{"method": "/reports.Reports/", "max_in_flight": 4}],
+                "adaptive": {"enabled": true}
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.routes[0].prefix, "/reports.Reports/");
+        assert_eq!(config.max_queue, 100);
+        assert_eq!(config.queue_timeout(), Duration::from_secs(1));
+        assert_eq!(config.adaptive.latency_target(), Duration::from_millis(500));
+        assert!(!ConcurrencyConfig::default().enabled);
+    }
+}
@@ -0,0 +1,205 @@
+//! Request deadlines and per-route timeouts.
+//!
+//! The HTTP and gRPC timeout layers pick a timeout with [`RequestTimeouts`],
+//! store the resulting [`Deadline`] in the request extensions and run the
+//! handler inside [`Deadline::timeout`], which also makes it available as
+//! [`Deadline::current`] so outbound calls can forward the remaining budget.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+tokio::task_local! {
+    static CURRENT: Deadline;
+}
+
+/// Point in time by which a request must be answered.
+///
+/// Handlers read it from the request extensions; code running inside the
+/// handler's task can also use [`Deadline::current`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    /// Deadline `timeout` from now.
+    pub fn after(timeout: Duration) -> Self {
+        Self(Instant::now() + timeout)
+    }
+
+    pub fn at(instant: Instant) -> Self {
+        Self(instant)
+    }
+
+    pub fn instant(&self) -> Instant {
+        self.0
+    }
+
+    /// Time left until the deadline, zero once it has passed.
+    pub fn remaining(&self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.0 <= Instant::now()
+    }
+
+    /// The deadline of the request being handled by the current task, if it
+    /// runs inside [`Deadline::timeout`] or [`Deadline::scope`].
+    pub fn current() -> Option<Deadline> {
+        CURRENT.try_with(|deadline| *deadline).ok()
+    }
+
+    /// Run `future` with this deadline as [`Deadline::current`], without
+    /// enforcing it. Use it to carry the deadline into spawned tasks.
+    pub fn scope<F: Future>(self, future: F) -> impl Future<Output = F::Output> {
+        CURRENT.scope(self, future)
+    }
+
+    /// Run `future` inside [`Deadline::scope`], failing once the deadline
+    /// passes. An enclosing deadline that is earlier wins.
+    pub async fn timeout<F: Future>(self, future: F) -> Result<F::Output, DeadlineExceeded> {
+        let deadline = Self::current().map_or(self, |outer| outer.min(self));
+        tokio::time::timeout_at(deadline.0, deadline.scope(future))
+            .await
+            .map_err(|_| DeadlineExceeded)
+    }
+}
+
+/// Error returned by [`Deadline::timeout`] when the deadline has passed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeadlineExceeded;
+
+impl fmt::Display for DeadlineExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("deadline exceeded")
+    }
+}
+
+impl std::error::Error for DeadlineExceeded {}
+
+/// Timeout override for routes starting with a prefix, from the config
+/// file:
+///
+/// ```toml
+/// [[route_timeouts]]
+/// prefix = "/reports"
+/// timeout_ms = 120000
+/// ```
+///
+/// `method` is accepted as an alias of `prefix` for gRPC paths. A timeout of
+/// `0` disables the timeout for the prefix, e.g. for long polling.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteTimeout {
+    #[serde(alias = "method")]
+    pub prefix: String,
+    pub timeout_ms: u64,
+}
+
+impl RouteTimeout {
+    pub fn timeout(&self) -> Option<Duration> {
+        (self.timeout_ms > 0).then(|| Duration::from_millis(self.timeout_ms))
+    }
+}
+
+/// Default request timeout with per-route-prefix overrides; the first
+/// matching prefix wins.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RequestTimeouts {
+    default: Option<Duration>,
+    routes: Vec<(String, Option<Duration>)>,
+}
+
+impl RequestTimeouts {
+    /// Timeouts using `default` for every route. A zero duration means no
+    /// timeout.
+    pub fn new(default: Duration) -> Self {
+        Self {
+            default: (!default.is_zero()).then_some(default),
+            routes: Vec::new(),
+        }
+    }
+
+    /// Build from a server's `request_timeout_secs` and route overrides.
+    pub fn from_config(default: Duration, routes: &[RouteTimeout]) -> Self {
+        routes.iter().fold(Self::new(default), |timeouts, route| {
+            timeouts.route(&route.prefix, route.timeout().unwrap_or_default())
+        })
+    }
+
+    /// Use `timeout` for routes starting with `prefix`; zero means no
+    /// timeout.
+    pub fn route(mut self, prefix: impl Into<String>, timeout: Duration) -> Self {
+        self.routes
+            .push((prefix.into(), (!timeout.is_zero()).then_some(timeout)));
+        self
+    }
+
+    /// Timeout for `route`, `None` when it has none.
+    pub fn for_route(&self, route: &str) -> Option<Duration> {
+        self.routes
+            .iter()
+            .find(|(prefix, _)| route.starts_with(prefix.as_str()))
+            .map_or(self.default, |(_, timeout)| *timeout)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn route_overrides_the_default() {
+        let timeouts = RequestTimeouts::from_config(
+            Duration::from_secs(30),
+            &[
+                RouteTimeout {
+                    prefix: "/reports".to_string(),
+                    timeout_ms: 120_000,
+                },
+                RouteTimeout {
+                    prefix: "/events".to_string(),
+                    timeout_ms: 0,
+                },
+            ],
+        );
+
+        assert_eq!(timeouts.for_route("/users"), Some(Duration::from_secs(30)));
+        assert_eq!(
+            timeouts.for_route("/reports/daily"),
+            Some(Duration::from_secs(120))
+        );
+        assert_eq!(timeouts.for_route("/events/stream"), None);
+        assert_eq!(RequestTimeouts::new(Duration::ZERO).for_route("/"), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn timeout_sets_the_current_deadline() {
+        assert_eq!(Deadline::current(), None);
+
+        let deadline = Deadline::after(Duration::from_secs(5));
+        let current = deadline.timeout(async { Deadline::current() }).await;
+        assert_eq!(current, Ok(Some(deadline)));
+
+        let result = Deadline::after(Duration::from_millis(10))
+            .timeout(tokio::time::sleep(Duration::from_secs(1)))
+            .await;
+        assert_eq!(result, Err(DeadlineExceeded));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn earlier_enclosing_deadline_wins() {
+        let outer = Deadline::after(Duration::from_secs(1));
+        let inner = outer
+            .timeout(async {
+                Deadline::after(Duration::from_secs(10))
+                    .timeout(async { Deadline::current() })
+                    .await
+            })
+            .await;
+        assert_eq!(inner, Ok(Ok(Some(outer))));
+        assert_eq!(outer.remaining(), Duration::from_secs(1));
+        assert!(!outer.is_expired());
+    }
+}
@@ -0,0 +1,273 @@
+//! Graceful shutdown with in-flight tracking and a drain deadline.
+//!
+//! [`InFlight`] counts requests from arrival until their response body is
+//! finished. [`Drain`] sequences shutdown for a server: after the shutdown
+//! signal the server's readiness starts failing ([`Drain::is_draining`]), an
+//! optional pre-stop delay lets load balancers deregister the instance while
+//! new connections are still accepted, and then the server gets `timeout` to
+//! finish in-flight requests before it is dropped.
+
+use bytes::Buf;
+use http_body::{Body, Frame, SizeHint};
+use pin_project_lite::pin_project;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::sync::watch;
+
+/// How often the in-flight count is logged while draining.
+const DRAIN_LOG_INTERVAL: Duration = Duration::from_secs(1);
+
+static GLOBAL_IN_FLIGHT: OnceLock<InFlight> = OnceLock::new();
+
+/// Counter of requests currently being processed.
+#[derive(Debug, Clone, Default)]
+pub struct InFlight {
+    count: Arc<AtomicUsize>,
+}
+
+impl InFlight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The process-wide counter used by the default layers.
+    pub fn global() -> &'static InFlight {
+        GLOBAL_IN_FLIGHT.get_or_init(InFlight::new)
+    }
+
+    /// Number of requests currently in flight.
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::Relaxed)
+    }
+
+    /// Count a request until the returned guard is dropped.
+    pub fn track(&self) -> InFlightGuard {
+        self.count.fetch_add(1, Ordering::Relaxed);
+        InFlightGuard {
+            count: Arc::clone(&self.count),
+        }
+    }
+}
+
+/// Decrements the [`InFlight`] counter when dropped.
+#[derive(Debug)]
+pub struct InFlightGuard {
+    count: Arc<AtomicUsize>,
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pin_project! {
+    /// Response body that keeps its request counted until the body ends.
+    pub struct InFlightBody<B> {
+        #[pin]
+        inner: B,
+        guard: Option<InFlightGuard>,
+    }
+}
+
+impl<B> InFlightBody<B> {
+    pub fn new(inner: B, guard: InFlightGuard) -> Self {
+        Self {
+            inner,
+            guard: Some(guard),
+        }
+    }
+}
+
+impl<B: Body> Body for InFlightBody<B>
+where
+    B::Data: Buf,
+{
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let result = this.inner.poll_frame(cx);
+        if let Poll::Ready(None) | Poll::Ready(Some(Err(_))) = &result {
+            this.guard.take();
+        }
+        result
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Shutdown sequencing for a single server.
+///
+/// # Example
+///
+/// ```ignore
+/// let drain = Drain::new(InFlight::global().clone());
+/// let controller = ShutdownController::new();
+/// let shutdown = drain.shutdown_after(async move { controller.wait().await }, config.shutdown_delay());
+/// let server = axum::serve(listener, app).with_graceful_shutdown(shutdown);
+///
+/// if drain.run(server.into_future(), config.shutdown_timeout()).await.is_none() {
+///     // drain deadline elapsed, remaining connections were closed
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Drain {
+    draining: Arc<AtomicBool>,
+    started: watch::Sender<bool>,
+    in_flight: InFlight,
+}
+
+impl Drain {
+    pub fn new(in_flight: InFlight) -> Self {
+        Self {
+            draining: Arc::new(AtomicBool::new(false)),
+            started: watch::channel(false).0,
+            in_flight,
+        }
+    }
+
+    /// Returns `true` once this server has started shutting down.
+    ///
+    /// Its readiness check should fail while this is `true`.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    /// Wrap a shutdown signal for the server's graceful shutdown hook.
+    ///
+    /// Once `signal` completes, readiness starts failing and the returned
+    /// future waits `pre_stop_delay` before resolving, so the server keeps
+    /// accepting connections until the load balancer has caught up.
+    pub fn shutdown_after<S>(
+        &self,
+        signal: S,
+        pre_stop_delay: Duration,
+    ) -> impl Future<Output = ()> + Send + 'static
+    where
+        S: Future<Output = ()> + Send + 'static,
+    {
+        let draining = Arc::clone(&self.draining);
+        let started = self.started.clone();
+        let in_flight = self.in_flight.clone();
+
+        async move {
+            signal.await;
+            draining.store(true, Ordering::Relaxed);
+
+            if !pre_stop_delay.is_zero() {
+                tracing::info!(
+                    delay_secs = pre_stop_delay.as_secs_f64(),
+                    "Readiness failing, waiting before draining"
+                );
+                tokio::time::sleep(pre_stop_delay).await;
+            }
+
+            tracing::info!(in_flight = in_flight.count(), "Draining in-flight requests");
+            started.send_replace(true);
+        }
+    }
+
+    /// Run `server`, dropping it `timeout` after draining has started.
+    ///
+    /// Returns `None` if the deadline elapsed before the server finished.
+    pub async fn run<F: Future>(&self, server: F, timeout: Duration) -> Option<F::Output> {
+        let mut started = self.started.subscribe();
+        let in_flight = self.in_flight.clone();
+
+        let deadline = async move {
+            let _ = started.wait_for(|started| *started).await;
+            let expired = tokio::time::sleep(timeout);
+            tokio::pin!(expired);
+
+            let mut interval = tokio::time::interval(DRAIN_LOG_INTERVAL);
+            interval.tick().await;
+            loop {
+                tokio::select! {
+                    _ = &mut expired => break,
+                    _ = interval.tick() => {
+                        tracing::info!(in_flight = in_flight.count(), "Waiting for in-flight requests");
+                    }
+                }
+            }
+
+            tracing::warn!(
+                in_flight = in_flight.count(),
+                timeout_secs = timeout.as_secs_f64(),
+                "Shutdown timeout elapsed, closing remaining connections"
+            );
+        };
+
+        tokio::select! {
+            output = server => Some(output),
+            _ = deadline => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bytes::Bytes;
+    use http_body_util::{BodyExt, Full};
+
+    #[tokio::test]
+    async fn body_releases_guard_at_end_of_stream() {
+        let in_flight = InFlight::new();
+        let body = InFlightBody::new(Full::new(Bytes::from("hello")), in_flight.track());
+        assert_eq!(in_flight.count(), 1);
+
+        body.collect().await.unwrap();
+        assert_eq!(in_flight.count(), 0);
+    }
+
+    #[tokio::test]
+    async fn dropping_body_releases_guard() {
+        let in_flight = InFlight::new();
+        let body = InFlightBody::new(Full::new(Bytes::from("hello")), in_flight.track());
+        drop(body);
+        assert_eq!(in_flight.count(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn run_forces_close_after_timeout() {
+        let drain = Drain::new(InFlight::new());
+        let shutdown = drain.shutdown_after(async {}, Duration::ZERO);
+        assert!(!drain.is_draining());
+        let server = async move {
+            shutdown.await;
+            // A stuck request keeps the server from finishing.
+            std::future::pending::<()>().await;
+        };
+
+        let result = drain.run(server, Duration::from_secs(5)).await;
+        assert!(result.is_none());
+        assert!(drain.is_draining());
+        assert!(!Drain::new(InFlight::new()).is_draining());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn run_returns_when_server_finishes() {
+        let drain = Drain::new(InFlight::new());
+        let shutdown = drain.shutdown_after(async {}, Duration::from_secs(2));
+        let start = tokio::time::Instant::now();
+
+        let result = drain.run(async move { shutdown.await; 7 }, Duration::from_secs(5)).await;
+        assert_eq!(result, Some(7));
+        assert!(start.elapsed() >= Duration::from_secs(2));
+    }
+}
@@ -0,0 +1,459 @@
+//! Zero-downtime restart by handing listening sockets to a new process.
+//!
+//! Servers with hot restart enabled register their listeners with
+//! [`register_for_restart`]. On SIGUSR2 the process re-executes itself with
+//! the same arguments and passes duplicates of the registered sockets as
+//! inherited descriptors. The new process adopts them in
+//! [`Listener::bind_or_inherit`] (matched by listen address) and reports
+//! ready through a pipe once every inherited socket is being served
+//! ([`restart_ready`]). The old process then shuts down through
+//! [`request_shutdown`](crate::request_shutdown), draining in-flight
+//! requests as usual. If the new process exits or misses the deadline it is
+//! killed and the old process keeps serving.
+//!
+//! Hot restart is only available on Unix.
+
+use std::io;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(unix)]
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::listener::Listener;
+
+#[cfg(unix)]
+use std::os::fd::{AsRawFd, OwnedFd, RawFd};
+
+/// Inherited sockets, as `fd=key` lines.
+#[cfg(unix)]
+const FDS_ENV: &str = "SERVER_KIT_RESTART_FDS";
+/// Write end of the readiness pipe.
+#[cfg(unix)]
+const READY_FD_ENV: &str = "SERVER_KIT_RESTART_READY_FD";
+/// PID of the process that started the restart; others ignore the variables.
+#[cfg(unix)]
+const PARENT_ENV: &str = "SERVER_KIT_RESTART_PARENT";
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+#[cfg(unix)]
+static REGISTRY: Mutex<Vec<Registered>> = Mutex::new(Vec::new());
+#[cfg(unix)]
+static SIGNAL_TASK: std::sync::Once = std::sync::Once::new();
+#[cfg(unix)]
+static RESTARTING: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+#[cfg(unix)]
+static INHERITED: std::sync::OnceLock<Mutex<Inherited>> = std::sync::OnceLock::new();
+#[cfg(unix)]
+static EXECUTABLE: std::sync::OnceLock<Option<PathBuf>> = std::sync::OnceLock::new();
+
+#[cfg(unix)]
+struct Registered {
+    id: u64,
+    key: String,
+    fd: OwnedFd,
+    ready_timeout: Duration,
+}
+
+/// Keeps a listener offered for hot restart until dropped.
+#[derive(Debug)]
+pub struct RestartRegistration {
+    #[cfg_attr(not(unix), allow(dead_code))]
+    id: u64,
+}
+
+#[cfg(unix)]
+impl Drop for RestartRegistration {
+    fn drop(&mut self) {
+        lock(&REGISTRY).retain(|registered| registered.id != self.id);
+    }
+}
+
+#[cfg(unix)]
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Offer `listener` to the replacement process started on SIGUSR2.
+///
+/// `key` identifies the socket across restarts; the servers use the listen
+/// address. The replacement has `ready_timeout` to report ready. The first
+/// registration installs the SIGUSR2 handler, so this must be called from
+/// within a Tokio runtime.
+pub fn register_for_restart(
+    key: &str,
+    listener: &Listener,
+    ready_timeout: Duration,
+) -> io::Result<RestartRegistration> {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsFd;
+
+        let fd = listener.as_fd().try_clone_to_owned()?;
+        // Resolve the binary before a deploy can replace it.
+        EXECUTABLE.get_or_init(|| std::env::current_exe().ok());
+        lock(&REGISTRY).push(Registered {
+            id,
+            key: key.to_string(),
+            fd,
+            ready_timeout,
+        });
+        SIGNAL_TASK.call_once(|| {
+            tokio::spawn(restart_on_signal());
+        });
+    }
+
+    #[cfg(not(unix))]
+    {
+        let _ = (key, listener, ready_timeout);
+        tracing::warn!("Hot restart is not supported on this platform");
+    }
+
+    Ok(RestartRegistration { id })
+}
+
+#[cfg(unix)]
+async fn restart_on_signal() {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut usr2 = match signal(SignalKind::user_defined2()) {
+        Ok(usr2) => usr2,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to install SIGUSR2 handler, hot restart disabled");
+            return;
+        }
+    };
+    while usr2.recv().await.is_some() {
+        tracing::info!("Received SIGUSR2, starting hot restart...");
+        if let Err(e) = hot_restart().await {
+            tracing::error!(error = %e, "Hot restart failed, continuing to serve");
+        }
+    }
+}
+
+/// Re-execute the current binary, hand over the registered listeners and,
+/// once the new process is ready, shut this one down gracefully.
+///
+/// This is what SIGUSR2 triggers. On error this process keeps serving.
+#[cfg(unix)]
+pub async fn hot_restart() -> io::Result<()> {
+    if RESTARTING.swap(true, Ordering::SeqCst) {
+        return Err(io::Error::other("a hot restart is already in progress"));
+    }
+
+    let result = async {
+        let (fds, ready_timeout) = {
+            let registry = lock(&REGISTRY);
+            let fds = registry
+                .iter()
+                .map(|r| Ok((r.key.clone(), r.fd.try_clone()?)))
+                .collect::<io::Result<Vec<_>>>()?;
+            let timeout = registry.iter().map(|r| r.ready_timeout).max();
+            (fds, timeout.unwrap_or(Duration::from_secs(30)))
+        };
+        if fds.is_empty() {
+            return Err(io::Error::other("no listeners registered for hot restart"));
+        }
+
+        let mut command = std::process::Command::new(executable()?);
+        command.args(std::env::args_os().skip(1));
+        restart_with(command, fds, ready_timeout).await
+    }
+    .await;
+
+    match result {
+        Ok(()) => crate::request_shutdown(),
+        Err(_) => RESTARTING.store(false, Ordering::SeqCst),
+    }
+    result
+}
+
+/// The binary to re-execute: the path it was started from, resolved when
+/// the first listener was registered.
+#[cfg(unix)]
+fn executable() -> io::Result<PathBuf> {
+    match EXECUTABLE.get_or_init(|| std::env::current_exe().ok()) {
+        Some(path) => Ok(replaced_path(path.clone())),
+        None => std::env::current_exe().map(replaced_path),
+    }
+}
+
+/// `path` without the " (deleted)" suffix Linux adds to `/proc/self/exe`
+/// once the binary has been replaced, e.g. by a deploy renaming a new one
+/// into place.
+#[cfg(unix)]
+fn replaced_path(path: PathBuf) -> PathBuf {
+    if path.exists() {
+        return path;
+    }
+    match path.to_str().and_then(|p| p.strip_suffix(" (deleted)")) {
+        Some(original) => PathBuf::from(original),
+        None => path,
+    }
+}
+
+/// Spawn `command` with `fds` inherited and wait for it to report ready.
+#[cfg(unix)]
+async fn restart_with(
+    mut command: std::process::Command,
+    fds: Vec<(String, OwnedFd)>,
+    ready_timeout: Duration,
+) -> io::Result<()> {
+    use std::io::Read;
+    use std::os::unix::process::CommandExt;
+
+    let (mut ready_rx, ready_tx) = io::pipe()?;
+    let entries: Vec<(RawFd, &str)> = fds
+        .iter()
+        .map(|(key, fd)| (fd.as_raw_fd(), key.as_str()))
+        .collect();
+    command
+        .env(FDS_ENV, encode_fds(&entries))
+        .env(READY_FD_ENV, ready_tx.as_raw_fd().to_string())
+        .env(PARENT_ENV, std::process::id().to_string());
+
+    let mut inherit: Vec<RawFd> = entries.iter().map(|(fd, _)| *fd).collect();
+    inherit.push(ready_tx.as_raw_fd());
+    // SAFETY: the closure only calls fcntl, which is async-signal-safe.
+    unsafe {
+        command.pre_exec(move || {
+            for fd in &inherit {
+                if libc::fcntl(*fd, libc::F_SETFD, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        });
+    }
+
+    let mut child = tokio::process::Command::from(command).spawn()?;
+    // Close our copies so the pipe reports EOF if the child exits.
+    drop(ready_tx);
+    drop(fds);
+    tracing::info!(
+        pid = child.id(),
+        "Started replacement process, waiting for it to become ready"
+    );
+
+    let ready = tokio::task::spawn_blocking(move || {
+        let mut buf = [0; 1];
+        ready_rx.read(&mut buf)
+    });
+    match tokio::time::timeout(ready_timeout, ready).await {
+        Ok(Ok(Ok(1))) => {
+            tracing::info!(pid = child.id(), "Replacement process is ready");
+            Ok(())
+        }
+        Ok(Ok(Ok(_))) => {
+            let _ = child.start_kill();
+            let status = child.wait().await?;
+            Err(io::Error::other(format!(
+                "replacement process exited before becoming ready ({})",
+                status
+            )))
+        }
+        Ok(Ok(Err(e))) => {
+            let _ = child.kill().await;
+            Err(e)
+        }
+        Ok(Err(e)) => Err(io::Error::other(e)),
+        Err(_) => {
+            let _ = child.kill().await;
+            Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                format!(
+                    "replacement process not ready after {}s",
+                    ready_timeout.as_secs_f64()
+                ),
+            ))
+        }
+    }
+}
+
+#[cfg(unix)]
+fn encode_fds(fds: &[(RawFd, &str)]) -> String {
+    fds.iter()
+        .map(|(fd, key)| format!("{}={}", fd, key))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(unix)]
+fn decode_fds(value: &str) -> Vec<(RawFd, String)> {
+    value
+        .lines()
+        .filter_map(|line| {
+            let (fd, key) = line.split_once('=')?;
+            Some((fd.parse().ok()?, key.to_string()))
+        })
+        .collect()
+}
+
+/// State of a process started by [`hot_restart`].
+#[cfg(unix)]
+struct Inherited {
+    fds: Vec<(RawFd, String)>,
+    ready: Option<io::PipeWriter>,
+}
+
+#[cfg(unix)]
+fn inherited() -> &'static Mutex<Inherited> {
+    use std::os::fd::FromRawFd;
+
+    INHERITED.get_or_init(|| {
+        let var = |key| std::env::var(key).ok();
+        let parent = var(PARENT_ENV).and_then(|pid| pid.parse::<u32>().ok());
+        if parent.is_none() || parent != Some(std::os::unix::process::parent_id()) {
+            return Mutex::new(Inherited {
+                fds: Vec::new(),
+                ready: None,
+            });
+        }
+
+        let fds = decode_fds(&var(FDS_ENV).unwrap_or_default());
+        let ready_fd = var(READY_FD_ENV).and_then(|fd| fd.parse::<RawFd>().ok());
+        for fd in fds.iter().map(|(fd, _)| *fd).chain(ready_fd) {
+            // Keep inherited descriptors out of processes we spawn.
+            // SAFETY: fcntl on a descriptor number has no memory effects.
+            unsafe { libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+        }
+        Mutex::new(Inherited {
+            fds,
+            // SAFETY: the parent passed us the write end of the pipe.
+            ready: ready_fd.map(|fd| unsafe { io::PipeWriter::from_raw_fd(fd) }),
+        })
+    })
+}
+
+/// Take the socket handed over under `key` by the previous process.
+#[cfg(unix)]
+pub(crate) fn take_inherited(key: &str) -> io::Result<Option<Listener>> {
+    let mut inherited = lock(inherited());
+    let Some(index) = inherited.fds.iter().position(|(_, k)| k == key) else {
+        return Ok(None);
+    };
+    let (fd, _) = inherited.fds.remove(index);
+    // SAFETY: the parent passed us this descriptor and it is taken once.
+    unsafe { crate::systemd::listener_from_fd(fd) }.map(Some)
+}
+
+/// Tell the previous process this one is ready, once every socket it
+/// handed over is being served.
+///
+/// Called by the servers after they start listening; a no-op in processes
+/// not started by [`hot_restart`].
+pub fn restart_ready() {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+
+        let mut inherited = lock(inherited());
+        if !inherited.fds.is_empty() {
+            return;
+        }
+        if let Some(mut ready) = inherited.ready.take() {
+            if let Err(e) = ready.write_all(b"1") {
+                tracing::warn!(error = %e, "Failed to report readiness to previous process");
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::fd::AsFd;
+    use std::process::Command;
+
+    #[test]
+    fn encodes_and_decodes_fds() {
+        let encoded = encode_fds(&[(7, "0.0.0.0:3000"), (8, "unix:///run/app.sock")]);
+        assert_eq!(
+            decode_fds(&encoded),
+            vec![
+                (7, "0.0.0.0:3000".to_string()),
+                (8, "unix:///run/app.sock".to_string())
+            ]
+        );
+        assert!(decode_fds("").is_empty());
+    }
+
+    fn listener_fd() -> (std::net::TcpListener, OwnedFd) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let fd = listener.as_fd().try_clone_to_owned().unwrap();
+        (listener, fd)
+    }
+
+    #[tokio::test]
+    async fn waits_for_child_to_report_ready() {
+        let (_listener, fd) = listener_fd();
+        let mut command = Command::new("sh");
+        // The child checks the socket was inherited, then reports ready.
+        command.arg("-c").arg(format!(
+            r#"fd="${{{FDS_ENV}%%=*}}"; test -e /dev/fd/$fd && printf 1 > /dev/fd/${{{READY_FD_ENV}}}"#
+        ));
+
+        restart_with(
+            command,
+            vec![("127.0.0.1:0".to_string(), fd)],
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn fails_when_child_exits_early() {
+        let (_listener, fd) = listener_fd();
+        let mut command = Command::new("sh");
+        command.arg("-c").arg("exit 3");
+
+        let err = restart_with(
+            command,
+            vec![("key".to_string(), fd)],
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("exited before becoming ready"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn finds_binary_replaced_by_a_deploy() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app");
+        std::fs::copy("/bin/sleep", &path).unwrap();
+        let mut child = Command::new(&path).arg("30").spawn().unwrap();
+
+        // Deploy a new binary by renaming it over the running one.
+        let staged = dir.path().join("app.new");
+        std::fs::copy("/bin/sleep", &staged).unwrap();
+        std::fs::rename(&staged, &path).unwrap();
+
+        let exe = std::fs::read_link(format!("/proc/{}/exe", child.id())).unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(exe.to_string_lossy().ends_with(" (deleted)"));
+        assert_eq!(replaced_path(exe), path);
+    }
+
+    #[tokio::test]
+    async fn kills_child_that_is_never_ready() {
+        let (_listener, fd) = listener_fd();
+        let mut command = Command::new("sleep");
+        command.arg("30");
+
+        let err = restart_with(
+            command,
+            vec![("key".to_string(), fd)],
+            Duration::from_millis(200),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    }
+}
@@ -0,0 +1,115 @@
+//! IP address ranges used by trust and filtering settings.
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+/// An IPv4 or IPv6 network in CIDR notation.
+///
+/// A bare address (`10.1.2.3`) is read as a single-host network. IPv4
+/// addresses mapped into IPv6 (`::ffff:10.1.2.3`), as reported by dual-stack
+/// sockets, match IPv4 networks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpNetwork(ipnet::IpNet);
+
+impl IpNetwork {
+    /// Returns `true` if `addr` is inside this network.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        self.0.contains(&addr.to_canonical())
+    }
+
+    /// Returns `true` if any of `networks` contains `addr`.
+    pub fn any_contains(networks: &[IpNetwork], addr: IpAddr) -> bool {
+        networks.iter().any(|network| network.contains(addr))
+    }
+}
+
+impl From<IpAddr> for IpNetwork {
+    fn from(addr: IpAddr) -> Self {
+        Self(addr.to_canonical().into())
+    }
+}
+
+impl FromStr for IpNetwork {
+    type Err = InvalidIpNetwork;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if let Ok(net) = s.parse::<ipnet::IpNet>() {
+            return Ok(Self(net.trunc()));
+        }
+        s.parse::<IpAddr>()
+            .map(Self::from)
+            .map_err(|_| InvalidIpNetwork(s.to_string()))
+    }
+}
+
+impl fmt::Display for IpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Serialize for IpNetwork {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for IpNetwork {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Error returned when parsing an [`IpNetwork`] fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidIpNetwork(String);
+
+impl fmt::Display for InvalidIpNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid IP network '{}'", self.0)
+    }
+}
+
+impl std::error::Error for InvalidIpNetwork {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn net(s: &str) -> IpNetwork {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_cidr_and_bare_addresses() {
+        assert_eq!(net("10.0.0.0/8").to_string(), "10.0.0.0/8");
+        assert_eq!(net("10.1.2.3/8").to_string(), "10.0.0.0/8");
+        assert_eq!(net("192.168.1.1").to_string(), "192.168.1.1/32");
+        assert_eq!(net("::1").to_string(), "::1/128");
+        assert!("10.0.0.0/33".parse::<IpNetwork>().is_err());
+        assert!("example.com".parse::<IpNetwork>().is_err());
+    }
+
+    #[test]
+    fn contains_matches_mapped_ipv4() {
+        let private = net("10.0.0.0/8");
+        assert!(private.contains("10.2.3.4".parse().unwrap()));
+        assert!(private.contains("::ffff:10.2.3.4".parse().unwrap()));
+        assert!(!private.contains("11.0.0.1".parse().unwrap()));
+        assert!(!net("fc00::/7").contains("10.2.3.4".parse().unwrap()));
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let nets: Vec<IpNetwork> = serde_json::from_str(r#"["127.0.0.1", "fc00::/7"]"#).unwrap();
+        assert_eq!(
+            serde_json::to_string(&nets).unwrap(),
+            r#"["127.0.0.1/32","fc00::/7"]"#
+        );
+        assert!(serde_json::from_str::<IpNetwork>(r#""nope""#).is_err());
+    }
+}
@@ -0,0 +1,145 @@
+//! IP allow/deny rules.
+//!
+//! The HTTP and gRPC crates check the resolved client IP against an
+//! [`IpFilter`]. Its rules can be replaced while the server is running, e.g.
+//! after re-reading the config file on SIGHUP.
+
+use crate::IpNetwork;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::sync::{Arc, RwLock};
+
+/// IP filter rules.
+///
+/// Loaded from the `ip_filter` section of the config file:
+///
+/// ```toml
+/// [ip_filter]
+/// allow = ["203.0.113.0/24", "10.8.0.0/16"]
+/// deny = ["10.8.99.0/24"]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IpFilterConfig {
+    /// Networks allowed through. Empty allows every address not denied.
+    pub allow: Vec<IpNetwork>,
+    /// Networks always rejected, even if they are also allowed.
+    pub deny: Vec<IpNetwork>,
+}
+
+impl IpFilterConfig {
+    /// Returns `true` if `ip` passes the rules.
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        !IpNetwork::any_contains(&self.deny, ip)
+            && (self.allow.is_empty() || IpNetwork::any_contains(&self.allow, ip))
+    }
+
+    /// Whether a request without a client IP (e.g. over a Unix socket)
+    /// passes: only when there is no allow list.
+    pub fn allows_unknown(&self) -> bool {
+        self.allow.is_empty()
+    }
+}
+
+/// Shared, reloadable IP filter.
+///
+/// Clones share the rules, so a handle kept after building the layers can
+/// [`reload`](IpFilter::reload) them for every server using the filter.
+///
+/// # Example
+///
+/// ```ignore
+/// let filter = IpFilter::new(config.ip_filter.clone());
+/// let admin = admin_routes().layer(IpFilterLayer::new(filter.clone()));
+///
+/// // later, after re-reading the config
+/// filter.reload(new_config.ip_filter);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct IpFilter {
+    rules: Arc<RwLock<Arc<IpFilterConfig>>>,
+}
+
+impl IpFilter {
+    pub fn new(config: IpFilterConfig) -> Self {
+        Self {
+            rules: Arc::new(RwLock::new(Arc::new(config))),
+        }
+    }
+
+    /// The current rules.
+    pub fn rules(&self) -> Arc<IpFilterConfig> {
+        Arc::clone(&self.rules.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Replace the rules. Requests already being checked use the old rules.
+    pub fn reload(&self, config: IpFilterConfig) {
+        tracing::info!(
+            allow = config.allow.len(),
+            deny = config.deny.len(),
+            "Reloaded IP filter rules"
+        );
+        *self.rules.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(config);
+    }
+
+    /// Check a client IP; `None` when the client address is unknown.
+    pub fn check(&self, ip: Option<IpAddr>) -> bool {
+        let rules = self.rules();
+        match ip {
+            Some(ip) => rules.is_allowed(ip),
+            None => rules.allows_unknown(),
+        }
+    }
+}
+
+impl From<IpFilterConfig> for IpFilter {
+    fn from(config: IpFilterConfig) -> Self {
+        Self::new(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(allow: &[&str], deny: &[&str]) -> IpFilterConfig {
+        let nets = |list: &[&str]| list.iter().map(|net| net.parse().unwrap()).collect();
+        IpFilterConfig {
+            allow: nets(allow),
+            deny: nets(deny),
+        }
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn deny_wins_over_allow() {
+        let rules = rules(&["10.8.0.0/16"], &["10.8.99.0/24"]);
+        assert!(rules.is_allowed(ip("10.8.1.1")));
+        assert!(!rules.is_allowed(ip("10.8.99.1")));
+        assert!(!rules.is_allowed(ip("192.168.1.1")));
+        assert!(!rules.allows_unknown());
+    }
+
+    #[test]
+    fn empty_allow_list_allows_everything_not_denied() {
+        let rules = rules(&[], &["203.0.113.0/24"]);
+        assert!(rules.is_allowed(ip("198.51.100.1")));
+        assert!(!rules.is_allowed(ip("203.0.113.9")));
+        assert!(IpFilterConfig::default().is_allowed(ip("::1")));
+    }
+
+    #[test]
+    fn reload_applies_to_clones() {
+        let filter = IpFilter::new(rules(&["10.0.0.0/8"], &[]));
+        let clone = filter.clone();
+        assert!(!clone.check(Some(ip("192.168.1.1"))));
+        assert!(!clone.check(None));
+
+        filter.reload(rules(&["192.168.0.0/16"], &[]));
+        assert!(clone.check(Some(ip("192.168.1.1"))));
+        assert!(!clone.check(Some(ip("10.0.0.1"))));
+    }
+}
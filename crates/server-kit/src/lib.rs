@@ -2,13 +2,71 @@
 //!
 //! Shared utilities for `server-kit-rest` and `server-kit-grpc`.
 
+mod access_log;
+mod authz;
+pub mod build;
+mod build_info;
+mod client_ip;
+mod concurrency;
 mod config;
+mod deadline;
+mod drain;
 mod environment;
+mod hot_restart;
+mod ip;
+mod ip_filter;
+mod listener;
 mod logging;
+mod panic;
+mod proxy_protocol;
+mod rate_limit;
+mod secret;
+mod shutdown;
+mod systemd;
+mod token_cache;
 
+pub use access_log::{
+    AccessLog, AccessLogBody, AccessLogConfig, AccessLogFormat, AccessLogOutput, AccessLogRecord,
+    ACCESS_LOG_TARGET,
+};
+pub use authz::{AuthorizationConfig, Permissions, Policy, PolicyMap, RoutePolicy};
+pub use build_info::{log_build_info, BuildInfo};
+pub use client_ip::{ForwardedHeader, TrustedProxies};
+pub use concurrency::{
+    AdaptiveConcurrencyConfig, ConcurrencyConfig, ConcurrencyError, ConcurrencyLimiter,
+    ConcurrencyLimits, ConcurrencyPermit, ConcurrencyStats, RouteConcurrency,
+};
 pub use config::{ConfigBuilder, ConfigError, ConfigFormat};
+pub use deadline::{Deadline, DeadlineExceeded, RequestTimeouts, RouteTimeout};
+pub use drain::{Drain, InFlight, InFlightBody, InFlightGuard};
 pub use environment::Environment;
+#[cfg(unix)]
+pub use hot_restart::hot_restart;
+pub use hot_restart::{register_for_restart, restart_ready, RestartRegistration};
+pub use ip::{InvalidIpNetwork, IpNetwork};
+pub use ip_filter::{IpFilter, IpFilterConfig};
+pub use listener::{ListenAddr, Listener, LocalAddr, ServerHandle, UNIX_SCHEME};
 pub use logging::LogFormat;
+pub use panic::{install_panic_hook, payload_message, CatchPanic, PanicHook, PanicReport};
+pub use proxy_protocol::{ProxiedAddr, ProxyProtocolAcceptor, ProxyProtocolConfig};
+pub use rate_limit::{
+    KeyExtractor, RateLimitConfig, RateLimitKey, RateLimitQuota, RateLimitRequest,
+    RateLimitStatus, RouteRateLimit, Subject,
+};
+#[cfg(feature = "ratelimit")]
+pub use rate_limit::{
+    reload_rate_limits, BackendFuture, KeyedRateLimiter, MemoryBackend, RateLimitBackend,
+    RateLimitBackendError,
+};
+#[cfg(feature = "redis")]
+pub use rate_limit::RedisBackend;
+pub use secret::Secret;
+pub use shutdown::{
+    is_shutdown_requested, request_shutdown, shutdown_requested, ShutdownController, ShutdownSignal,
+    ShutdownToken,
+};
+pub use systemd::{SdNotify, WatchdogGuard};
+pub use token_cache::TokenCache;
 
 #[cfg(feature = "tracing")]
 pub use logging::{init_logging, init_logging_from_env};
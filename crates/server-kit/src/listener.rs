@@ -0,0 +1,336 @@
+//! Listening sockets.
+//!
+//! [`ListenAddr`] is what servers are configured with: either `host:port` or
+//! `unix:///path/to/socket`. [`Listener`] is a bound socket of either kind,
+//! and [`ServerHandle`] is returned by the `spawn` entry points so callers
+//! (mostly tests) can learn the address the server actually bound.
+
+use std::fmt;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use crate::ShutdownController;
+
+/// Scheme prefix for Unix domain socket addresses.
+pub const UNIX_SCHEME: &str = "unix://";
+
+/// Address a server listens on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddr {
+    /// `host:port`, resolved when binding.
+    Tcp(String),
+    /// Path of a Unix domain socket.
+    Unix(PathBuf),
+}
+
+impl ListenAddr {
+    /// Parse `unix:///path` as a Unix socket and anything else as `host:port`.
+    pub fn parse(addr: &str) -> Self {
+        match addr.strip_prefix(UNIX_SCHEME) {
+            Some(path) => Self::Unix(PathBuf::from(path)),
+            None => Self::Tcp(addr.to_string()),
+        }
+    }
+
+    /// Build an address from a config `host` and `port`.
+    ///
+    /// A `unix://` host selects a Unix socket and the port is ignored.
+    pub fn from_host_port(host: &str, port: u16) -> Self {
+        match host.strip_prefix(UNIX_SCHEME) {
+            Some(path) => Self::Unix(PathBuf::from(path)),
+            None => Self::Tcp(format!("{}:{}", host, port)),
+        }
+    }
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => f.write_str(addr),
+            Self::Unix(path) => write!(f, "{}{}", UNIX_SCHEME, path.display()),
+        }
+    }
+}
+
+/// Address a [`Listener`] is bound to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LocalAddr {
+    Tcp(SocketAddr),
+    /// Socket path; `None` for unnamed sockets.
+    Unix(Option<PathBuf>),
+}
+
+impl LocalAddr {
+    /// The TCP address, if this is a TCP listener.
+    pub fn as_tcp(&self) -> Option<SocketAddr> {
+        match self {
+            Self::Tcp(addr) => Some(*addr),
+            Self::Unix(_) => None,
+        }
+    }
+
+    /// The socket path, if this is a named Unix socket.
+    pub fn as_unix(&self) -> Option<&Path> {
+        match self {
+            Self::Tcp(_) => None,
+            Self::Unix(path) => path.as_deref(),
+        }
+    }
+}
+
+impl fmt::Display for LocalAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tcp(addr) => write!(f, "{}", addr),
+            Self::Unix(Some(path)) => write!(f, "{}{}", UNIX_SCHEME, path.display()),
+            Self::Unix(None) => write!(f, "{}(unnamed)", UNIX_SCHEME),
+        }
+    }
+}
+
+/// A bound TCP or Unix domain socket listener.
+#[derive(Debug)]
+pub enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(tokio::net::UnixListener),
+}
+
+impl Listener {
+    /// Bind `addr`.
+    ///
+    /// A stale socket file left behind by a previous process is removed
+    /// before binding a Unix socket; one a server is still listening on
+    /// fails with [`io::ErrorKind::AddrInUse`]. Unix sockets are not
+    /// supported on other platforms.
+    pub async fn bind(addr: &ListenAddr) -> io::Result<Self> {
+        match addr {
+            ListenAddr::Tcp(addr) => TcpListener::bind(addr.as_str()).await.map(Self::Tcp),
+            #[cfg(unix)]
+            ListenAddr::Unix(path) => {
+                remove_stale_socket(path)?;
+                tokio::net::UnixListener::bind(path).map(Self::Unix)
+            }
+            #[cfg(not(unix))]
+            ListenAddr::Unix(_) => Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Unix domain sockets are not supported on this platform",
+            )),
+        }
+    }
+
+    /// Use an inherited socket if there is one, otherwise bind `addr`.
+    ///
+    /// Sockets handed over by a hot restart for the same `addr` come first,
+    /// then sockets passed by systemd socket activation (see
+    /// [`Listener::from_systemd`] for how `name` selects one).
+    pub async fn bind_or_inherit(addr: &ListenAddr, name: Option<&str>) -> io::Result<Self> {
+        #[cfg(unix)]
+        if let Some(listener) = crate::hot_restart::take_inherited(&addr.to_string())? {
+            tracing::info!(addr = %addr, "Using socket handed over by previous process");
+            return Ok(listener);
+        }
+        #[cfg(unix)]
+        if let Some(listener) = Self::from_systemd(name)? {
+            tracing::info!(name = name.unwrap_or("-"), "Using socket passed by systemd");
+            return Ok(listener);
+        }
+        #[cfg(not(unix))]
+        let _ = name;
+        Self::bind(addr).await
+    }
+
+    /// Address the listener is bound to.
+    pub fn local_addr(&self) -> io::Result<LocalAddr> {
+        match self {
+            Self::Tcp(listener) => listener.local_addr().map(LocalAddr::Tcp),
+            #[cfg(unix)]
+            Self::Unix(listener) => listener
+                .local_addr()
+                .map(|addr| LocalAddr::Unix(addr.as_pathname().map(Path::to_path_buf))),
+        }
+    }
+}
+
+#[cfg(unix)]
+impl std::os::fd::AsFd for Listener {
+    fn as_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+        match self {
+            Self::Tcp(listener) => listener.as_fd(),
+            Self::Unix(listener) => listener.as_fd(),
+        }
+    }
+}
+
+impl From<TcpListener> for Listener {
+    fn from(listener: TcpListener) -> Self {
+        Self::Tcp(listener)
+    }
+}
+
+#[cfg(unix)]
+impl From<tokio::net::UnixListener> for Listener {
+    fn from(listener: tokio::net::UnixListener) -> Self {
+        Self::Unix(listener)
+    }
+}
+
+/// Remove the socket file at `path` if nothing is listening on it anymore.
+#[cfg(unix)]
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.file_type().is_socket() => {}
+        _ => return Ok(()),
+    }
+    match std::os::unix::net::UnixStream::connect(path) {
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            format!("{} is in use by another server", path.display()),
+        )),
+        Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => {
+            match std::fs::remove_file(path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+                _ => Ok(()),
+            }
+        }
+        // Gone in the meantime, or not ours to judge; binding reports it.
+        Err(_) => Ok(()),
+    }
+}
+
+/// Handle to a server running in a background task.
+///
+/// # Example
+///
+/// ```ignore
+/// let config = ServerConfig { port: 0, ..Default::default() };
+/// let server = router.spawn(&config).await?;
+///
+/// let url = format!("http://{}/health", server.local_addr());
+/// // ... exercise the server ...
+///
+/// server.stop().await?;
+/// ```
+#[derive(Debug)]
+pub struct ServerHandle<E> {
+    local_addr: LocalAddr,
+    shutdown: ShutdownController,
+    task: JoinHandle<Result<(), E>>,
+}
+
+impl<E> ServerHandle<E> {
+    pub fn new(
+        local_addr: LocalAddr,
+        shutdown: ShutdownController,
+        task: JoinHandle<Result<(), E>>,
+    ) -> Self {
+        Self {
+            local_addr,
+            shutdown,
+            task,
+        }
+    }
+
+    /// Address the server is bound to.
+    pub fn local_addr(&self) -> &LocalAddr {
+        &self.local_addr
+    }
+
+    /// The controller that stops this server.
+    pub fn shutdown_controller(&self) -> &ShutdownController {
+        &self.shutdown
+    }
+
+    /// Start a graceful shutdown without waiting for it.
+    pub fn shutdown(&self) {
+        self.shutdown.shutdown();
+    }
+
+    /// Wait for the server to exit.
+    ///
+    /// A panic in the server task is resumed on the caller.
+    pub async fn join(self) -> Result<(), E> {
+        match self.task.await {
+            Ok(result) => result,
+            Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+            // Cancelled because the runtime is shutting down.
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Shut down gracefully and wait for the server to exit.
+    pub async fn stop(self) -> Result<(), E> {
+        self.shutdown();
+        self.join().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_listen_addr() {
+        assert_eq!(
+            ListenAddr::parse("127.0.0.1:8080"),
+            ListenAddr::Tcp("127.0.0.1:8080".to_string())
+        );
+        assert_eq!(
+            ListenAddr::parse("unix:///run/app.sock"),
+            ListenAddr::Unix(PathBuf::from("/run/app.sock"))
+        );
+        assert_eq!(
+            ListenAddr::from_host_port("unix:///run/app.sock", 3000).to_string(),
+            "unix:///run/app.sock"
+        );
+        assert_eq!(
+            ListenAddr::from_host_port("[::1]", 50051).to_string(),
+            "[::1]:50051"
+        );
+    }
+
+    #[tokio::test]
+    async fn tcp_reports_bound_port() {
+        let listener = Listener::bind(&ListenAddr::parse("127.0.0.1:0"))
+            .await
+            .unwrap();
+        let addr = listener.local_addr().unwrap().as_tcp().unwrap();
+        assert_ne!(addr.port(), 0);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unix_replaces_stale_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.sock");
+        let addr = ListenAddr::Unix(path.clone());
+
+        let first = Listener::bind(&addr).await.unwrap();
+        drop(first);
+        assert!(path.exists());
+
+        let listener = Listener::bind(&addr).await.unwrap();
+        assert_eq!(
+            listener.local_addr().unwrap().as_unix(),
+            Some(path.as_path())
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn unix_keeps_socket_in_use() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.sock");
+        let addr = ListenAddr::Unix(path.clone());
+
+        let _first = Listener::bind(&addr).await.unwrap();
+        let err = Listener::bind(&addr).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AddrInUse);
+        std::os::unix::net::UnixStream::connect(&path).unwrap();
+    }
+}
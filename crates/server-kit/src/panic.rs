@@ -0,0 +1,361 @@
+//! Panic capture and the process-wide panic hook.
+//!
+//! [`CatchPanic`] wraps a future and turns a panic during `poll` into a
+//! [`PanicReport`] carrying the message, source location and backtrace.
+//! Location and backtrace are only available inside the panic hook, so a
+//! hook is installed on first use; it records the report for the catching
+//! future and forwards all other panics to the previously installed hook.
+//!
+//! [`PanicHook`] replaces that forwarding with structured tracing events,
+//! optional crash report files and an optional graceful shutdown.
+
+use std::any::Any;
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::io;
+use std::panic::{self, AssertUnwindSafe, PanicHookInfo};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+use std::task::{Context, Poll};
+use std::time::SystemTime;
+
+use crate::access_log::format_iso8601;
+use crate::shutdown::request_shutdown;
+
+thread_local! {
+    static CATCH_DEPTH: Cell<usize> = const { Cell::new(0) };
+    static LAST_PANIC: RefCell<Option<PanicReport>> = const { RefCell::new(None) };
+}
+
+static INSTALL_CAPTURE_HOOK: Once = Once::new();
+
+/// Details of a caught panic.
+#[derive(Debug, Clone)]
+pub struct PanicReport {
+    /// Panic message (`&str` or `String` payloads, otherwise a placeholder).
+    pub message: String,
+    /// Source location as `file:line:column`.
+    pub location: Option<String>,
+    /// Name of the panicking thread.
+    pub thread: Option<String>,
+    /// Captured backtrace.
+    pub backtrace: Option<String>,
+}
+
+impl PanicReport {
+    pub(crate) fn from_hook(info: &PanicHookInfo<'_>) -> Self {
+        Self {
+            message: payload_message(info.payload()),
+            location: info
+                .location()
+                .map(|l| format!("{}:{}:{}", l.file(), l.line(), l.column())),
+            thread: std::thread::current().name().map(str::to_string),
+            backtrace: Some(Backtrace::force_capture().to_string()),
+        }
+    }
+
+    fn from_payload(payload: &(dyn Any + Send)) -> Self {
+        Self {
+            message: payload_message(payload),
+            location: None,
+            thread: std::thread::current().name().map(str::to_string),
+            backtrace: None,
+        }
+    }
+}
+
+impl fmt::Display for PanicReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "panicked at {}: {}", location, self.message),
+            None => write!(f, "panicked: {}", self.message),
+        }
+    }
+}
+
+/// Extract the message from a panic payload.
+pub fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(s) = payload.downcast_ref::<&str>() {
+        (*s).to_string()
+    } else if let Some(s) = payload.downcast_ref::<String>() {
+        s.clone()
+    } else {
+        "Box<dyn Any>".to_string()
+    }
+}
+
+/// Returns `true` if the current thread is polling a [`CatchPanic`] future.
+pub(crate) fn is_catching() -> bool {
+    CATCH_DEPTH.with(|d| d.get() > 0)
+}
+
+/// Store a report for the [`CatchPanic`] future currently being polled.
+pub(crate) fn record_caught(report: PanicReport) {
+    LAST_PANIC.with(|last| *last.borrow_mut() = Some(report));
+}
+
+fn install_capture_hook() {
+    INSTALL_CAPTURE_HOOK.call_once(|| {
+        let previous = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if is_catching() {
+                record_caught(PanicReport::from_hook(info));
+            } else {
+                previous(info);
+            }
+        }));
+    });
+}
+
+/// Builder for the process-wide panic hook.
+///
+/// Panics outside [`CatchPanic`] (background tasks, worker threads, `main`)
+/// are logged as ERROR events with message, location, thread name and
+/// backtrace instead of unstructured text on stderr. Panics inside
+/// [`CatchPanic`] are left to the request-level handler.
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit::PanicHook;
+///
+/// PanicHook::new()
+///     .crash_report_dir("/var/log/app/crashes")
+///     .shutdown_on_panic(true)
+///     .install();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct PanicHook {
+    crash_report_dir: Option<PathBuf>,
+    shutdown_on_panic: bool,
+}
+
+impl PanicHook {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Write a crash report file into `dir` for every uncaught panic.
+    pub fn crash_report_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.crash_report_dir = Some(dir.into());
+        self
+    }
+
+    /// Request a graceful shutdown of all servers after an uncaught panic.
+    pub fn shutdown_on_panic(mut self, enabled: bool) -> Self {
+        self.shutdown_on_panic = enabled;
+        self
+    }
+
+    /// Install the hook, replacing any previously installed hook.
+    pub fn install(self) {
+        // Our hook handles `CatchPanic` itself, so the capture hook must not
+        // be installed on top of it later.
+        INSTALL_CAPTURE_HOOK.call_once(|| {});
+        panic::set_hook(Box::new(move |info| {
+            if is_catching() {
+                record_caught(PanicReport::from_hook(info));
+            } else {
+                self.handle(&PanicReport::from_hook(info));
+            }
+        }));
+    }
+
+    fn handle(&self, report: &PanicReport) {
+        tracing::error!(
+            panic.message = %report.message,
+            panic.location = report.location.as_deref().unwrap_or("-"),
+            panic.thread = report.thread.as_deref().unwrap_or("-"),
+            panic.backtrace = report.backtrace.as_deref().unwrap_or("-"),
+            "uncaught panic"
+        );
+        if !tracing::dispatcher::has_been_set() {
+            eprintln!("{} (thread: {})", report, report.thread.as_deref().unwrap_or("-"));
+        }
+
+        if let Some(dir) = &self.crash_report_dir {
+            match write_crash_report(dir, report) {
+                Ok(path) => tracing::error!(path = %path.display(), "crash report written"),
+                Err(e) => tracing::error!(error = %e, "failed to write crash report"),
+            }
+        }
+
+        if self.shutdown_on_panic {
+            tracing::error!("requesting graceful shutdown after panic");
+            request_shutdown();
+        }
+    }
+}
+
+/// Install the process-wide panic hook with default settings.
+///
+/// See [`PanicHook`] for crash reports and shutdown on panic.
+pub fn install_panic_hook() {
+    PanicHook::new().install();
+}
+
+/// Crash reports written by this process, so reports within the same second
+/// get distinct file names.
+static CRASH_REPORTS: AtomicU64 = AtomicU64::new(0);
+
+fn write_crash_report(dir: &Path, report: &PanicReport) -> io::Result<PathBuf> {
+    let now = SystemTime::now();
+    let secs = now
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let pid = std::process::id();
+    let seq = CRASH_REPORTS.fetch_add(1, Ordering::Relaxed);
+
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("crash-{}-{}-{}.txt", secs, pid, seq));
+    let contents = format!(
+        "time: {}\npid: {}\nthread: {}\nlocation: {}\nmessage: {}\n\nbacktrace:\n{}\n",
+        format_iso8601(now),
+        pid,
+        report.thread.as_deref().unwrap_or("-"),
+        report.location.as_deref().unwrap_or("-"),
+        report.message,
+        report.backtrace.as_deref().unwrap_or("-"),
+    );
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+/// Future wrapper that catches panics raised while polling the inner future.
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit::CatchPanic;
+///
+/// match CatchPanic::new(handler(req)).await {
+///     Ok(response) => response,
+///     Err(report) => {
+///         tracing::error!(message = %report.message, "handler panicked");
+///         internal_error()
+///     }
+/// }
+/// ```
+pub struct CatchPanic<F> {
+    inner: Pin<Box<F>>,
+}
+
+impl<F: Future> CatchPanic<F> {
+    pub fn new(inner: F) -> Self {
+        install_capture_hook();
+        Self {
+            inner: Box::pin(inner),
+        }
+    }
+}
+
+impl<F: Future> Future for CatchPanic<F> {
+    type Output = Result<F::Output, PanicReport>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        CATCH_DEPTH.with(|d| d.set(d.get() + 1));
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.inner.as_mut().poll(cx)));
+        CATCH_DEPTH.with(|d| d.set(d.get() - 1));
+
+        match result {
+            Ok(Poll::Ready(output)) => Poll::Ready(Ok(output)),
+            Ok(Poll::Pending) => Poll::Pending,
+            Err(payload) => {
+                let report = LAST_PANIC
+                    .with(|last| last.borrow_mut().take())
+                    .unwrap_or_else(|| PanicReport::from_payload(payload.as_ref()));
+                Poll::Ready(Err(report))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn payload_message_handles_common_types() {
+        let payload: Box<dyn Any + Send> = Box::new("static str");
+        assert_eq!(payload_message(payload.as_ref()), "static str");
+
+        let payload: Box<dyn Any + Send> = Box::new(String::from("owned"));
+        assert_eq!(payload_message(payload.as_ref()), "owned");
+
+        let payload: Box<dyn Any + Send> = Box::new(42);
+        assert_eq!(payload_message(payload.as_ref()), "Box<dyn Any>");
+    }
+
+    #[tokio::test]
+    async fn catch_panic_passes_through_output() {
+        let result = CatchPanic::new(async { 7 }).await;
+        assert_eq!(result.unwrap(), 7);
+    }
+
+    #[tokio::test]
+    async fn catch_panic_reports_message_and_location() {
+        let result = CatchPanic::new(async {
+            panic!("boom {}", 1);
+        })
+        .await;
+
+        let report: PanicReport = result.unwrap_err();
+        assert_eq!(report.message, "boom 1");
+        assert!(report.location.unwrap().contains("panic.rs"));
+        assert!(report.backtrace.is_some());
+        assert!(!is_catching());
+    }
+
+    #[test]
+    fn hook_writes_crash_report() {
+        let dir = tempfile::tempdir().unwrap();
+        let hook = PanicHook::new()
+            .crash_report_dir(dir.path().join("crashes"))
+            // Requesting shutdown is process-wide and would end other tests'
+            // `ShutdownController::wait`.
+            .shutdown_on_panic(false);
+
+        let report = PanicReport {
+            message: "worker died".to_string(),
+            location: Some("src/worker.rs:10:5".to_string()),
+            thread: Some("worker-1".to_string()),
+            backtrace: Some("0: worker::run".to_string()),
+        };
+        hook.handle(&report);
+
+        let entry = std::fs::read_dir(dir.path().join("crashes"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let name = entry.file_name().into_string().unwrap();
+        assert!(name.starts_with("crash-"));
+
+        let contents = std::fs::read_to_string(entry.path()).unwrap();
+        assert!(contents.contains("thread: worker-1"));
+        assert!(contents.contains("location: src/worker.rs:10:5"));
+        assert!(contents.contains("message: worker died"));
+        assert!(contents.contains("0: worker::run"));
+    }
+    #[test]
+    fn crash_reports_in_the_same_second_are_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let report = PanicReport {
+            message: "worker died".to_string(),
+            location: None,
+            thread: None,
+            backtrace: None,
+        };
+
+        let first = write_crash_report(dir.path(), &report).unwrap();
+        let second = write_crash_report(dir.path(), &report).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
@@ -0,0 +1,435 @@
+//! PROXY protocol (v1 and v2) support for TCP listeners.
+//!
+//! Load balancers that terminate TCP (HAProxy, AWS NLB, ...) can prepend a
+//! PROXY header carrying the original client address to each connection.
+//! [`ProxyProtocolAcceptor`] accepts connections only from trusted sources,
+//! strips the header and hands out the stream together with a
+//! [`ProxiedAddr`].
+
+use crate::IpNetwork;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+/// Longest possible v1 header, including the trailing CRLF.
+const V1_MAX_LEN: usize = 107;
+/// Connections with a parsed header waiting to be picked up by the server.
+const ACCEPT_BACKLOG: usize = 64;
+
+/// PROXY protocol settings.
+///
+/// Loaded from the `proxy_protocol` section of the config file, or from
+/// `PROXY_PROTOCOL__*` environment variables.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ProxyProtocolConfig {
+    /// Require a PROXY header on every TCP connection.
+    pub enabled: bool,
+    /// Peers allowed to connect and send a PROXY header. Connections from
+    /// anywhere else are closed. Defaults to loopback and private ranges;
+    /// an empty list rejects every connection.
+    pub trusted_sources: Vec<IpNetwork>,
+    /// How long a client has to send the header after connecting.
+    pub header_timeout_secs: u64,
+}
+
+impl Default for ProxyProtocolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            trusted_sources: [
+                "127.0.0.0/8",
+                "10.0.0.0/8",
+                "172.16.0.0/12",
+                "192.168.0.0/16",
+                "::1/128",
+                "fc00::/7",
+            ]
+            .iter()
+            .map(|net| net.parse().expect("valid network"))
+            .collect(),
+            header_timeout_secs: 5,
+        }
+    }
+}
+
+impl ProxyProtocolConfig {
+    pub fn header_timeout(&self) -> Duration {
+        Duration::from_secs(self.header_timeout_secs)
+    }
+
+    /// Returns `true` if `peer` may connect.
+    pub fn is_trusted(&self, peer: IpAddr) -> bool {
+        IpNetwork::any_contains(&self.trusted_sources, peer)
+    }
+}
+
+/// Addresses of a connection accepted through a PROXY protocol listener.
+///
+/// Available to axum handlers as `ConnectInfo<ProxiedAddr>`; the gRPC crate
+/// exposes it through `RequestExt`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProxiedAddr {
+    /// The proxy's address, as seen by the socket.
+    pub peer: SocketAddr,
+    /// The original client address. `None` for health checks sent by the
+    /// proxy itself (`LOCAL`/`UNKNOWN`) and non-IP sources.
+    pub source: Option<SocketAddr>,
+    /// The address the client connected to on the proxy.
+    pub destination: Option<SocketAddr>,
+}
+
+impl ProxiedAddr {
+    /// The original client address, falling back to the proxy's.
+    pub fn client(&self) -> SocketAddr {
+        self.source.unwrap_or(self.peer)
+    }
+}
+
+/// Addresses carried by a PROXY header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+struct ProxyHeader {
+    source: Option<SocketAddr>,
+    destination: Option<SocketAddr>,
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+/// Read a v1 or v2 PROXY header, leaving the stream at the first byte after it.
+async fn read_proxy_header<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<ProxyHeader> {
+    // Both versions are at least 12 bytes long ("PROXY UNKNOWN\r\n" is 15).
+    let mut start = [0u8; 12];
+    stream.read_exact(&mut start).await?;
+
+    if &start == V2_SIGNATURE {
+        read_v2(stream).await
+    } else if start.starts_with(b"PROXY ") {
+        read_v1(stream, &start).await
+    } else {
+        Err(invalid("missing PROXY protocol header"))
+    }
+}
+
+async fn read_v1<S: AsyncRead + Unpin>(stream: &mut S, start: &[u8]) -> io::Result<ProxyHeader> {
+    // The application data follows directly, so read byte by byte up to LF.
+    let mut line = start.to_vec();
+    while !line.ends_with(b"\r\n") {
+        if line.len() >= V1_MAX_LEN {
+            return Err(invalid("PROXY v1 header too long"));
+        }
+        line.push(stream.read_u8().await?);
+    }
+    let line = std::str::from_utf8(&line[..line.len() - 2])
+        .map_err(|_| invalid("PROXY v1 header is not ASCII"))?;
+
+    let mut parts = line.split(' ').skip(1);
+    match parts.next() {
+        Some("UNKNOWN") => Ok(ProxyHeader::default()),
+        Some("TCP4") | Some("TCP6") => {
+            let mut next = || {
+                parts
+                    .next()
+                    .ok_or_else(|| invalid("truncated PROXY v1 header"))
+            };
+            let (src, dst, src_port, dst_port) = (next()?, next()?, next()?, next()?);
+            let ip = |s: &str| {
+                s.parse::<IpAddr>()
+                    .map_err(|_| invalid("invalid PROXY v1 address"))
+            };
+            let port = |s: &str| {
+                s.parse::<u16>()
+                    .map_err(|_| invalid("invalid PROXY v1 port"))
+            };
+            Ok(ProxyHeader {
+                source: Some(SocketAddr::new(ip(src)?, port(src_port)?)),
+                destination: Some(SocketAddr::new(ip(dst)?, port(dst_port)?)),
+            })
+        }
+        _ => Err(invalid("unsupported PROXY v1 protocol")),
+    }
+}
+
+async fn read_v2<S: AsyncRead + Unpin>(stream: &mut S) -> io::Result<ProxyHeader> {
+    let mut fixed = [0u8; 4];
+    stream.read_exact(&mut fixed).await?;
+    let [version_command, family, len_hi, len_lo] = fixed;
+
+    if version_command >> 4 != 2 {
+        return Err(invalid("unsupported PROXY protocol version"));
+    }
+    let mut payload = vec![0u8; u16::from_be_bytes([len_hi, len_lo]) as usize];
+    stream.read_exact(&mut payload).await?;
+
+    match version_command & 0x0f {
+        // LOCAL: the proxy's own connection, e.g. a health check.
+        0x0 => return Ok(ProxyHeader::default()),
+        0x1 => {}
+        _ => return Err(invalid("unsupported PROXY v2 command")),
+    }
+
+    let port = |bytes: &[u8]| u16::from_be_bytes([bytes[0], bytes[1]]);
+    match family >> 4 {
+        // AF_INET
+        0x1 => {
+            let addrs = payload
+                .get(..12)
+                .ok_or_else(|| invalid("truncated PROXY v2 address"))?;
+            let src = Ipv4Addr::from(<[u8; 4]>::try_from(&addrs[0..4]).unwrap());
+            let dst = Ipv4Addr::from(<[u8; 4]>::try_from(&addrs[4..8]).unwrap());
+            Ok(ProxyHeader {
+                source: Some(SocketAddr::new(src.into(), port(&addrs[8..10]))),
+                destination: Some(SocketAddr::new(dst.into(), port(&addrs[10..12]))),
+            })
+        }
+        // AF_INET6
+        0x2 => {
+            let addrs = payload
+                .get(..36)
+                .ok_or_else(|| invalid("truncated PROXY v2 address"))?;
+            let src = Ipv6Addr::from(<[u8; 16]>::try_from(&addrs[0..16]).unwrap());
+            let dst = Ipv6Addr::from(<[u8; 16]>::try_from(&addrs[16..32]).unwrap());
+            Ok(ProxyHeader {
+                source: Some(SocketAddr::new(src.into(), port(&addrs[32..34]))),
+                destination: Some(SocketAddr::new(dst.into(), port(&addrs[34..36]))),
+            })
+        }
+        // AF_UNSPEC and AF_UNIX carry no IP address.
+        _ => Ok(ProxyHeader::default()),
+    }
+}
+
+/// Accepts TCP connections that start with a PROXY header.
+///
+/// Connections from peers outside `trusted_sources`, without a valid header
+/// or that don't send one within `header_timeout_secs` are closed. Headers
+/// are read in the background so a slow client can't hold up other
+/// connections.
+#[derive(Debug)]
+pub struct ProxyProtocolAcceptor {
+    local_addr: SocketAddr,
+    rx: mpsc::Receiver<(TcpStream, ProxiedAddr)>,
+    task: JoinHandle<()>,
+}
+
+impl ProxyProtocolAcceptor {
+    /// Start accepting on `listener`. Must be called within a Tokio runtime.
+    pub fn new(listener: TcpListener, config: &ProxyProtocolConfig) -> io::Result<Self> {
+        let local_addr = listener.local_addr()?;
+        let (tx, rx) = mpsc::channel(ACCEPT_BACKLOG);
+        let task = tokio::spawn(accept_loop(listener, config.clone(), tx));
+        Ok(Self {
+            local_addr,
+            rx,
+            task,
+        })
+    }
+
+    /// Address the listener is bound to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Wait for the next connection with a valid header.
+    pub async fn accept(&mut self) -> (TcpStream, ProxiedAddr) {
+        std::future::poll_fn(|cx| self.poll_accept(cx)).await
+    }
+
+    /// Poll for the next connection with a valid header.
+    pub fn poll_accept(&mut self, cx: &mut Context<'_>) -> Poll<(TcpStream, ProxiedAddr)> {
+        match self.rx.poll_recv(cx) {
+            Poll::Ready(Some(conn)) => Poll::Ready(conn),
+            // The accept loop only stops when this acceptor is dropped.
+            Poll::Ready(None) | Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for ProxyProtocolAcceptor {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    config: ProxyProtocolConfig,
+    tx: mpsc::Sender<(TcpStream, ProxiedAddr)>,
+) {
+    loop {
+        let (mut stream, peer) = match listener.accept().await {
+            Ok(conn) => conn,
+            Err(e) => {
+                if !is_connection_error(&e) {
+                    tracing::error!(error = %e, "Failed to accept connection");
+                    tokio::time::sleep(Duration::from_secs(1)).await;
+                }
+                continue;
+            }
+        };
+
+        if !config.is_trusted(peer.ip()) {
+            tracing::warn!(peer = %peer, "Rejected connection from untrusted PROXY protocol source");
+            continue;
+        }
+
+        let tx = tx.clone();
+        let timeout = config.header_timeout();
+        tokio::spawn(async move {
+            let header = match tokio::time::timeout(timeout, read_proxy_header(&mut stream)).await {
+                Ok(Ok(header)) => header,
+                Ok(Err(e)) => {
+                    tracing::warn!(peer = %peer, error = %e, "Invalid PROXY protocol header");
+                    return;
+                }
+                Err(_) => {
+                    tracing::warn!(peer = %peer, "Timed out waiting for PROXY protocol header");
+                    return;
+                }
+            };
+            let addr = ProxiedAddr {
+                peer,
+                source: header.source,
+                destination: header.destination,
+            };
+            let _ = tx.send((stream, addr)).await;
+        });
+    }
+}
+
+fn is_connection_error(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionReset
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    async fn parse(bytes: &[u8]) -> io::Result<(ProxyHeader, Vec<u8>)> {
+        let mut stream = bytes;
+        let header = read_proxy_header(&mut stream).await?;
+        Ok((header, stream.to_vec()))
+    }
+
+    fn v2(command: u8, family: u8, addrs: &[u8]) -> Vec<u8> {
+        let mut bytes = V2_SIGNATURE.to_vec();
+        bytes.extend([0x20 | command, family]);
+        bytes.extend((addrs.len() as u16).to_be_bytes());
+        bytes.extend(addrs);
+        bytes
+    }
+
+    #[tokio::test]
+    async fn parses_v1() {
+        let (header, rest) = parse(b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 443\r\nGET /")
+            .await
+            .unwrap();
+        assert_eq!(header.source, Some("203.0.113.7:51234".parse().unwrap()));
+        assert_eq!(header.destination, Some("10.0.0.1:443".parse().unwrap()));
+        assert_eq!(rest, b"GET /");
+
+        let (header, _) = parse(b"PROXY TCP6 2001:db8::1 2001:db8::2 4000 80\r\n")
+            .await
+            .unwrap();
+        assert_eq!(header.source, Some("[2001:db8::1]:4000".parse().unwrap()));
+
+        let (header, rest) = parse(b"PROXY UNKNOWN\r\nPING").await.unwrap();
+        assert_eq!(header, ProxyHeader::default());
+        assert_eq!(rest, b"PING");
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_v1() {
+        assert!(parse(b"GET / HTTP/1.1\r\n\r\n").await.is_err());
+        assert!(parse(b"PROXY TCP4 203.0.113.7 10.0.0.1\r\n").await.is_err());
+        assert!(parse(b"PROXY TCP4 nope 10.0.0.1 1 2\r\n").await.is_err());
+        assert!(parse(&[b"PROXY TCP4 ".as_slice(), &[b'1'; 200]].concat())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn parses_v2() {
+        let mut addrs = vec![203, 0, 113, 7, 10, 0, 0, 1];
+        addrs.extend(51234u16.to_be_bytes());
+        addrs.extend(443u16.to_be_bytes());
+        // A trailing TLV is skipped.
+        addrs.extend([0x04, 0x00, 0x01, 0xff]);
+        let mut bytes = v2(0x1, 0x11, &addrs);
+        bytes.extend(b"hello");
+
+        let (header, rest) = parse(&bytes).await.unwrap();
+        assert_eq!(header.source, Some("203.0.113.7:51234".parse().unwrap()));
+        assert_eq!(header.destination, Some("10.0.0.1:443".parse().unwrap()));
+        assert_eq!(rest, b"hello");
+
+        let mut addrs = Ipv6Addr::LOCALHOST.octets().to_vec();
+        addrs.extend(Ipv6Addr::UNSPECIFIED.octets());
+        addrs.extend([0, 80, 0, 81]);
+        let (header, _) = parse(&v2(0x1, 0x21, &addrs)).await.unwrap();
+        assert_eq!(header.source, Some("[::1]:80".parse().unwrap()));
+
+        let (header, _) = parse(&v2(0x0, 0x00, &[])).await.unwrap();
+        assert_eq!(header, ProxyHeader::default());
+
+        assert!(parse(&v2(0x1, 0x11, &[1, 2, 3])).await.is_err());
+    }
+
+    fn config(trusted: &[&str]) -> ProxyProtocolConfig {
+        ProxyProtocolConfig {
+            enabled: true,
+            trusted_sources: trusted.iter().map(|net| net.parse().unwrap()).collect(),
+            header_timeout_secs: 5,
+        }
+    }
+
+    #[tokio::test]
+    async fn acceptor_strips_header() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut acceptor = ProxyProtocolAcceptor::new(listener, &config(&["127.0.0.1"])).unwrap();
+
+        let mut client = TcpStream::connect(acceptor.local_addr()).await.unwrap();
+        client
+            .write_all(b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 443\r\nhello")
+            .await
+            .unwrap();
+
+        let (mut stream, addr) = acceptor.accept().await;
+        assert_eq!(addr.client(), "203.0.113.7:51234".parse().unwrap());
+        assert_eq!(addr.peer, client.local_addr().unwrap());
+
+        let mut body = [0u8; 5];
+        stream.read_exact(&mut body).await.unwrap();
+        assert_eq!(&body, b"hello");
+    }
+
+    #[tokio::test]
+    async fn acceptor_closes_untrusted_connections() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut acceptor = ProxyProtocolAcceptor::new(listener, &config(&["10.0.0.0/8"])).unwrap();
+
+        let mut client = TcpStream::connect(acceptor.local_addr()).await.unwrap();
+        let _ = client
+            .write_all(b"PROXY TCP4 203.0.113.7 10.0.0.1 51234 443\r\n")
+            .await;
+        let mut buf = [0u8; 1];
+        assert!(matches!(client.read(&mut buf).await, Ok(0) | Err(_)));
+
+        let accepted = tokio::time::timeout(Duration::from_millis(100), acceptor.accept()).await;
+        assert!(accepted.is_err());
+    }
+}
@@ -0,0 +1,182 @@
+//! Shared rate limit stores.
+//!
+//! Each replica keeps its own governor buckets, so N replicas allow N times
+//! the quota. A [`RateLimitBackend`] moves the bucket state to a store they
+//! all reach. Backends implement GCRA, the algorithm governor uses, so the
+//! limits behave the same with or without one.
+
+use super::{RateLimitQuota, RateLimitStatus};
+use std::collections::HashMap;
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Future returned by [`RateLimitBackend::check`].
+pub type BackendFuture<'a> =
+    Pin<Box<dyn Future<Output = Result<RateLimitStatus, RateLimitBackendError>> + Send + 'a>>;
+
+/// A store holding rate limit state for several server replicas.
+pub trait RateLimitBackend: Send + Sync + 'static {
+    /// Count one request for `key` against `quota`.
+    fn check<'a>(&'a self, key: &'a str, quota: RateLimitQuota) -> BackendFuture<'a>;
+}
+
+impl<T: RateLimitBackend> RateLimitBackend for Arc<T> {
+    fn check<'a>(&'a self, key: &'a str, quota: RateLimitQuota) -> BackendFuture<'a> {
+        (**self).check(key, quota)
+    }
+}
+
+/// Error returned when the rate limit store can't be reached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RateLimitBackendError(String);
+
+impl RateLimitBackendError {
+    pub fn new(message: impl Into<String>) -> Self {
+        Self(message.into())
+    }
+}
+
+impl fmt::Display for RateLimitBackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "rate limit store error: {}", self.0)
+    }
+}
+
+impl std::error::Error for RateLimitBackendError {}
+
+/// GCRA parameters of a quota, in microseconds: the time one request's
+/// worth of quota takes to come back, and how far ahead of the clock the
+/// theoretical arrival time may run.
+pub(crate) fn gcra_params(quota: RateLimitQuota) -> (u64, u64) {
+    let limit = u64::from(quota.requests.max(1));
+    let interval = (quota.period().as_micros() as u64 / limit).max(1);
+    (interval, interval * (limit - 1))
+}
+
+/// Build the status from a GCRA decision. `reset_us` is how far the
+/// theoretical arrival time is ahead of now after the decision.
+pub(crate) fn gcra_status(
+    quota: RateLimitQuota,
+    retry_after_us: Option<u64>,
+    reset_us: u64,
+) -> RateLimitStatus {
+    let (interval, tolerance) = gcra_params(quota);
+    let remaining = match retry_after_us {
+        Some(_) => 0,
+        None => ((tolerance + interval).saturating_sub(reset_us) / interval) as u32,
+    };
+    RateLimitStatus {
+        limit: quota.requests,
+        remaining,
+        reset: Duration::from_micros(reset_us),
+        retry_after: retry_after_us.map(Duration::from_micros),
+    }
+}
+
+/// How often a [`MemoryBackend`] drops keys whose bucket is full again, in
+/// microseconds.
+const SWEEP_INTERVAL_US: u64 = 1_000_000;
+
+/// Rate limit state in process memory.
+///
+/// Only shared by limiters holding the same instance, so it's mainly useful
+/// in tests of code that takes a [`RateLimitBackend`].
+#[derive(Debug)]
+pub struct MemoryBackend {
+    started: Instant,
+    tats: Mutex<Tats>,
+}
+
+#[derive(Debug, Default)]
+struct Tats {
+    // Theoretical arrival time per key, in microseconds since `started`.
+    by_key: HashMap<String, u64>,
+    next_sweep: u64,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            tats: Mutex::new(Tats::default()),
+        }
+    }
+
+    /// Count one request for `key` at `now`, in microseconds since the
+    /// backend was created.
+    pub(crate) fn check_at(&self, key: &str, quota: RateLimitQuota, now: u64) -> RateLimitStatus {
+        let (interval, tolerance) = gcra_params(quota);
+        let mut tats = self.tats.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Keys whose arrival time has passed are back to a full bucket.
+        if now >= tats.next_sweep {
+            tats.by_key.retain(|_, tat| *tat > now);
+            tats.next_sweep = now + SWEEP_INTERVAL_US;
+        }
+
+        let tat = tats.by_key.get(key).copied().unwrap_or(now).max(now);
+        if tat - now > tolerance {
+            return gcra_status(quota, Some(tat - now - tolerance), tat - now);
+        }
+        let new_tat = tat + interval;
+        tats.by_key.insert(key.to_string(), new_tat);
+        gcra_status(quota, None, new_tat - now)
+    }
+}
+
+impl Default for MemoryBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimitBackend for MemoryBackend {
+    fn check<'a>(&'a self, key: &'a str, quota: RateLimitQuota) -> BackendFuture<'a> {
+        let now = self.started.elapsed().as_micros() as u64;
+        let status = self.check_at(key, quota, now);
+        Box::pin(async move { Ok(status) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn memory_backend_matches_local_limits() {
+        let backend = MemoryBackend::new();
+        let quota = RateLimitQuota::per_minute(2);
+
+        let first = backend.check("a", quota).await.unwrap();
+        assert_eq!((first.remaining, first.reset), (1, Duration::from_secs(30)));
+        let second = backend.check("a", quota).await.unwrap();
+        assert_eq!(second.remaining, 0);
+        assert!(second.reset > Duration::from_secs(59) && second.reset <= Duration::from_secs(60));
+
+        let rejected = backend.check("a", quota).await.unwrap();
+        let retry_after = rejected.retry_after.unwrap();
+        assert!(retry_after > Duration::from_secs(29) && retry_after <= Duration::from_secs(30));
+        assert_eq!(rejected.remaining, 0);
+
+        assert!(backend.check("b", quota).await.unwrap().is_allowed());
+    }
+    #[test]
+    fn memory_backend_sweeps_full_buckets_once_per_interval() {
+        let backend = MemoryBackend::new();
+        let quota = RateLimitQuota::new(1, Duration::from_millis(1));
+        let keys = || backend.tats.lock().unwrap().by_key.len();
+
+        for i in 0..100 {
+            backend.check_at(&i.to_string(), quota, 0);
+        }
+        // Those buckets are full again, but the next sweep isn't due yet.
+        backend.check_at("late", quota, SWEEP_INTERVAL_US / 2);
+        assert_eq!(keys(), 101);
+
+        backend.check_at("late", quota, SWEEP_INTERVAL_US);
+        assert_eq!(keys(), 1);
+    }
+}
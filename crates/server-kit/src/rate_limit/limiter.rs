@@ -0,0 +1,703 @@
+//! Governor-backed limiter.
+
+use super::{
+    KeyExtractor, RateLimitBackend, RateLimitConfig, RateLimitQuota, RateLimitRequest,
+    RateLimitStatus,
+};
+use crate::IpNetwork;
+use governor::clock::{Clock, DefaultClock};
+use governor::middleware::StateInformationMiddleware;
+use governor::state::keyed::DefaultKeyedStateStore;
+use governor::{Quota, RateLimiter};
+use std::fmt;
+use std::num::NonZeroU32;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+use std::time::{Duration, Instant};
+
+/// Limiters reached by [`reload_rate_limits`], with the config section they
+/// were registered under.
+static REGISTERED: Mutex<Vec<(String, Weak<KeyedRateLimiter>)>> = Mutex::new(Vec::new());
+
+type Limiter =
+    RateLimiter<String, DefaultKeyedStateStore<String>, DefaultClock, StateInformationMiddleware>;
+
+/// `None` for a quota without requests or without a period, which limits
+/// nothing. Quotas refilling faster than one request per nanosecond are
+/// clamped to that.
+fn to_governor(quota: RateLimitQuota) -> Option<Quota> {
+    let burst = NonZeroU32::new(quota.requests)?;
+    if quota.period().is_zero() {
+        return None;
+    }
+    let per_request = (quota.period() / burst.get()).max(Duration::from_nanos(1));
+    Some(Quota::with_period(per_request)?.allow_burst(burst))
+}
+
+struct Bucket {
+    /// Namespace of the bucket's keys in a backend.
+    name: String,
+    quota: RateLimitQuota,
+    limiter: Limiter,
+}
+
+impl Bucket {
+    /// `None` when `quota` limits nothing, see [`to_governor`].
+    fn new(name: String, quota: RateLimitQuota) -> Option<Self> {
+        Some(Self {
+            name,
+            quota,
+            limiter: RateLimiter::keyed(to_governor(quota)?).with_middleware(),
+        })
+    }
+
+    fn check(&self, key: String) -> RateLimitStatus {
+        let limit = self.quota.requests;
+        // Time for one request's worth of quota to come back.
+        let per_request = self.quota.period() / limit;
+        match self.limiter.check_key(&key) {
+            Ok(snapshot) => {
+                let remaining = snapshot.remaining_burst_capacity();
+                RateLimitStatus {
+                    limit,
+                    remaining,
+                    reset: per_request * (limit - remaining),
+                    retry_after: None,
+                }
+            }
+            Err(not_until) => {
+                let retry_after = not_until.wait_time_from(self.limiter.clock().now());
+                RateLimitStatus {
+                    limit,
+                    remaining: 0,
+                    reset: retry_after + per_request * (limit - 1),
+                    retry_after: Some(retry_after),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct RouteGroup {
+    prefix: String,
+    /// `None` leaves the group's routes unlimited.
+    bucket: Option<Arc<Bucket>>,
+}
+
+#[derive(Clone, Default)]
+struct Rules {
+    default: Option<Arc<Bucket>>,
+    groups: Vec<RouteGroup>,
+    exempt_routes: Vec<String>,
+    exempt_ips: Vec<IpNetwork>,
+}
+
+impl Rules {
+    fn bucket(&self, route: &str) -> Option<&Arc<Bucket>> {
+        match self
+            .groups
+            .iter()
+            .find(|group| route.starts_with(&group.prefix))
+        {
+            Some(group) => group.bucket.as_ref(),
+            None => self.default.as_ref(),
+        }
+    }
+
+    fn buckets(&self) -> impl Iterator<Item = &Arc<Bucket>> {
+        self.default
+            .iter()
+            .chain(self.groups.iter().filter_map(|group| group.bucket.as_ref()))
+    }
+
+    fn is_exempt(&self, req: &RateLimitRequest<'_>) -> bool {
+        self.exempt_routes
+            .iter()
+            .any(|prefix| req.route.starts_with(prefix.as_str()))
+            || req
+                .client_ip
+                .is_some_and(|ip| IpNetwork::any_contains(&self.exempt_ips, ip))
+    }
+}
+
+struct SharedStore {
+    backend: Box<dyn RateLimitBackend>,
+    /// Set while the store is failing, so the warning is logged once.
+    unavailable: AtomicBool,
+}
+
+/// Rate limiter with one bucket per key.
+///
+/// Requests whose route starts with a group's prefix use that group's quota
+/// (first match wins); others use the default quota, or are not limited if
+/// there is none. Each group counts keys separately. When the extractor
+/// returns no key the client IP is used, and requests without either pass
+/// through.
+///
+/// Keys that have been idle long enough to have a full bucket again are
+/// evicted every [`evict_interval`](Self::evict_interval) (60 seconds by
+/// default), so memory stays bounded by the number of active clients.
+///
+/// With a [`backend`](Self::backend), buckets are kept in the shared store
+/// and the local ones are only used while the store is unreachable; each
+/// replica then enforces the full quota on its own.
+///
+/// Quotas and exemptions can be replaced at runtime with
+/// [`reload`](Self::reload).
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit::{KeyedRateLimiter, RateLimitKey, RateLimitQuota};
+///
+/// let limiter = KeyedRateLimiter::new(RateLimitKey::ClientIp)
+///     .quota(RateLimitQuota::per_second(20))
+///     .route_group("/auth/login", RateLimitQuota::per_minute(5));
+/// ```
+pub struct KeyedRateLimiter {
+    extractor: Box<dyn KeyExtractor>,
+    rules: RwLock<Arc<Rules>>,
+    store: Option<SharedStore>,
+    evict_interval_ms: AtomicU64,
+    started: Instant,
+    last_evict_ms: AtomicU64,
+}
+
+impl KeyedRateLimiter {
+    pub fn new(extractor: impl KeyExtractor) -> Self {
+        Self {
+            extractor: Box::new(extractor),
+            rules: RwLock::new(Arc::new(Rules::default())),
+            store: None,
+            evict_interval_ms: AtomicU64::new(60_000),
+            started: Instant::now(),
+            last_evict_ms: AtomicU64::new(0),
+        }
+    }
+
+    /// Build a limiter from the `rate_limit` config section. Without
+    /// `enabled` it has no quotas and limits nothing.
+    pub fn from_config(config: &RateLimitConfig) -> Self {
+        let limiter = Self::new(config.key.clone());
+        limiter.set_rules(config);
+        limiter
+    }
+
+    /// Set the quota for routes not in a group. A quota of zero requests
+    /// or with a zero period leaves them unlimited, as in the config.
+    pub fn quota(mut self, quota: RateLimitQuota) -> Self {
+        self.rules_mut().default = Bucket::new("*".to_string(), quota).map(Arc::new);
+        self
+    }
+
+    /// Use a separate quota for routes starting with `prefix`. A quota of
+    /// zero requests or with a zero period leaves them unlimited.
+    pub fn route_group(mut self, prefix: impl Into<String>, quota: RateLimitQuota) -> Self {
+        let prefix = prefix.into();
+        self.rules_mut().groups.push(RouteGroup {
+            bucket: Bucket::new(prefix.clone(), quota).map(Arc::new),
+            prefix,
+        });
+        self
+    }
+
+    /// Never limit routes starting with `prefix`.
+    pub fn exempt_route(mut self, prefix: impl Into<String>) -> Self {
+        self.rules_mut().exempt_routes.push(prefix.into());
+        self
+    }
+
+    /// Never limit clients in `network`.
+    pub fn exempt_ip(mut self, network: IpNetwork) -> Self {
+        self.rules_mut().exempt_ips.push(network);
+        self
+    }
+
+    /// Keep the buckets in a store shared by all replicas.
+    pub fn backend(mut self, backend: impl RateLimitBackend) -> Self {
+        self.store = Some(SharedStore {
+            backend: Box::new(backend),
+            unavailable: AtomicBool::new(false),
+        });
+        self
+    }
+
+    /// Set how often idle keys are evicted.
+    pub fn evict_interval(self, interval: Duration) -> Self {
+        self.set_evict_interval(interval);
+        self
+    }
+
+    /// Share the limiter and register it with [`reload_rate_limits`] under
+    /// `section`, the config section its rules come from.
+    pub fn register(self, section: impl Into<String>) -> Arc<Self> {
+        let limiter = Arc::new(self);
+        let mut registered = REGISTERED.lock().unwrap_or_else(|e| e.into_inner());
+        registered.retain(|(_, limiter)| limiter.strong_count() > 0);
+        registered.push((section.into(), Arc::downgrade(&limiter)));
+        limiter
+    }
+
+    /// Replace the quotas, exemptions and eviction interval with those of
+    /// `config`.
+    ///
+    /// Buckets whose quota is unchanged keep their state, so clients aren't
+    /// handed a fresh quota by a reload; changed quotas start out full. The
+    /// key and the backend are kept.
+    pub fn reload(&self, config: &RateLimitConfig) {
+        self.set_rules(config);
+        tracing::info!(
+            enabled = config.enabled,
+            requests = config.requests,
+            period_secs = config.period_secs,
+            routes = config.routes.len(),
+            "Reloaded rate limits"
+        );
+    }
+
+    /// The quota applied to `route`, if any.
+    pub fn quota_for(&self, route: &str) -> Option<RateLimitQuota> {
+        self.rules().bucket(route).map(|bucket| bucket.quota)
+    }
+
+    /// Number of keys currently tracked across all groups.
+    pub fn len(&self) -> usize {
+        self.rules()
+            .buckets()
+            .map(|bucket| bucket.limiter.len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Count a request against its key's quota.
+    ///
+    /// Returns `None` when the request isn't limited: it's exempt, no quota
+    /// applies to its route, or it has neither a key nor a client IP.
+    pub async fn check(&self, req: &RateLimitRequest<'_>) -> Option<RateLimitStatus> {
+        self.maybe_evict();
+
+        let rules = self.rules();
+        if rules.is_exempt(req) {
+            return None;
+        }
+        let bucket = rules.bucket(req.route)?;
+        let key = self
+            .extractor
+            .extract(req)
+            .or_else(|| req.client_ip.map(|ip| ip.to_string()))?;
+
+        if let Some(store) = &self.store {
+            let shared_key = format!("{}:{}", bucket.name, key);
+            match store.backend.check(&shared_key, bucket.quota).await {
+                Ok(status) => {
+                    if store.unavailable.swap(false, Ordering::Relaxed) {
+                        tracing::info!("Rate limit store reachable again");
+                    }
+                    return Some(status);
+                }
+                Err(e) => {
+                    if !store.unavailable.swap(true, Ordering::Relaxed) {
+                        tracing::warn!(
+                            error = %e,
+                            "Rate limit store unavailable, falling back to local limits"
+                        );
+                    }
+                }
+            }
+        }
+        Some(bucket.check(key))
+    }
+
+    /// Drop keys whose buckets have refilled and release the freed memory.
+    pub fn evict_idle(&self) {
+        for bucket in self.rules().buckets() {
+            bucket.limiter.retain_recent();
+            bucket.limiter.shrink_to_fit();
+        }
+    }
+
+    fn maybe_evict(&self) {
+        let now = self.started.elapsed().as_millis() as u64;
+        let last = self.last_evict_ms.load(Ordering::Relaxed);
+        if now.saturating_sub(last) < self.evict_interval_ms.load(Ordering::Relaxed) {
+            return;
+        }
+        // Only one caller per interval does the work.
+        if self
+            .last_evict_ms
+            .compare_exchange(last, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+        {
+            self.evict_idle();
+        }
+    }
+
+    fn set_evict_interval(&self, interval: Duration) {
+        self.evict_interval_ms
+            .store(interval.as_millis() as u64, Ordering::Relaxed);
+    }
+
+    fn set_rules(&self, config: &RateLimitConfig) {
+        let old = self.rules();
+        let bucket = |name: &str, quota: RateLimitQuota| {
+            old.buckets()
+                .find(|bucket| bucket.name == name && bucket.quota == quota)
+                .cloned()
+                .or_else(|| Bucket::new(name.to_string(), quota).map(Arc::new))
+        };
+
+        let mut rules = Rules::default();
+        if config.enabled {
+            rules.default = config.quota().and_then(|quota| bucket("*", quota));
+            rules.groups = config
+                .routes
+                .iter()
+                .map(|route| RouteGroup {
+                    prefix: route.prefix.clone(),
+                    bucket: route.quota().and_then(|quota| bucket(&route.prefix, quota)),
+                })
+                .collect();
+            rules.exempt_routes = config.exempt_routes.clone();
+            rules.exempt_ips = config.exempt_ips.clone();
+        }
+        self.set_evict_interval(config.evict_interval());
+        *self.rules.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(rules);
+    }
+
+    fn rules(&self) -> Arc<Rules> {
+        Arc::clone(&self.rules.read().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    fn rules_mut(&mut self) -> &mut Rules {
+        Arc::make_mut(self.rules.get_mut().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl fmt::Debug for KeyedRateLimiter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rules = self.rules();
+        f.debug_struct("KeyedRateLimiter")
+            .field("quota", &rules.default.as_ref().map(|bucket| bucket.quota))
+            .field(
+                "groups",
+                &rules
+                    .groups
+                    .iter()
+                    .map(|group| (&group.prefix, group.bucket.as_ref().map(|b| b.quota)))
+                    .collect::<Vec<_>>(),
+            )
+            .field("exempt_routes", &rules.exempt_routes)
+            .field("exempt_ips", &rules.exempt_ips)
+            .field("shared", &self.store.is_some())
+            .field("keys", &self.len())
+            .finish()
+    }
+}
+
+/// Reload the limiters [registered](KeyedRateLimiter::register) under
+/// `section` with `config`. Limiters of other sections, e.g. the other
+/// server's, keep their rules. Returns the number of limiters reloaded.
+///
+/// Nothing calls this on its own: call it after re-reading the config file,
+/// e.g. on SIGHUP.
+pub fn reload_rate_limits(section: &str, config: &RateLimitConfig) -> usize {
+    let limiters: Vec<_> = REGISTERED
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .filter(|(registered, _)| registered == section)
+        .filter_map(|(_, limiter)| limiter.upgrade())
+        .collect();
+    for limiter in &limiters {
+        limiter.reload(config);
+    }
+    limiters.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::tests::Req;
+    use crate::rate_limit::{
+        BackendFuture, MemoryBackend, RateLimitBackendError, RateLimitKey, RouteRateLimit,
+    };
+
+    async fn allowed(limiter: &KeyedRateLimiter, req: &RateLimitRequest<'_>) -> bool {
+        let status = limiter.check(req).await;
+        status.is_none_or(|status| status.is_allowed())
+    }
+
+    #[tokio::test]
+    async fn limits_each_key_separately() {
+        let limiter =
+            KeyedRateLimiter::new(RateLimitKey::ClientIp).quota(RateLimitQuota::per_minute(2));
+        let a = Req::new("/api", "10.0.0.1");
+        let b = Req::new("/api", "10.0.0.2");
+
+        assert!(allowed(&limiter, &a.view()).await);
+        assert!(allowed(&limiter, &a.view()).await);
+        assert!(!allowed(&limiter, &a.view()).await);
+        assert!(allowed(&limiter, &b.view()).await);
+        assert_eq!(limiter.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn keeps_sub_second_periods() {
+        // 10 per 100ms is 100 per second, not 10.
+        let quota = RateLimitQuota::new(10, Duration::from_millis(100));
+        let limiter = KeyedRateLimiter::new(RateLimitKey::ClientIp).quota(quota);
+        let req = Req::new("/api", "10.0.0.1");
+        for _ in 0..10 {
+            assert!(allowed(&limiter, &req.view()).await);
+        }
+        let status = limiter.check(&req.view()).await.unwrap();
+        assert!(status.retry_after.unwrap() <= Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn degenerate_quotas_do_not_panic() {
+        let limiter = KeyedRateLimiter::new(RateLimitKey::ClientIp)
+            .quota(RateLimitQuota::per_second(0))
+            .route_group("/now", RateLimitQuota::new(5, Duration::ZERO));
+        assert_eq!(limiter.quota_for("/api"), None);
+        assert_eq!(limiter.quota_for("/now"), None);
+
+        // Finer than a nanosecond per request.
+        let limiter = KeyedRateLimiter::from_config(&RateLimitConfig {
+            enabled: true,
+            requests: u32::MAX,
+            period_secs: 1,
+            ..Default::default()
+        });
+        assert!(allowed(&limiter, &Req::new("/api", "10.0.0.1").view()).await);
+    }
+
+    #[tokio::test]
+    async fn route_groups_have_their_own_quota() {
+        let limiter = KeyedRateLimiter::new(RateLimitKey::ClientIp)
+            .route_group("/login", RateLimitQuota::per_minute(1));
+        let login = Req::new("/login", "10.0.0.1");
+        let api = Req::new("/api", "10.0.0.1");
+
+        assert!(allowed(&limiter, &login.view()).await);
+        assert!(!allowed(&limiter, &login.view()).await);
+        // No default quota: other routes are not limited.
+        assert_eq!(limiter.check(&api.view()).await, None);
+        assert_eq!(limiter.quota_for("/api"), None);
+        assert_eq!(
+            limiter.quota_for("/login"),
+            Some(RateLimitQuota::per_minute(1))
+        );
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_client_ip_without_key() {
+        let limiter =
+            KeyedRateLimiter::new(RateLimitKey::Subject).quota(RateLimitQuota::per_minute(1));
+        let anonymous = Req::new("/api", "10.0.0.1");
+        assert!(allowed(&limiter, &anonymous.view()).await);
+        assert!(!allowed(&limiter, &anonymous.view()).await);
+
+        // Neither a subject nor a client IP: not limited.
+        let unix = Req::new("/api", "-");
+        assert_eq!(limiter.check(&unix.view()).await, None);
+    }
+
+    #[tokio::test]
+    async fn closures_are_extractors() {
+        let limiter = KeyedRateLimiter::new(|req: &RateLimitRequest<'_>| {
+            req.headers
+                .get("x-tenant")
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        })
+        .quota(RateLimitQuota::per_minute(1));
+
+        let mut a = Req::new("/api", "10.0.0.1");
+        a.headers.insert("x-tenant", "a".parse().unwrap());
+        let mut b = Req::new("/api", "10.0.0.1");
+        b.headers.insert("x-tenant", "b".parse().unwrap());
+        assert!(allowed(&limiter, &a.view()).await);
+        assert!(allowed(&limiter, &b.view()).await);
+        assert!(!allowed(&limiter, &a.view()).await);
+    }
+
+    #[tokio::test]
+    async fn reports_remaining_quota_and_retry_after() {
+        let limiter =
+            KeyedRateLimiter::new(RateLimitKey::Global).quota(RateLimitQuota::per_minute(2));
+        let req = Req::new("/api", "10.0.0.1");
+
+        let first = limiter.check(&req.view()).await.unwrap();
+        assert_eq!((first.limit, first.remaining), (2, 1));
+        assert_eq!(first.reset, Duration::from_secs(30));
+        assert!(first.is_allowed());
+
+        let second = limiter.check(&req.view()).await.unwrap();
+        assert_eq!(second.remaining, 0);
+        assert_eq!(second.reset, Duration::from_secs(60));
+
+        let rejected = limiter.check(&req.view()).await.unwrap();
+        assert_eq!(rejected.remaining, 0);
+        let retry_after = rejected.retry_after.unwrap();
+        assert!(retry_after > Duration::from_secs(29) && retry_after <= Duration::from_secs(30));
+        assert_eq!(rejected.reset, retry_after + Duration::from_secs(30));
+
+        let mut headers = http::HeaderMap::new();
+        rejected.insert_headers(&mut headers);
+        assert_eq!(headers["ratelimit-limit"], "2");
+        assert_eq!(headers["ratelimit-remaining"], "0");
+        assert_eq!(headers["ratelimit-reset"], "60");
+        assert_eq!(headers["retry-after"], "30");
+    }
+
+    #[tokio::test]
+    async fn replicas_share_a_backend() {
+        let store = Arc::new(MemoryBackend::new());
+        let replica = || {
+            KeyedRateLimiter::new(RateLimitKey::ClientIp)
+                .quota(RateLimitQuota::per_minute(2))
+                .backend(Arc::clone(&store))
+        };
+        let (a, b) = (replica(), replica());
+        let req = Req::new("/api", "10.0.0.1");
+
+        assert!(allowed(&a, &req.view()).await);
+        assert!(allowed(&b, &req.view()).await);
+        assert!(!allowed(&a, &req.view()).await);
+        // The local buckets weren't used.
+        assert!(a.is_empty());
+    }
+
+    struct Unreachable;
+
+    impl RateLimitBackend for Unreachable {
+        fn check<'a>(&'a self, _key: &'a str, _quota: RateLimitQuota) -> BackendFuture<'a> {
+            Box::pin(async { Err(RateLimitBackendError::new("connection refused")) })
+        }
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_local_limits_when_store_fails() {
+        let limiter = KeyedRateLimiter::new(RateLimitKey::ClientIp)
+            .quota(RateLimitQuota::per_minute(1))
+            .backend(Unreachable);
+        let req = Req::new("/api", "10.0.0.1");
+
+        assert!(allowed(&limiter, &req.view()).await);
+        assert!(!allowed(&limiter, &req.view()).await);
+        assert_eq!(limiter.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn evicts_idle_keys() {
+        let limiter = KeyedRateLimiter::new(RateLimitKey::ClientIp)
+            .quota(RateLimitQuota::new(1000, Duration::from_secs(1)));
+        assert!(allowed(&limiter, &Req::new("/", "10.0.0.1").view()).await);
+        assert_eq!(limiter.len(), 1);
+
+        std::thread::sleep(Duration::from_millis(5));
+        limiter.evict_idle();
+        assert!(limiter.is_empty());
+    }
+
+    fn config() -> RateLimitConfig {
+        RateLimitConfig {
+            enabled: true,
+            requests: 2,
+            period_secs: 60,
+            routes: vec![RouteRateLimit {
+                prefix: "/login".to_string(),
+                requests: 1,
+                period_secs: 60,
+            }],
+            exempt_routes: vec!["/health".to_string()],
+            exempt_ips: vec!["10.9.0.0/16".parse().unwrap()],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn builds_from_config() {
+        let limiter = KeyedRateLimiter::from_config(&config());
+        assert_eq!(
+            limiter.quota_for("/api"),
+            Some(RateLimitQuota::per_minute(2))
+        );
+        assert_eq!(
+            limiter.quota_for("/login"),
+            Some(RateLimitQuota::per_minute(1))
+        );
+
+        let login = Req::new("/login", "10.0.0.1");
+        assert!(allowed(&limiter, &login.view()).await);
+        assert!(!allowed(&limiter, &login.view()).await);
+        assert_eq!(
+            limiter.check(&Req::new("/health", "10.0.0.1").view()).await,
+            None
+        );
+        assert_eq!(
+            limiter.check(&Req::new("/api", "10.9.1.1").view()).await,
+            None
+        );
+
+        let disabled = KeyedRateLimiter::from_config(&RateLimitConfig::default());
+        assert_eq!(disabled.quota_for("/api"), None);
+    }
+
+    #[tokio::test]
+    async fn reload_keeps_state_of_unchanged_quotas() {
+        let limiter = KeyedRateLimiter::from_config(&config());
+        let api = Req::new("/api", "10.0.0.1");
+        let login = Req::new("/login", "10.0.0.1");
+        assert!(allowed(&limiter, &api.view()).await);
+        assert!(allowed(&limiter, &login.view()).await);
+
+        let mut reloaded = config();
+        reloaded.routes[0].requests = 3;
+        limiter.reload(&reloaded);
+
+        // The default quota is unchanged: one request left.
+        assert!(allowed(&limiter, &api.view()).await);
+        assert!(!allowed(&limiter, &api.view()).await);
+        // The login quota changed and starts out full.
+        assert_eq!(
+            limiter.quota_for("/login"),
+            Some(RateLimitQuota::per_minute(3))
+        );
+        assert!(allowed(&limiter, &login.view()).await);
+
+        limiter.reload(&RateLimitConfig::default());
+        assert_eq!(limiter.check(&api.view()).await, None);
+    }
+
+    #[tokio::test]
+    async fn unlimited_route_overrides_default_quota() {
+        let mut config = config();
+        config.routes[0].requests = 0;
+        let limiter = KeyedRateLimiter::from_config(&config);
+        assert_eq!(limiter.quota_for("/login"), None);
+        assert_eq!(
+            limiter.check(&Req::new("/login", "10.0.0.1").view()).await,
+            None
+        );
+    }
+
+    #[test]
+    fn reloads_limiters_of_one_section() {
+        const SECTION: &str = "test.reloads_limiters_of_one_section";
+        let limiter = KeyedRateLimiter::new(RateLimitKey::ClientIp).register(SECTION);
+        let other = KeyedRateLimiter::new(RateLimitKey::ClientIp).register("test.other");
+
+        assert_eq!(reload_rate_limits(SECTION, &config()), 1);
+        assert_eq!(
+            limiter.quota_for("/api"),
+            Some(RateLimitQuota::per_minute(2))
+        );
+        assert_eq!(other.quota_for("/api"), None);
+    }
+}
@@ -0,0 +1,381 @@
+//! Keyed rate limiting.
+//!
+//! A [`KeyedRateLimiter`] keeps one governor bucket per key, so a single
+//! noisy client only exhausts its own quota. The HTTP and gRPC crates wrap
+//! it in a tower layer; this module holds the key extraction, route groups
+//! and idle-key eviction they share. With a [`RateLimitBackend`] the buckets
+//! live in a store shared by all replicas instead.
+//!
+//! The key and quota types and [`RateLimitConfig`] are always available so
+//! config files parse the same with or without the `ratelimit` feature.
+
+#[cfg(feature = "ratelimit")]
+mod backend;
+#[cfg(feature = "ratelimit")]
+mod limiter;
+#[cfg(feature = "redis")]
+mod redis;
+
+#[cfg(feature = "redis")]
+pub use self::redis::RedisBackend;
+#[cfg(feature = "ratelimit")]
+pub use backend::{BackendFuture, MemoryBackend, RateLimitBackend, RateLimitBackendError};
+#[cfg(feature = "ratelimit")]
+pub use limiter::{reload_rate_limits, KeyedRateLimiter};
+
+use crate::IpNetwork;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::time::Duration;
+
+/// Authenticated subject of a request, e.g. the `sub` claim of a JWT.
+///
+/// Auth layers insert it into the request extensions; [`RateLimitKey::Subject`]
+/// reads it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Subject(pub String);
+
+/// The parts of a request a [`KeyExtractor`] can look at.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitRequest<'a> {
+    /// Matched route if known, otherwise the request path. For gRPC this is
+    /// `/package.Service/Method`.
+    pub route: &'a str,
+    pub headers: &'a http::HeaderMap,
+    pub extensions: &'a http::Extensions,
+    /// Resolved client IP, `None` when unknown (e.g. Unix sockets).
+    pub client_ip: Option<IpAddr>,
+}
+
+/// Picks the key a request is counted under.
+///
+/// Implemented by [`RateLimitKey`] and by closures taking a
+/// [`RateLimitRequest`].
+pub trait KeyExtractor: Send + Sync + 'static {
+    /// The key for `req`, or `None` to fall back to the client IP.
+    fn extract(&self, req: &RateLimitRequest<'_>) -> Option<String>;
+}
+
+impl<F> KeyExtractor for F
+where
+    F: Fn(&RateLimitRequest<'_>) -> Option<String> + Send + Sync + 'static,
+{
+    fn extract(&self, req: &RateLimitRequest<'_>) -> Option<String> {
+        self(req)
+    }
+}
+
+/// Built-in rate limit keys.
+///
+/// In a config file: `key = "client_ip"`, `key = "subject"`, `key = "route"`
+/// or `key = { header = "x-api-key" }`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKey {
+    /// The resolved client IP.
+    #[default]
+    ClientIp,
+    /// The value of a request header, e.g. an API key.
+    Header(String),
+    /// The authenticated [`Subject`].
+    Subject,
+    /// The route, limiting each endpoint as a whole.
+    Route,
+    /// One key for all requests.
+    Global,
+}
+
+impl KeyExtractor for RateLimitKey {
+    fn extract(&self, req: &RateLimitRequest<'_>) -> Option<String> {
+        match self {
+            RateLimitKey::ClientIp => req.client_ip.map(|ip| ip.to_string()),
+            RateLimitKey::Header(name) => req
+                .headers
+                .get(name.as_str())
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+            RateLimitKey::Subject => req
+                .extensions
+                .get::<Subject>()
+                .map(|Subject(sub)| sub.clone()),
+            RateLimitKey::Route => Some(req.route.to_string()),
+            RateLimitKey::Global => Some(String::new()),
+        }
+    }
+}
+
+/// Number of requests allowed per period.
+///
+/// The quota refills evenly: one request's worth comes back every
+/// `period / requests`, and up to `requests` can be used at once.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimitQuota {
+    pub requests: u32,
+    pub period: Duration,
+}
+
+impl RateLimitQuota {
+    /// Allow `requests` per `period`.
+    pub fn new(requests: u32, period: Duration) -> Self {
+        Self { requests, period }
+    }
+
+    pub fn per_second(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(1))
+    }
+
+    pub fn per_minute(requests: u32) -> Self {
+        Self::new(requests, Duration::from_secs(60))
+    }
+
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+}
+
+/// Outcome of a rate limit check, in the terms of the IETF
+/// `RateLimit` header fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimitStatus {
+    /// Requests allowed per period.
+    pub limit: u32,
+    /// Requests left before the key is limited.
+    pub remaining: u32,
+    /// Time until the key's quota is fully available again.
+    pub reset: Duration,
+    /// Set when the request was rejected: time until the next one is allowed.
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimitStatus {
+    pub fn is_allowed(&self) -> bool {
+        self.retry_after.is_none()
+    }
+
+    /// Insert `RateLimit-Limit`, `RateLimit-Remaining` and `RateLimit-Reset`,
+    /// plus `Retry-After` when rejected. Durations are whole seconds, rounded
+    /// up.
+    pub fn insert_headers(&self, headers: &mut http::HeaderMap) {
+        headers.insert("ratelimit-limit", self.limit.into());
+        headers.insert("ratelimit-remaining", self.remaining.into());
+        headers.insert("ratelimit-reset", ceil_secs(self.reset).into());
+        if let Some(retry_after) = self.retry_after {
+            headers.insert(
+                http::header::RETRY_AFTER,
+                ceil_secs(retry_after).max(1).into(),
+            );
+        }
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+/// Rate limits loaded from the `rate_limit` section of the config file:
+///
+/// ```toml
+/// [rate_limit]
+/// enabled = true
+/// key = "client_ip"
+/// requests = 100
+/// period_secs = 1
+/// exempt_routes = ["/health", "/metrics"]
+/// exempt_ips = ["10.0.0.0/8"]
+///
+/// [[rate_limit.routes]]
+/// prefix = "/auth/login"
+/// requests = 5
+/// period_secs = 60
+/// ```
+///
+/// gRPC routes are `/package.Service/Method`, so a prefix can name a whole
+/// service or a single method; `methods` is accepted as an alias of
+/// `routes` there.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimitConfig {
+    /// Apply the limits in the default layers. Off by default.
+    pub enabled: bool,
+    /// What requests are counted by.
+    pub key: RateLimitKey,
+    /// Requests allowed per period on routes without an override. `0` leaves
+    /// them unlimited.
+    pub requests: u32,
+    pub period_secs: u64,
+    /// Per-route quotas; the first matching prefix wins.
+    #[serde(alias = "methods")]
+    pub routes: Vec<RouteRateLimit>,
+    /// Route prefixes that are never limited.
+    pub exempt_routes: Vec<String>,
+    /// Client networks that are never limited.
+    pub exempt_ips: Vec<IpNetwork>,
+    /// How often keys with a full bucket again are dropped.
+    pub evict_interval_secs: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            key: RateLimitKey::default(),
+            requests: 0,
+            period_secs: 1,
+            routes: Vec::new(),
+            exempt_routes: Vec::new(),
+            exempt_ips: Vec::new(),
+            evict_interval_secs: 60,
+        }
+    }
+}
+
+impl RateLimitConfig {
+    /// The quota for routes without an override, if any.
+    pub fn quota(&self) -> Option<RateLimitQuota> {
+        quota(self.requests, self.period_secs)
+    }
+
+    pub fn evict_interval(&self) -> Duration {
+        Duration::from_secs(self.evict_interval_secs)
+    }
+
+    /// Whether a request to `route` from `client_ip` is exempt.
+    pub fn is_exempt(&self, route: &str, client_ip: Option<IpAddr>) -> bool {
+        self.exempt_routes
+            .iter()
+            .any(|prefix| route.starts_with(prefix.as_str()))
+            || client_ip.is_some_and(|ip| IpNetwork::any_contains(&self.exempt_ips, ip))
+    }
+}
+
+/// Quota override for routes (gRPC: methods) starting with `prefix`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RouteRateLimit {
+    #[serde(alias = "method")]
+    pub prefix: String,
+    /// Requests allowed per period. `0` leaves the routes unlimited.
+    pub requests: u32,
+    pub period_secs: u64,
+}
+
+impl Default for RouteRateLimit {
+    fn default() -> Self {
+        Self {
+            prefix: String::new(),
+            requests: 0,
+            period_secs: 1,
+        }
+    }
+}
+
+impl RouteRateLimit {
+    /// The routes' quota, `None` when they're unlimited.
+    pub fn quota(&self) -> Option<RateLimitQuota> {
+        quota(self.requests, self.period_secs)
+    }
+}
+
+fn quota(requests: u32, period_secs: u64) -> Option<RateLimitQuota> {
+    (requests > 0 && period_secs > 0)
+        .then(|| RateLimitQuota::new(requests, Duration::from_secs(period_secs)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    pub(super) struct Req {
+        pub route: &'static str,
+        pub headers: http::HeaderMap,
+        pub extensions: http::Extensions,
+        pub client_ip: Option<IpAddr>,
+    }
+
+    impl Req {
+        pub fn new(route: &'static str, ip: &str) -> Self {
+            Self {
+                route,
+                headers: http::HeaderMap::new(),
+                extensions: http::Extensions::new(),
+                client_ip: ip.parse().ok(),
+            }
+        }
+
+        pub fn view(&self) -> RateLimitRequest<'_> {
+            RateLimitRequest {
+                route: self.route,
+                headers: &self.headers,
+                extensions: &self.extensions,
+                client_ip: self.client_ip,
+            }
+        }
+    }
+
+    #[test]
+    fn extracts_header_and_subject_keys() {
+        let mut req = Req::new("/api", "10.0.0.1");
+        req.headers.insert("x-api-key", "key-1".parse().unwrap());
+        req.extensions.insert(Subject("alice".to_string()));
+
+        let header = RateLimitKey::Header("x-api-key".to_string());
+        assert_eq!(header.extract(&req.view()).as_deref(), Some("key-1"));
+        assert_eq!(
+            RateLimitKey::Subject.extract(&req.view()).as_deref(),
+            Some("alice")
+        );
+        assert_eq!(
+            RateLimitKey::Route.extract(&req.view()).as_deref(),
+            Some("/api")
+        );
+    }
+
+    #[test]
+    fn key_deserializes_from_config() {
+        #[derive(Deserialize)]
+        struct Section {
+            key: RateLimitKey,
+        }
+        let section: Section = serde_json::from_str(r#"{"key":"client_ip"}"#).unwrap();
+        assert_eq!(section.key, RateLimitKey::ClientIp);
+        let section: Section = serde_json::from_str(r#"{"key":{"header":"x-api-key"}}"#).unwrap();
+        assert_eq!(section.key, RateLimitKey::Header("x-api-key".to_string()));
+    }
+
+    #[test]
+    fn config_deserializes_with_defaults() {
+        let config: RateLimitConfig = serde_json::from_str(
+            r#"{
+                "enabled": true,
+                "requests": 100,
+                "methods": [{"method": "/auth.Auth/", "requests": 5, "period_secs": 60}],
+                "exempt_ips": ["10.0.0.0/8"]
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(config.key, RateLimitKey::ClientIp);
+        assert_eq!(config.quota(), Some(RateLimitQuota::per_second(100)));
+        assert_eq!(config.routes[0].prefix, "/auth.Auth/");
+        assert_eq!(
+            config.routes[0].quota(),
+            Some(RateLimitQuota::per_minute(5))
+        );
+        assert_eq!(config.evict_interval(), Duration::from_secs(60));
+
+        assert_eq!(RateLimitConfig::default().quota(), None);
+        assert!(!RateLimitConfig::default().enabled);
+    }
+
+    #[test]
+    fn exempts_routes_and_networks() {
+        let config = RateLimitConfig {
+            exempt_routes: vec!["/health".to_string()],
+            exempt_ips: vec!["10.0.0.0/8".parse().unwrap()],
+            ..Default::default()
+        };
+        assert!(config.is_exempt("/health/ready", None));
+        assert!(config.is_exempt("/api", "10.1.2.3".parse().ok()));
+        assert!(!config.is_exempt("/api", "192.168.0.1".parse().ok()));
+        assert!(!config.is_exempt("/api", None));
+    }
+}
@@ -0,0 +1,379 @@
+//! Redis rate limit store.
+
+use super::backend::{gcra_params, gcra_status};
+use super::{
+    BackendFuture, RateLimitBackend, RateLimitBackendError, RateLimitQuota, RateLimitStatus,
+};
+use redis::aio::MultiplexedConnection;
+use redis::{AsyncConnectionConfig, Client, Script};
+use std::fmt;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// GCRA in one atomic step. Uses the server clock so replicas with skewed
+/// clocks agree. Returns `{allowed, retry_after_us, reset_us}`.
+const GCRA_SCRIPT: &str = r#"
+local interval = tonumber(ARGV[1])
+local tolerance = tonumber(ARGV[2])
+local time = redis.call('TIME')
+local now = tonumber(time[1]) * 1000000 + tonumber(time[2])
+local tat = tonumber(redis.call('GET', KEYS[1])) or now
+if tat < now then
+  tat = now
+end
+if tat - now > tolerance then
+  return {0, tat - now - tolerance, tat - now}
+end
+local new_tat = tat + interval
+redis.call('SET', KEYS[1], new_tat, 'PX', math.ceil((new_tat - now) / 1000))
+return {1, 0, new_tat - now}
+"#;
+
+/// How long to wait before reconnecting after a failed attempt.
+const RECONNECT_DELAY: Duration = Duration::from_secs(1);
+
+struct State {
+    conn: Option<MultiplexedConnection>,
+    retry_at: Option<Instant>,
+}
+
+/// Rate limit store on Redis or any server speaking its protocol and Lua
+/// scripting (Valkey, KeyDB, ...).
+///
+/// Each check runs one script that reads and updates the key's state
+/// atomically; keys expire once their bucket is full again. The connection
+/// is opened on first use and re-opened after errors, at most once a second.
+///
+/// # Example
+///
+/// ```ignore
+/// let backend = RedisBackend::new("redis://redis.internal:6379")?
+///     .key_prefix("myapp:ratelimit:");
+/// let limiter = KeyedRateLimiter::new(RateLimitKey::ClientIp)
+///     .quota(RateLimitQuota::per_second(20))
+///     .backend(backend);
+/// ```
+pub struct RedisBackend {
+    client: Client,
+    script: Script,
+    prefix: String,
+    timeout: Duration,
+    state: Mutex<State>,
+}
+
+impl RedisBackend {
+    /// Create a backend for a `redis://` URL. Doesn't connect yet.
+    pub fn new(url: &str) -> Result<Self, RateLimitBackendError> {
+        let client = Client::open(url).map_err(|e| RateLimitBackendError::new(e.to_string()))?;
+        Ok(Self {
+            client,
+            script: Script::new(GCRA_SCRIPT),
+            prefix: "ratelimit:".to_string(),
+            timeout: Duration::from_millis(100),
+            state: Mutex::new(State {
+                conn: None,
+                retry_at: None,
+            }),
+        })
+    }
+
+    /// Prefix for the Redis keys, `ratelimit:` by default.
+    pub fn key_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// Timeout for connecting and for each check, 100ms by default.
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    async fn connection(&self) -> Result<MultiplexedConnection, RateLimitBackendError> {
+        let mut state = self.state.lock().await;
+        if let Some(conn) = &state.conn {
+            return Ok(conn.clone());
+        }
+        if state.retry_at.is_some_and(|at| Instant::now() < at) {
+            return Err(RateLimitBackendError::new("not connected"));
+        }
+
+        let config = AsyncConnectionConfig::new()
+            .set_connection_timeout(self.timeout)
+            .set_response_timeout(self.timeout);
+        match self
+            .client
+            .get_multiplexed_async_connection_with_config(&config)
+            .await
+        {
+            Ok(conn) => {
+                state.conn = Some(conn.clone());
+                state.retry_at = None;
+                Ok(conn)
+            }
+            Err(e) => {
+                state.retry_at = Some(Instant::now() + RECONNECT_DELAY);
+                Err(RateLimitBackendError::new(e.to_string()))
+            }
+        }
+    }
+
+    async fn check_key(
+        &self,
+        key: &str,
+        quota: RateLimitQuota,
+    ) -> Result<RateLimitStatus, RateLimitBackendError> {
+        let mut conn = self.connection().await?;
+        let (interval, tolerance) = gcra_params(quota);
+        let result: Result<(i64, i64, i64), _> = self
+            .script
+            .key(format!("{}{}", self.prefix, key))
+            .arg(interval)
+            .arg(tolerance)
+            .invoke_async(&mut conn)
+            .await;
+
+        match result {
+            Ok(reply) => Ok(script_status(quota, reply)),
+            Err(e) => {
+                if e.is_io_error() || e.is_timeout() || e.is_connection_dropped() {
+                    self.state.lock().await.conn = None;
+                }
+                Err(RateLimitBackendError::new(e.to_string()))
+            }
+        }
+    }
+}
+
+/// Status from the script's `{allowed, retry_after_us, reset_us}` reply.
+fn script_status(quota: RateLimitQuota, reply: (i64, i64, i64)) -> RateLimitStatus {
+    let (allowed, retry_after, reset) = reply;
+    let retry_after = (allowed == 0).then_some(retry_after.max(0) as u64);
+    gcra_status(quota, retry_after, reset.max(0) as u64)
+}
+
+impl RateLimitBackend for RedisBackend {
+    fn check<'a>(&'a self, key: &'a str, quota: RateLimitQuota) -> BackendFuture<'a> {
+        Box::pin(self.check_key(key, quota))
+    }
+}
+
+impl fmt::Debug for RedisBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RedisBackend")
+            .field("addr", &self.client.get_connection_info().addr)
+            .field("prefix", &self.prefix)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rate_limit::MemoryBackend;
+    use mlua::{Lua, Value, Variadic};
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+
+    /// A Redis stand-in speaking just enough RESP for the backend. It runs
+    /// the GCRA script natively, after checking the client loaded it.
+    async fn fake_redis() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let store = Arc::new(MemoryBackend::new());
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                tokio::spawn(serve(stream, Arc::clone(&store)));
+            }
+        });
+        format!("redis://{addr}")
+    }
+
+    async fn serve(stream: TcpStream, store: Arc<MemoryBackend>) {
+        let sha = Script::new(GCRA_SCRIPT).get_hash().to_string();
+        let mut loaded = false;
+        let (read, mut write) = stream.into_split();
+        let mut read = BufReader::new(read);
+
+        while let Some(args) = read_command(&mut read).await {
+            let reply = match args[0].to_ascii_uppercase().as_str() {
+                "SCRIPT" => {
+                    assert_eq!(args[2], GCRA_SCRIPT);
+                    loaded = true;
+                    format!("${}\r\n{sha}\r\n", sha.len())
+                }
+                "EVALSHA" if !loaded => "-NOSCRIPT No matching script.\r\n".to_string(),
+                "EVALSHA" => {
+                    assert_eq!(args[1], sha);
+                    let (interval, tolerance): (u64, u64) =
+                        (args[4].parse().unwrap(), args[5].parse().unwrap());
+                    // Back out the quota the client derived the parameters from.
+                    let requests = (tolerance / interval + 1) as u32;
+                    let period = Duration::from_micros(interval * u64::from(requests));
+                    let quota = RateLimitQuota::new(requests, period);
+                    let status = store.check(&args[3], quota).await.unwrap();
+                    let allowed = i64::from(status.is_allowed());
+                    let retry = status.retry_after.unwrap_or_default().as_micros();
+                    let reset = status.reset.as_micros();
+                    format!("*3\r\n:{allowed}\r\n:{retry}\r\n:{reset}\r\n")
+                }
+                _ => "+OK\r\n".to_string(),
+            };
+            if write.write_all(reply.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+
+    async fn read_command(
+        read: &mut BufReader<tokio::net::tcp::OwnedReadHalf>,
+    ) -> Option<Vec<String>> {
+        let mut line = String::new();
+        read.read_line(&mut line).await.ok()?;
+        let count: usize = line.trim().strip_prefix('*')?.parse().ok()?;
+        let mut args = Vec::with_capacity(count);
+        for _ in 0..count {
+            line.clear();
+            read.read_line(&mut line).await.ok()?;
+            let len: usize = line.trim().strip_prefix('$')?.parse().ok()?;
+            let mut buf = vec![0; len + 2];
+            tokio::io::AsyncReadExt::read_exact(read, &mut buf)
+                .await
+                .ok()?;
+            buf.truncate(len);
+            args.push(String::from_utf8(buf).ok()?);
+        }
+        Some(args)
+    }
+
+    /// Runs `GCRA_SCRIPT` in Lua, with `redis.call` served from a map and a
+    /// clock the test sets.
+    struct LuaRedis {
+        lua: Lua,
+        now_us: Rc<Cell<u64>>,
+        ttls_ms: Rc<RefCell<HashMap<String, u64>>>,
+    }
+
+    impl LuaRedis {
+        fn new() -> Self {
+            let lua = Lua::new();
+            let now_us = Rc::new(Cell::new(0));
+            let ttls_ms = Rc::new(RefCell::new(HashMap::new()));
+            let mut values = HashMap::<String, String>::new();
+
+            let (clock, ttls) = (Rc::clone(&now_us), Rc::clone(&ttls_ms));
+            let call = lua
+                .create_function_mut(move |lua, args: Variadic<String>| {
+                    Ok(match args[0].as_str() {
+                        "TIME" => {
+                            let now = clock.get();
+                            let time = [now / 1_000_000, now % 1_000_000].map(|n| n.to_string());
+                            Value::Table(lua.create_sequence_from(time)?)
+                        }
+                        "GET" => match values.get(&args[1]) {
+                            Some(value) => Value::String(lua.create_string(value)?),
+                            None => Value::Boolean(false),
+                        },
+                        "SET" => {
+                            assert_eq!(args[3], "PX");
+                            ttls.borrow_mut()
+                                .insert(args[1].clone(), args[4].parse().unwrap());
+                            values.insert(args[1].clone(), args[2].clone());
+                            Value::String(lua.create_string("OK")?)
+                        }
+                        other => panic!("unexpected command {other}"),
+                    })
+                })
+                .unwrap();
+            let redis = lua.create_table().unwrap();
+            redis.set("call", call).unwrap();
+            lua.globals().set("redis", redis).unwrap();
+            Self {
+                lua,
+                now_us,
+                ttls_ms,
+            }
+        }
+
+        fn check(&self, key: &str, quota: RateLimitQuota, now_us: u64) -> RateLimitStatus {
+            let (interval, tolerance) = gcra_params(quota);
+            let globals = self.lua.globals();
+            globals.set("KEYS", [key]).unwrap();
+            globals.set("ARGV", [interval, tolerance]).unwrap();
+            self.now_us.set(now_us);
+            let reply: Vec<i64> = self.lua.load(GCRA_SCRIPT).eval().unwrap();
+            script_status(quota, (reply[0], reply[1], reply[2]))
+        }
+    }
+
+    #[test]
+    fn script_matches_memory_backend() {
+        let redis = LuaRedis::new();
+        let memory = MemoryBackend::new();
+        let quota = RateLimitQuota::per_minute(2);
+        // The Redis clock is wall time; the memory backend counts from zero.
+        let epoch = 1_700_000_000_000_000;
+
+        let steps = [(0, "a"), (0, "a"), (1, "a"), (0, "b"), (31, "a")];
+        let mut now = 0;
+        for (elapsed_secs, key) in steps {
+            now += elapsed_secs * 1_000_000;
+            let expected = memory.check_at(key, quota, now);
+            assert_eq!(
+                redis.check(key, quota, epoch + now),
+                expected,
+                "{key} at {now}us"
+            );
+        }
+
+        // Keys expire once their bucket would be full again.
+        assert_eq!(redis.ttls_ms.borrow()["a"], 58_000);
+        assert_eq!(redis.ttls_ms.borrow()["b"], 30_000);
+    }
+
+    #[tokio::test]
+    async fn shares_quota_through_the_store() {
+        let url = fake_redis().await;
+        let replica_a = RedisBackend::new(&url).unwrap();
+        let replica_b = RedisBackend::new(&url).unwrap();
+        let quota = RateLimitQuota::per_minute(2);
+
+        let first = replica_a.check("10.0.0.1", quota).await.unwrap();
+        assert_eq!((first.limit, first.remaining), (2, 1));
+        assert_eq!(first.reset, Duration::from_secs(30));
+        assert!(replica_b
+            .check("10.0.0.1", quota)
+            .await
+            .unwrap()
+            .is_allowed());
+
+        let rejected = replica_a.check("10.0.0.1", quota).await.unwrap();
+        assert!(!rejected.is_allowed());
+        assert!(rejected.retry_after.unwrap() <= Duration::from_secs(30));
+        assert!(replica_b
+            .check("10.0.0.2", quota)
+            .await
+            .unwrap()
+            .is_allowed());
+    }
+
+    #[tokio::test]
+    async fn unreachable_store_is_an_error() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("redis://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let backend = RedisBackend::new(&url).unwrap();
+        let quota = RateLimitQuota::per_minute(2);
+        assert!(backend.check("key", quota).await.is_err());
+        // Within the reconnect delay no new attempt is made.
+        let err = backend.check("key", quota).await.unwrap_err();
+        assert_eq!(err.to_string(), "rate limit store error: not connected");
+    }
+}
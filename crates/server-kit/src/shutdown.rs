@@ -0,0 +1,352 @@
+//! Shutdown coordination.
+//!
+//! [`ShutdownController`] decides when servers stop: on one of its signals
+//! (SIGINT and SIGTERM by default), when triggered from code, or when any
+//! part of the process calls [`request_shutdown`] (e.g. the panic hook).
+//! Cleanup hooks registered on the controller run in order once the servers
+//! have drained.
+
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::task::Poll;
+use std::time::Duration;
+use tokio::sync::watch;
+
+static SHUTDOWN: OnceLock<watch::Sender<bool>> = OnceLock::new();
+
+fn sender() -> &'static watch::Sender<bool> {
+    SHUTDOWN.get_or_init(|| watch::channel(false).0)
+}
+
+/// Ask all servers in this process to shut down gracefully.
+pub fn request_shutdown() {
+    sender().send_replace(true);
+}
+
+/// Returns `true` once [`request_shutdown`] has been called.
+pub fn is_shutdown_requested() -> bool {
+    *sender().borrow()
+}
+
+/// Completes when [`request_shutdown`] is called.
+pub async fn shutdown_requested() {
+    let mut rx = sender().subscribe();
+    // The sender lives in a static, so this only fails if it was dropped,
+    // which cannot happen.
+    let _ = rx.wait_for(|requested| *requested).await;
+}
+
+/// OS signals that can start a shutdown.
+///
+/// Only [`ShutdownSignal::Interrupt`] is supported on non-Unix platforms;
+/// the others never fire there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShutdownSignal {
+    /// SIGINT (Ctrl+C).
+    Interrupt,
+    /// SIGTERM.
+    Terminate,
+    /// SIGQUIT.
+    Quit,
+    /// SIGHUP.
+    Hangup,
+}
+
+impl ShutdownSignal {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Interrupt => "SIGINT",
+            Self::Terminate => "SIGTERM",
+            Self::Quit => "SIGQUIT",
+            Self::Hangup => "SIGHUP",
+        }
+    }
+
+    /// Wait for the signal. Never completes if the handler cannot be installed.
+    async fn recv(self) -> Self {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+
+            let kind = match self {
+                Self::Interrupt => SignalKind::interrupt(),
+                Self::Terminate => SignalKind::terminate(),
+                Self::Quit => SignalKind::quit(),
+                Self::Hangup => SignalKind::hangup(),
+            };
+            match signal(kind) {
+                Ok(mut stream) => {
+                    stream.recv().await;
+                    return self;
+                }
+                Err(e) => {
+                    tracing::error!(signal = self.name(), error = %e, "Failed to install signal handler");
+                }
+            }
+        }
+
+        #[cfg(not(unix))]
+        if self == Self::Interrupt {
+            match tokio::signal::ctrl_c().await {
+                Ok(()) => return self,
+                Err(e) => {
+                    tracing::error!(signal = self.name(), error = %e, "Failed to install signal handler");
+                }
+            }
+        }
+
+        std::future::pending().await
+    }
+}
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+struct Hook {
+    name: String,
+    timeout: Duration,
+    run: Box<dyn FnOnce() -> BoxFuture + Send>,
+}
+
+/// Cloneable handle that observes a [`ShutdownController`].
+#[derive(Debug, Clone)]
+pub struct ShutdownToken {
+    rx: watch::Receiver<bool>,
+}
+
+impl ShutdownToken {
+    /// Returns `true` once shutdown has started.
+    pub fn is_shutdown(&self) -> bool {
+        *self.rx.borrow()
+    }
+
+    /// Completes when shutdown starts.
+    pub async fn cancelled(&self) {
+        let mut rx = self.rx.clone();
+        // The sender is owned by the controller; if every controller is gone
+        // nobody can trigger shutdown any more.
+        if rx.wait_for(|shutdown| *shutdown).await.is_err() {
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Controls when servers shut down and what runs afterwards.
+///
+/// Clones share state: triggering one triggers all of them.
+///
+/// # Example
+///
+/// ```ignore
+/// use server_kit::{ShutdownController, ShutdownSignal};
+/// use std::time::Duration;
+///
+/// let shutdown = ShutdownController::new().with_signal(ShutdownSignal::Quit);
+///
+/// let token = shutdown.token();
+/// tokio::spawn(async move {
+///     token.cancelled().await;
+///     // stop background work
+/// });
+///
+/// shutdown.on_shutdown("flush metrics", Duration::from_secs(5), || async {
+///     flush().await;
+/// });
+///
+/// router.serve_with_controller(&config, shutdown.clone()).await?;
+/// ```
+#[derive(Clone)]
+pub struct ShutdownController {
+    tx: Arc<watch::Sender<bool>>,
+    signals: Vec<ShutdownSignal>,
+    hooks: Arc<Mutex<Vec<Hook>>>,
+}
+
+impl std::fmt::Debug for ShutdownController {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ShutdownController")
+            .field("shutdown", &self.is_shutdown())
+            .field("signals", &self.signals)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for ShutdownController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShutdownController {
+    /// Create a controller listening for SIGINT and SIGTERM.
+    pub fn new() -> Self {
+        Self {
+            tx: Arc::new(watch::channel(false).0),
+            signals: vec![ShutdownSignal::Interrupt, ShutdownSignal::Terminate],
+            hooks: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Also shut down on `signal`.
+    pub fn with_signal(mut self, signal: ShutdownSignal) -> Self {
+        if !self.signals.contains(&signal) {
+            self.signals.push(signal);
+        }
+        self
+    }
+
+    /// Ignore OS signals; shutdown only happens when triggered from code.
+    pub fn without_signals(mut self) -> Self {
+        self.signals.clear();
+        self
+    }
+
+    /// Get a token that observes this controller.
+    pub fn token(&self) -> ShutdownToken {
+        ShutdownToken {
+            rx: self.tx.subscribe(),
+        }
+    }
+
+    /// Start shutdown.
+    pub fn shutdown(&self) {
+        self.tx.send_replace(true);
+    }
+
+    /// Returns `true` once shutdown has started.
+    pub fn is_shutdown(&self) -> bool {
+        *self.tx.borrow()
+    }
+
+    /// Completes when shutdown starts, from code, a signal or
+    /// [`request_shutdown`].
+    pub async fn wait(&self) {
+        let token = self.token();
+        if token.is_shutdown() {
+            return;
+        }
+
+        let mut signals: Vec<Pin<Box<dyn Future<Output = ShutdownSignal> + Send>>> = self
+            .signals
+            .iter()
+            .map(|signal| Box::pin(signal.recv()) as Pin<Box<_>>)
+            .collect();
+        let any_signal = poll_fn(|cx| {
+            for signal in &mut signals {
+                if let Poll::Ready(signal) = signal.as_mut().poll(cx) {
+                    return Poll::Ready(signal);
+                }
+            }
+            Poll::Pending
+        });
+
+        tokio::select! {
+            _ = token.cancelled() => {
+                tracing::info!("Shutdown triggered, starting graceful shutdown...");
+            },
+            signal = any_signal => {
+                tracing::info!("Received {}, starting graceful shutdown...", signal.name());
+            },
+            _ = shutdown_requested() => {
+                tracing::info!("Shutdown requested, starting graceful shutdown...");
+            },
+        }
+        self.shutdown();
+    }
+
+    /// Register a cleanup hook.
+    ///
+    /// Hooks run in registration order after the server has drained; each
+    /// is abandoned with a warning if it takes longer than `timeout`.
+    pub fn on_shutdown<F, Fut>(&self, name: impl Into<String>, timeout: Duration, hook: F)
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let hook = Hook {
+            name: name.into(),
+            timeout,
+            run: Box::new(move || Box::pin(hook()) as BoxFuture),
+        };
+        self.hooks
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(hook);
+    }
+
+    /// Run the registered cleanup hooks.
+    ///
+    /// Each hook runs at most once, even if this is called again or from
+    /// several servers sharing the controller.
+    pub async fn run_hooks(&self) {
+        let hooks = std::mem::take(&mut *self.hooks.lock().unwrap_or_else(|e| e.into_inner()));
+
+        for hook in hooks {
+            tracing::debug!(hook = %hook.name, "Running shutdown hook");
+            if tokio::time::timeout(hook.timeout, (hook.run)()).await.is_err() {
+                tracing::warn!(
+                    hook = %hook.name,
+                    timeout_secs = hook.timeout.as_secs_f64(),
+                    "Shutdown hook timed out"
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn trigger_from_code_wakes_tokens() {
+        let controller = ShutdownController::new().without_signals();
+        let token = controller.token();
+        assert!(!token.is_shutdown());
+
+        let waiter = {
+            let controller = controller.clone();
+            async move { controller.wait().await }
+        };
+        controller.clone().shutdown();
+
+        waiter.await;
+        token.cancelled().await;
+        assert!(token.is_shutdown());
+        assert!(controller.is_shutdown());
+    }
+
+    #[test]
+    fn with_signal_adds_once() {
+        let controller = ShutdownController::new()
+            .with_signal(ShutdownSignal::Quit)
+            .with_signal(ShutdownSignal::Quit);
+        assert_eq!(
+            controller.signals,
+            vec![
+                ShutdownSignal::Interrupt,
+                ShutdownSignal::Terminate,
+                ShutdownSignal::Quit
+            ]
+        );
+        assert!(controller.without_signals().signals.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn hooks_run_in_order_with_timeouts() {
+        let controller = ShutdownController::new().without_signals();
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        for (name, delay) in [("first", 0), ("stuck", 60), ("last", 0)] {
+            let order = Arc::clone(&order);
+            controller.on_shutdown(name, Duration::from_secs(5), move || async move {
+                tokio::time::sleep(Duration::from_secs(delay)).await;
+                order.lock().unwrap().push(name);
+            });
+        }
+
+        controller.run_hooks().await;
+        controller.run_hooks().await;
+        assert_eq!(*order.lock().unwrap(), vec!["first", "last"]);
+    }
+}
@@ -0,0 +1,375 @@
+//! systemd integration: socket activation and `sd_notify`.
+//!
+//! Sockets passed by a systemd socket unit (`LISTEN_FDS`, `LISTEN_FDNAMES`)
+//! are picked up by [`Listener::from_systemd`] and, through
+//! [`Listener::bind_or_inherit`], by the REST and gRPC servers. [`SdNotify`]
+//! sends `READY=1`, `STOPPING=1` and watchdog pings to `NOTIFY_SOCKET`; it is
+//! a no-op when the process was not started by systemd.
+//!
+//! Socket activation is only available on Unix; elsewhere nothing is
+//! inherited and notifications are dropped.
+
+use std::io;
+#[cfg(unix)]
+use std::os::fd::{FromRawFd, RawFd};
+use std::path::PathBuf;
+#[cfg(unix)]
+use std::sync::Mutex;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+#[cfg(unix)]
+use crate::listener::Listener;
+
+/// First file descriptor passed by systemd.
+#[cfg(unix)]
+const SD_LISTEN_FDS_START: RawFd = 3;
+
+#[cfg(unix)]
+static LISTEN_FDS: OnceLock<Mutex<Vec<ListenFd>>> = OnceLock::new();
+static NOTIFY: OnceLock<SdNotify> = OnceLock::new();
+
+/// A socket inherited through `LISTEN_FDS`.
+#[cfg(unix)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ListenFd {
+    fd: RawFd,
+    name: Option<String>,
+}
+
+/// Parse the socket activation variables.
+///
+/// Returns nothing unless `LISTEN_PID` names this process, as required by
+/// `sd_listen_fds(3)`.
+#[cfg(unix)]
+fn parse_listen_fds(
+    listen_pid: Option<&str>,
+    listen_fds: Option<&str>,
+    listen_fdnames: Option<&str>,
+    pid: u32,
+) -> Vec<ListenFd> {
+    if listen_pid.and_then(|p| p.trim().parse::<u32>().ok()) != Some(pid) {
+        return Vec::new();
+    }
+    let Some(count) = listen_fds.and_then(|n| n.trim().parse::<RawFd>().ok()) else {
+        return Vec::new();
+    };
+
+    let mut names = listen_fdnames.unwrap_or_default().split(':');
+    (0..count.max(0))
+        .map(|i| ListenFd {
+            fd: SD_LISTEN_FDS_START + i,
+            name: names
+                .next()
+                .filter(|name| !name.is_empty() && *name != "unknown")
+                .map(str::to_string),
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+fn inherited() -> &'static Mutex<Vec<ListenFd>> {
+    LISTEN_FDS.get_or_init(|| {
+        let var = |key| std::env::var(key).ok();
+        let fds = parse_listen_fds(
+            var("LISTEN_PID").as_deref(),
+            var("LISTEN_FDS").as_deref(),
+            var("LISTEN_FDNAMES").as_deref(),
+            std::process::id(),
+        );
+        for fd in &fds {
+            // Keep inherited sockets out of processes we spawn.
+            // SAFETY: fcntl on a descriptor number has no memory effects.
+            unsafe { libc::fcntl(fd.fd, libc::F_SETFD, libc::FD_CLOEXEC) };
+        }
+        Mutex::new(fds)
+    })
+}
+
+/// Remove the matching inherited socket from the pool.
+#[cfg(unix)]
+fn take_listen_fd(name: Option<&str>) -> Option<ListenFd> {
+    let mut fds = inherited().lock().unwrap_or_else(|e| e.into_inner());
+    let index = match name {
+        Some(name) => fds.iter().position(|fd| fd.name.as_deref() == Some(name))?,
+        None if fds.is_empty() => return None,
+        None => 0,
+    };
+    Some(fds.remove(index))
+}
+
+/// Adopt a listening socket descriptor as a [`Listener`].
+///
+/// # Safety
+///
+/// `fd` must be an open socket not owned by anything else.
+#[cfg(unix)]
+pub(crate) unsafe fn listener_from_fd(fd: RawFd) -> io::Result<Listener> {
+    let mut addr: libc::sockaddr_storage = std::mem::zeroed();
+    let mut len = std::mem::size_of::<libc::sockaddr_storage>() as libc::socklen_t;
+    if libc::getsockname(fd, &mut addr as *mut _ as *mut libc::sockaddr, &mut len) != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    match addr.ss_family as libc::c_int {
+        libc::AF_INET | libc::AF_INET6 => {
+            let listener = std::net::TcpListener::from_raw_fd(fd);
+            listener.set_nonblocking(true)?;
+            tokio::net::TcpListener::from_std(listener).map(Listener::Tcp)
+        }
+        libc::AF_UNIX => {
+            let listener = std::os::unix::net::UnixListener::from_raw_fd(fd);
+            listener.set_nonblocking(true)?;
+            tokio::net::UnixListener::from_std(listener).map(Listener::Unix)
+        }
+        family => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "inherited fd {} has unsupported address family {}",
+                fd, family
+            ),
+        )),
+    }
+}
+
+#[cfg(unix)]
+impl Listener {
+    /// Take a socket passed by systemd socket activation.
+    ///
+    /// With a `name`, the socket whose `FileDescriptorName=` matches is
+    /// used; without one, the first socket not yet taken. Each inherited
+    /// socket is handed out once. Returns `Ok(None)` when there is no match.
+    pub fn from_systemd(name: Option<&str>) -> io::Result<Option<Self>> {
+        match take_listen_fd(name) {
+            // SAFETY: systemd passed us ownership of the descriptor and
+            // `take_listen_fd` hands each one out only once.
+            Some(listen_fd) => unsafe { listener_from_fd(listen_fd.fd) }.map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Sends `sd_notify(3)` messages to the service manager.
+///
+/// # Example
+///
+/// ```ignore
+/// let notify = SdNotify::global();
+/// notify.ready("Listening on 0.0.0.0:3000");
+/// let _watchdog = notify.spawn_watchdog();
+/// // ...
+/// notify.stopping();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SdNotify {
+    socket: Option<PathBuf>,
+    watchdog: Option<Duration>,
+}
+
+impl SdNotify {
+    /// Notifier sending to `socket`, e.g. a fake socket in tests.
+    ///
+    /// A path starting with `@` names an abstract socket (Linux only).
+    pub fn new(socket: impl Into<PathBuf>) -> Self {
+        Self {
+            socket: Some(socket.into()),
+            watchdog: None,
+        }
+    }
+
+    /// Notifier configured from `NOTIFY_SOCKET`, `WATCHDOG_USEC` and
+    /// `WATCHDOG_PID`.
+    pub fn from_env() -> Self {
+        let var = |key| std::env::var(key).ok().filter(|v: &String| !v.is_empty());
+        let watchdog_for_us = var("WATCHDOG_PID")
+            .map(|pid| pid.parse::<u32>().ok() == Some(std::process::id()))
+            .unwrap_or(true);
+
+        Self {
+            socket: var("NOTIFY_SOCKET").map(PathBuf::from),
+            watchdog: var("WATCHDOG_USEC")
+                .and_then(|usec| usec.parse().ok())
+                .filter(|usec| *usec > 0 && watchdog_for_us)
+                .map(Duration::from_micros),
+        }
+    }
+
+    /// The process-wide notifier, configured from the environment.
+    pub fn global() -> &'static SdNotify {
+        NOTIFY.get_or_init(Self::from_env)
+    }
+
+    /// Set the watchdog timeout; pings are sent at half this interval.
+    pub fn with_watchdog(mut self, timeout: Duration) -> Self {
+        self.watchdog = Some(timeout).filter(|timeout| !timeout.is_zero());
+        self
+    }
+
+    /// Returns `true` if a notify socket is configured.
+    pub fn is_enabled(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    /// The watchdog timeout requested by the service manager.
+    pub fn watchdog_timeout(&self) -> Option<Duration> {
+        self.watchdog
+    }
+
+    /// Send a raw notification such as `"READY=1"`.
+    ///
+    /// Does nothing if no notify socket is configured.
+    pub fn notify(&self, state: &str) -> io::Result<()> {
+        let Some(socket) = &self.socket else {
+            return Ok(());
+        };
+        #[cfg(not(unix))]
+        let _ = (socket, state);
+
+        #[cfg(unix)]
+        {
+            let datagram = std::os::unix::net::UnixDatagram::unbound()?;
+            #[cfg(target_os = "linux")]
+            if let Some(name) = socket.to_str().and_then(|s| s.strip_prefix('@')) {
+                use std::os::linux::net::SocketAddrExt;
+
+                let addr = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes())?;
+                return datagram.send_to_addr(state.as_bytes(), &addr).map(|_| ());
+            }
+            datagram.send_to(state.as_bytes(), socket)?;
+        }
+        Ok(())
+    }
+
+    fn notify_or_log(&self, state: &str) {
+        if let Err(e) = self.notify(state) {
+            tracing::warn!(error = %e, state, "Failed to notify systemd");
+        }
+    }
+
+    /// Tell the service manager startup has finished.
+    pub fn ready(&self, status: &str) {
+        self.notify_or_log(&format!("READY=1\nSTATUS={}", status));
+    }
+
+    /// Tell the service manager the service is shutting down.
+    pub fn stopping(&self) {
+        self.notify_or_log("STOPPING=1");
+    }
+
+    /// Send a single watchdog ping.
+    pub fn watchdog(&self) {
+        self.notify_or_log("WATCHDOG=1");
+    }
+
+    /// Ping the watchdog at half its timeout until the guard is dropped.
+    ///
+    /// Returns `None` when no watchdog was requested.
+    pub fn spawn_watchdog(&self) -> Option<WatchdogGuard> {
+        let timeout = self.watchdog.filter(|_| self.is_enabled())?;
+        let notify = self.clone();
+        let task = tokio::spawn(async move {
+            let mut interval = tokio::time::interval(timeout / 2);
+            loop {
+                interval.tick().await;
+                notify.watchdog();
+            }
+        });
+        Some(WatchdogGuard { task })
+    }
+}
+
+/// Stops watchdog pings when dropped.
+#[derive(Debug)]
+pub struct WatchdogGuard {
+    task: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for WatchdogGuard {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::fd::IntoRawFd;
+    use std::os::unix::net::UnixDatagram;
+
+    #[test]
+    fn parses_named_fds_for_this_process() {
+        let fds = parse_listen_fds(Some("42"), Some("3"), Some("http:unknown:grpc"), 42);
+        assert_eq!(
+            fds,
+            vec![
+                ListenFd {
+                    fd: 3,
+                    name: Some("http".to_string())
+                },
+                ListenFd { fd: 4, name: None },
+                ListenFd {
+                    fd: 5,
+                    name: Some("grpc".to_string())
+                },
+            ]
+        );
+
+        assert!(parse_listen_fds(Some("41"), Some("1"), None, 42).is_empty());
+        assert!(parse_listen_fds(None, Some("1"), None, 42).is_empty());
+        assert_eq!(
+            parse_listen_fds(Some("42"), Some("1"), None, 42)[0].name,
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn adopts_tcp_and_unix_sockets() {
+        let tcp = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let listener = unsafe { listener_from_fd(tcp.into_raw_fd()) }.unwrap();
+        assert_eq!(listener.local_addr().unwrap().as_tcp(), Some(addr));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.sock");
+        let unix = std::os::unix::net::UnixListener::bind(&path).unwrap();
+        let listener = unsafe { listener_from_fd(unix.into_raw_fd()) }.unwrap();
+        assert_eq!(
+            listener.local_addr().unwrap().as_unix(),
+            Some(path.as_path())
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn notifies_fake_socket() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("notify.sock");
+        let socket = UnixDatagram::bind(&path).unwrap();
+        socket.set_nonblocking(true).unwrap();
+        let recv = || {
+            let mut buf = [0; 256];
+            let n = socket.recv(&mut buf).unwrap();
+            String::from_utf8_lossy(&buf[..n]).into_owned()
+        };
+
+        let notify = SdNotify::new(&path).with_watchdog(Duration::from_secs(10));
+        notify.ready("Listening on 127.0.0.1:3000");
+        assert_eq!(recv(), "READY=1\nSTATUS=Listening on 127.0.0.1:3000");
+
+        let guard = notify.spawn_watchdog().unwrap();
+        tokio::time::sleep(Duration::from_secs(6)).await;
+        assert_eq!(recv(), "WATCHDOG=1");
+        assert_eq!(recv(), "WATCHDOG=1");
+        drop(guard);
+
+        notify.stopping();
+        assert_eq!(recv(), "STOPPING=1");
+    }
+
+    #[test]
+    fn disabled_without_socket() {
+        let notify = SdNotify::default();
+        assert!(!notify.is_enabled());
+        notify.notify("READY=1").unwrap();
+        assert!(notify.spawn_watchdog().is_none());
+    }
+}
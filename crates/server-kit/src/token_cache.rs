@@ -0,0 +1,293 @@
+//! Cache of token validation results.
+//!
+//! Validators that check tokens against a database or an introspection
+//! endpoint are slow compared to the request they guard. The HTTP and gRPC
+//! crates wrap them in a caching validator backed by [`TokenCache`], which
+//! remembers accepted tokens for a TTL and rejected ones for a (usually
+//! shorter) negative TTL.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime};
+use tokio::time::Instant;
+
+/// Entries of one kind, indexed by token and by expiry so that the next one
+/// to evict is found without a scan.
+struct Entries<T> {
+    by_token: HashMap<String, (T, Instant)>,
+    by_expiry: BTreeSet<(Instant, String)>,
+    max: usize,
+}
+
+impl<T: Clone> Entries<T> {
+    fn new(max: usize) -> Self {
+        Self {
+            by_token: HashMap::new(),
+            by_expiry: BTreeSet::new(),
+            max,
+        }
+    }
+
+    fn get(&mut self, token: &str) -> Option<T> {
+        let (value, expires) = self.by_token.get(token)?;
+        if *expires > Instant::now() {
+            return Some(value.clone());
+        }
+        self.remove(token);
+        None
+    }
+
+    fn insert(&mut self, token: &str, value: T, expires: Instant) {
+        if self.max == 0 {
+            return;
+        }
+        self.remove(token);
+        let now = Instant::now();
+        while let Some((first, _)) = self.by_expiry.first() {
+            if self.by_token.len() < self.max && *first > now {
+                break;
+            }
+            // Expired, or the closest to expiring while full.
+            let (_, oldest) = self.by_expiry.pop_first().unwrap();
+            self.by_token.remove(&oldest);
+        }
+        self.by_expiry.insert((expires, token.to_string()));
+        self.by_token.insert(token.to_string(), (value, expires));
+    }
+
+    fn remove(&mut self, token: &str) {
+        if let Some((_, expires)) = self.by_token.remove(token) {
+            self.by_expiry.remove(&(expires, token.to_string()));
+        }
+    }
+
+    fn clear(&mut self) {
+        self.by_token.clear();
+        self.by_expiry.clear();
+    }
+}
+
+/// Validation results keyed by token, each kept for a limited time.
+///
+/// Accepted and rejected tokens are counted separately, so a flood of
+/// garbage tokens only evicts other rejections. When either limit is
+/// reached, expired entries are dropped first, then the one closest to
+/// expiring.
+pub struct TokenCache<I, E> {
+    accepted: Mutex<Entries<I>>,
+    rejected: Mutex<Entries<E>>,
+    ttl: Duration,
+    negative_ttl: Duration,
+}
+
+impl<I, E> std::fmt::Debug for TokenCache<I, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TokenCache")
+            .field("len", &self.len())
+            .field("ttl", &self.ttl)
+            .field("negative_ttl", &self.negative_ttl)
+            .field("max_entries", &self.accepted.lock().unwrap().max)
+            .field("max_negative_entries", &self.rejected.lock().unwrap().max)
+            .finish()
+    }
+}
+
+impl<I: Clone, E: Clone> TokenCache<I, E> {
+    /// Keep accepted tokens for `ttl`. Rejections are kept for 10 seconds.
+    /// At most 10 000 accepted and 1 000 rejected tokens are cached.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            accepted: Mutex::new(Entries::new(10_000)),
+            rejected: Mutex::new(Entries::new(1_000)),
+            ttl,
+            negative_ttl: Duration::from_secs(10),
+        }
+    }
+
+    /// How long rejections are kept; zero disables negative caching.
+    pub fn negative_ttl(mut self, ttl: Duration) -> Self {
+        self.negative_ttl = ttl;
+        self
+    }
+
+    /// Maximum number of accepted tokens.
+    pub fn max_entries(self, max_entries: usize) -> Self {
+        self.accepted.lock().unwrap().max = max_entries;
+        self
+    }
+
+    /// Maximum number of rejected tokens.
+    pub fn max_negative_entries(self, max_entries: usize) -> Self {
+        self.rejected.lock().unwrap().max = max_entries;
+        self
+    }
+
+    /// The cached result for `token`, if it hasn't expired.
+    pub fn get(&self, token: &str) -> Option<Result<I, E>> {
+        if let Some(identity) = self.accepted.lock().unwrap().get(token) {
+            return Some(Ok(identity));
+        }
+        self.rejected.lock().unwrap().get(token).map(Err)
+    }
+
+    /// Remember `result` for `token`.
+    pub fn insert(&self, token: &str, result: Result<I, E>) {
+        let ttl = match result {
+            Ok(_) => self.ttl,
+            Err(_) => self.negative_ttl,
+        };
+        self.insert_for(token, result, ttl);
+    }
+
+    /// Remember `result` for `token`, but not past `expires_at`, e.g. the
+    /// `exp` of the token. Nothing is cached if that time has passed.
+    pub fn insert_until(&self, token: &str, result: Result<I, E>, expires_at: SystemTime) {
+        let ttl = match result {
+            Ok(_) => self.ttl,
+            Err(_) => self.negative_ttl,
+        };
+        let left = expires_at
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        self.insert_for(token, result, ttl.min(left));
+    }
+
+    fn insert_for(&self, token: &str, result: Result<I, E>, ttl: Duration) {
+        if ttl.is_zero() {
+            return;
+        }
+        let expires = Instant::now() + ttl;
+        match result {
+            Ok(identity) => {
+                self.rejected.lock().unwrap().remove(token);
+                self.accepted
+                    .lock()
+                    .unwrap()
+                    .insert(token, identity, expires);
+            }
+            Err(error) => {
+                self.accepted.lock().unwrap().remove(token);
+                self.rejected.lock().unwrap().insert(token, error, expires);
+            }
+        }
+    }
+
+    /// Forget `token`, e.g. after it has been revoked.
+    pub fn invalidate(&self, token: &str) {
+        self.accepted.lock().unwrap().remove(token);
+        self.rejected.lock().unwrap().remove(token);
+    }
+
+    pub fn clear(&self) {
+        self.accepted.lock().unwrap().clear();
+        self.rejected.lock().unwrap().clear();
+    }
+}
+
+impl<I, E> TokenCache<I, E> {
+    /// Number of cached tokens, including expired ones not yet dropped.
+    pub fn len(&self) -> usize {
+        self.accepted.lock().unwrap().by_token.len() + self.rejected.lock().unwrap().by_token.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn results_expire_after_their_ttl() {
+        let cache = TokenCache::<&str, &str>::new(Duration::from_secs(60))
+            .negative_ttl(Duration::from_secs(5));
+        cache.insert("good", Ok("ada"));
+        cache.insert("bad", Err("invalid"));
+
+        assert_eq!(cache.get("good"), Some(Ok("ada")));
+        assert_eq!(cache.get("bad"), Some(Err("invalid")));
+        assert_eq!(cache.get("other"), None);
+
+        tokio::time::advance(Duration::from_secs(6)).await;
+        assert_eq!(cache.get("bad"), None);
+        assert_eq!(cache.get("good"), Some(Ok("ada")));
+
+        cache.invalidate("good");
+        assert_eq!(cache.get("good"), None);
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn evicts_the_entry_closest_to_expiring() {
+        let cache = TokenCache::<u32, ()>::new(Duration::from_secs(60)).max_entries(2);
+        cache.insert("a", Ok(1));
+        tokio::time::advance(Duration::from_secs(1)).await;
+        cache.insert("b", Ok(2));
+        cache.insert("c", Ok(3));
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.get("c"), Some(Ok(3)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn entries_expire_with_the_token() {
+        let cache = TokenCache::<&str, ()>::new(Duration::from_secs(60));
+        let soon = SystemTime::now() + Duration::from_secs(5);
+        cache.insert_until("short", Ok("ada"), soon);
+        cache.insert_until("long", Ok("bob"), soon + Duration::from_secs(3600));
+        let past = SystemTime::now() - Duration::from_secs(1);
+        cache.insert_until("expired", Ok("eve"), past);
+
+        assert_eq!(cache.get("short"), Some(Ok("ada")));
+        assert_eq!(cache.get("expired"), None);
+
+        tokio::time::advance(Duration::from_secs(6)).await;
+        assert_eq!(cache.get("short"), None);
+        assert_eq!(cache.get("long"), Some(Ok("bob")));
+
+        tokio::time::advance(Duration::from_secs(60)).await;
+        assert_eq!(cache.get("long"), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rejections_do_not_evict_accepted_tokens() {
+        let cache = TokenCache::<u32, ()>::new(Duration::from_secs(60))
+            .max_entries(2)
+            .max_negative_entries(2);
+        cache.insert("a", Ok(1));
+        cache.insert("b", Ok(2));
+        for i in 0..100 {
+            cache.insert(&format!("garbage-{i}"), Err(()));
+        }
+
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.get("a"), Some(Ok(1)));
+        assert_eq!(cache.get("b"), Some(Ok(2)));
+        assert_eq!(cache.get("garbage-99"), Some(Err(())));
+        assert_eq!(cache.get("garbage-0"), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drops_expired_entries_before_live_ones() {
+        let cache = TokenCache::<u32, ()>::new(Duration::from_secs(60)).max_entries(2);
+        let soon = SystemTime::now() + Duration::from_secs(1);
+        cache.insert_until("a", Ok(1), soon);
+        cache.insert("b", Ok(2));
+        tokio::time::advance(Duration::from_secs(2)).await;
+        cache.insert("c", Ok(3));
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get("b"), Some(Ok(2)));
+        assert_eq!(cache.get("c"), Some(Ok(3)));
+    }
+
+    #[test]
+    fn zero_negative_ttl_disables_negative_caching() {
+        let cache = TokenCache::<(), ()>::new(Duration::from_secs(60)).negative_ttl(Duration::ZERO);
+        cache.insert("bad", Err(()));
+        assert_eq!(cache.get("bad"), None);
+    }
+}